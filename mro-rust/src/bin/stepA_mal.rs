@@ -1,703 +1,127 @@
 extern crate mal;
 
-#[macro_use]
-extern crate lazy_static;
-
-#[macro_use]
-extern crate log;
 extern crate env_logger;
-use log::LogLevel::Trace;
+extern crate libc;
 
 use std::io::{self, Write};
-use std::collections::HashMap;
-use std::ops::Deref;
-use std::rc::Rc;
-use std::cell::RefCell;
-
 use std::env;
-use std::path::Path;
-
-use mal::reader;
-use mal::printer;
-use mal::env::{EnvType, Env, Symbol, wrapped_env_type};
-
-use mal::common::{MalData, MapKey, NativeFunction};
-use mal::common::{FnClosure, CallableFun, FunContext};
-use mal::common::{make_mal_list_from_vec, make_mal_symbol, mal_symbol_name, make_mal_list_from_slice, is_mal_list, get_wrapped_list, make_mal_string};
-use mal::common::{make_mal_vector_from_vec};
-
-use mal::core::init_ns_map;
-use mal::eval::{EvalError, MalEvalResult};
-
-fn read<'a>(input: &'a str) -> Result<MalData, String> {
-    reader::read_str(input)
-}
-
-fn call_function(env: EnvType, f: &NativeFunction, args: &[MalData]) -> Result<MalData, EvalError> {
-    debug!("call_function, f: {:?}, args: {:?}", f, args);
-
-    let callable = f.callable.clone();
-    let ctx = &FunContext { eval: Some(make_eval_closure(env)), eval2: Box::from(eval), env: None };
-    let result = callable(ctx, args);
-
-    if log_enabled!(Trace) {
-        for arg in args { trace!("arg: {:?}", arg) };
-    }
-
-    debug!("call_function, result: {:?}", result);
-    result.map_err( | e | EvalError::from(e))
-}
-
-
-fn mal_closure(env: EnvType, binds: &Vec<Symbol>, body: &MalData) -> MalData {
-    MalData::FnClosure(FnClosure::new(env, &binds, body))
-}
-
-fn eval_fn(env: EnvType, binds: & MalData, body: & MalData) -> Result<MalData, EvalError> {
-    debug!("eval_fn, binds: {:?}, body: {:?}", binds, body);
-
-    match binds {
-        &MalData::Vector(ref b, _) | &MalData::List(ref b, _) => {
-            let mut binds_vec = Vec::with_capacity(b.len());
-
-            for bind in b.iter() {
-                if let &MalData::Symbol(ref sym) = bind {
-                    binds_vec.push(sym.clone());
-                } else {
-                    return Err(EvalError::General(format!("Expected symbol for bind, got: {:?}", bind)));
-                }
-            }
+use std::thread;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
-            Ok(mal_closure(env, &binds_vec, body))
-        }
+use mal::{Interpreter, Backend};
+use mal::common::{MalData, make_mal_list_from_vec, make_mal_string};
+use mal::eval::EvalError;
+use mal::limits::Limits;
 
-        _ =>
-            Err(EvalError::General("expected vector for binds".to_string()))
-    }
-}
+// hoechstens so viele zeilen des aufrufstapels; gleiche aufeinanderfolgende eintraege (z.b. bei
+// ueberschrittener tiefe) ergeben eine zeile
+const MAX_PRINTED_FRAMES: usize = 20;
 
-fn eval_def(env: EnvType, name: Option<&MalData>, value: Option<&MalData>) -> Result<MalData, EvalError> {
-    match ( name, value ) {
-        ( None, None ) | ( None, Some(_) ) | ( Some(_), None ) =>
-            Err(EvalError::General("def! requires a name and a value".to_owned())),
-
-        ( Some(& MalData::Symbol(ref key)), Some(value) ) => {
-            eval(env.clone(), value).map( | evaluated | {
-                env.borrow_mut().set(&key, &evaluated); 
-                // debug!("def! {:?} -> {:?}/{:?}, env: {:?}", key, value, evaluated, env);
-                evaluated
-            })
-        }
+fn print_error(err: &EvalError) {
+    println!("error: {}", err);
 
-        ( key, value ) => {
-            let err_msg = format!("unhandled in def!, key: {:?}, value: {:?}", key, value);
-            Err(EvalError::General(err_msg.to_owned()))
-        }
-    }
-}
+    let stack = match err.stack() {
+        Some(stack) => stack,
+        None => return
+    };
 
-fn eval_defmacro(env: EnvType, name: Option<&MalData>, value: Option<&MalData>) -> Result<MalData, EvalError> {
-    match ( name, value ) {
-        ( Some(& MalData::Symbol(ref name)), Some(ref fnc_form) ) => {
-            let eval_res = eval(env.clone(), fnc_form);
+    let mut lines: Vec<( String, usize )> = Vec::new();
 
-            if let MalData::FnClosure(ref fnc) = eval_res? {
-                let res = MalData::FnClosure(fnc.to_macro());
+    for frame in stack.iter() {
+        let line = frame.to_string();
 
-                env.borrow_mut().set(&name, &res); 
-                Ok(res)
-            } else {
-                Err(EvalError::General("closure expected".to_owned()))
-            }
+        match lines.last_mut() {
+            Some(&mut ( ref last, ref mut count )) if *last == line => *count += 1,
+            _ => lines.push(( line, 1 ))
         }
-
-        ( None, None ) | ( None, Some(_) ) | ( Some(_), None ) =>
-            Err(EvalError::General("defmacro! requires a name and a closure".to_owned())),
-
-        ( key, value ) => {
-            let err_msg = format!("unhandled in def!, key: {:?}, value: {:?}", key, value);
-            Err(EvalError::General(err_msg.to_owned()))
-        }
-    }
-}
-
-fn is_pair(ast: &MalData) -> bool {
-    match ast {
-        &MalData::List(ref list, _) | &MalData::Vector(ref list, _) =>
-            !list.is_empty(),
-
-        _ =>
-            false
-    }
-}
-
-fn is_symbol_named(ast: &MalData, name: &str) -> bool {
-    if let &MalData::Symbol(ref sym) = ast {
-        sym == name
-    } else {
-        false
     }
-}
-
-fn quasiquote(ast: &MalData) -> Result<MalData, EvalError> {
-    debug!("> quasiquote, ast: {:?}", ast);
-
-    if !is_pair(ast) {
-        let res = make_mal_list_from_vec(vec![make_mal_symbol("quote"), ast.clone()]);
-        debug!("< quasiquote, !pair; res: {:?}", res);
-        return Ok(res);
-    } else if let Some(list) = get_wrapped_list(ast) {
-        let first = list.first().unwrap();
-        let first_as_list = if is_pair(first) { get_wrapped_list(first) } else { None };
-
-        if mal_symbol_name(first).map_or(false, |sym| sym == "unquote") {
-            let res = list.get(1).ok_or("expected form to unquote")?.clone();
-            debug!("< unquote, res: {:?}", res);
-
-            return Ok(res)
-        } else if is_pair(first) && first_as_list.map_or(false, |l| l.first().map_or(false, |f| is_symbol_named(f, "splice-unquote"))) {
-            let to_unquote = first_as_list.map( |l| l.get(1) ).ok_or("expected form to unquote")?.unwrap();
-            debug!("splice-unquote, to_unquote: {:?}", to_unquote);
 
-            let quasiquote_rest = if list.len() > 1 {
-                quasiquote(&make_mal_list_from_slice(&list[1..]))?
-            } else {
-                MalData::Nil
-            };
-
-            debug!("splice-unquote, quasiquote_rest: {:?}", quasiquote_rest);
-
-            let res = if list.len() > 1 {
-                make_mal_list_from_vec(vec![make_mal_symbol("concat"), to_unquote.clone(), quasiquote_rest])
-            } else {
-                make_mal_list_from_vec(vec![make_mal_symbol("concat"), to_unquote.clone()])
-            };
-
-            debug!("< splice-unquote, res: {:?}", res);
-
-            return Ok(res)
+    for &( ref line, count ) in lines.iter().take(MAX_PRINTED_FRAMES) {
+        if count > 1 {
+            println!("    {} (repeated {} times)", line, count);
         } else {
-            let quasiquote_first = quasiquote(&first)?;
-            debug!("quasiquote, first: {:?}", quasiquote_first);
-
-            let quasiquote_rest = quasiquote(&make_mal_list_from_slice(&list[1..]))?;
-            debug!("quasiquote, rest: {:?}", quasiquote_rest);
-
-            let res = make_mal_list_from_vec(vec![make_mal_symbol("cons"), quasiquote_first, quasiquote_rest]);
-            debug!("< quasiquote, res: {:?}", res);
-
-            return Ok(res);
+            println!("    {}", line);
         }
-    } else {
-        panic!("quasiquote, ast: {:?}", ast);
     }
-}
-
-fn mal_list_head(ast: &MalData) -> Option<&MalData> {
-    if let &MalData::List(ref list, _) = ast {
-        list.first()
-    } else {
-        None
-    }
-}
-
-fn is_macro_call(env: EnvType, ast: &MalData) -> bool {
-    let list_head = mal_list_head(ast);
-
-    let res = if let Some(& MalData::Symbol(ref sym)) = list_head {
-        Env::get(&env, sym).map_or(false, |symval| {
-            if let & MalData::FnClosure(ref fnc) = symval.as_ref() {
-                fnc.is_macro()
-            } else {
-                false
-            }
-            
-        })
-    } else {
-        false
-    };
-
-    debug!("is_macro_call, ast: {:?}, res: {:?}", ast, res);
-
-    res
-}
-
-fn mal_closure_apply(env: EnvType, closure: &FnClosure, args: &[MalData]) -> Result<MalData, EvalError> {
-    debug!("mal_closure_apply, closure: {:?}, args: {:?}", closure, args);
-
-    let fn_env = Env::new(Some(env.clone()), closure.binds.as_slice(), args)?;
-    let res = eval(wrapped_env_type(fn_env), closure.body.as_ref());
-
-    debug!("mal_closure_apply, res: {:?}", res);
-
-    res
-}
 
-fn macroexpand(env: EnvType, ast: &MalData) -> Result<MalData, EvalError> {
-    debug!("macroexpand, ast: {:?}", ast);
-
-    let mut expand_ast = ast.clone();
-
-    while is_macro_call(env.clone(), &expand_ast) {
-        let curr_ast = expand_ast.clone();  // FIXME schauen, ob auch ohne klonen moeglich
-        let list_head = { mal_list_head(&curr_ast) };
-
-        debug!("macro call, list_head: {:?}, expand_ast: {:?}\n", list_head, expand_ast);
-
-        if let Some(& MalData::Symbol(ref sym)) = list_head {
-            let expanded = Env::get(&env, sym).map( |symval| {
-                if let & MalData::FnClosure(ref fnc) = symval.as_ref() {
-                    let empty_args = vec![];
-                    let macro_args: &[MalData] = get_wrapped_list(&curr_ast).map_or(&empty_args, |l| &l[1..]);
-
-                    debug!("macro call, fnc: {:?}, args: {:?}", fnc, macro_args);
-
-                    mal_closure_apply(env.clone(), fnc, macro_args)
-                } else {
-                    panic!("in macro call, not a closure: {:?}", symval);
-                }
-            }).unwrap();
-
-            expand_ast = try!(expanded);
-        } else {
-            panic!("in macro call, expected symbol, got: {:?}", list_head);
-        };
+    if lines.len() > MAX_PRINTED_FRAMES {
+        let omitted: usize = lines[MAX_PRINTED_FRAMES..].iter().map( |&( _, count )| count ).sum();
+        println!("    ... {} more frames", omitted);
     }
-
-    Ok(expand_ast)
 }
 
-fn eval_try(env: EnvType, body_form: &MalData, catch_form: &MalData) -> MalEvalResult {
-    let eval_res = match eval(env.clone(), body_form) {
-        Ok(res) =>
-            Ok(res),
+// Ctrl-C bricht eine laufende auswertung ab; am wartenden prompt beendet erst das zweite Ctrl-C die REPL
+static EVALUATING: AtomicBool = AtomicBool::new(false);
+static IDLE_INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
 
-        Err(err) =>
-            Ok(MalData::Exception(Box::from(MalData::String(format!("{}", err)))))
-    };
-
-    debug!("eval_try, body_form: {:?},\ncatch_form: {:?}\n-> {:?}", body_form, catch_form, eval_res);
-
-    if let Ok(MalData::Exception(exc)) = eval_res {
-        if let &MalData::List(ref cfl, _) = catch_form {
-            match ( cfl.get(0), cfl.get(1), cfl.get(2) ) {
-                ( Some(catch_sym @ &MalData::Symbol(_)), Some(&MalData::Symbol(ref catch_bind)), Some(ref catch_body) ) if is_symbol_named(catch_sym, "catch*") => {
-                    let catch_env = wrapped_env_type(Env::new(Some(env.clone()), vec![catch_bind.clone()].as_slice(), &vec![*exc])?);
+const EXIT_HINT: &str = "\n(press Ctrl-C again to exit)\nuser> ";
 
-                    debug!("eval_try, catch_bodu: {:?}", catch_body);
-                    eval(catch_env, &catch_body)
-                }
-
-                _ => {
-                    Err(EvalError::from("invalid catch* form"))
-                }
-            }
-        } else {
-            Err(EvalError::from("catch* form expected"))
-        }
+// im signal-handler nur atomare operationen und async-signal-sichere aufrufe
+#[cfg(unix)]
+extern "C" fn on_sigint(_signum: libc::c_int) {
+    if EVALUATING.load(Ordering::SeqCst) {
+        mal::interrupt::interrupt();
+    } else if IDLE_INTERRUPTS.fetch_add(1, Ordering::SeqCst) > 0 {
+        unsafe { libc::_exit(130) }
     } else {
-        eval_res
-    }
-}
-
-fn eval(mut env: EnvType, ast: & MalData) -> Result<MalData, EvalError> {
-    let mut tco_ast: MalData = ast.clone();
-
-    loop {
-        let let_body: &MalData;
-
-        debug!("eval, loop; tco_ast: {:?}", tco_ast);
-
-        // macro expansion
-        if is_mal_list(&tco_ast) {
-            tco_ast = macroexpand(env.clone(), &tco_ast)?;
-
-            if !is_mal_list(&tco_ast) {
-                return eval_ast(env.clone(), &tco_ast);
-            }
-        }
-
-        match tco_ast.clone() {
-            MalData::List(ref list, _) if list.is_empty() =>
-                return Ok(tco_ast.clone()),
-
-            MalData::List(ref list, _) => {
-                // sonderformen/special forms
-                if let Some(& MalData::Symbol(ref sym)) = list.first() {
-                    match sym.as_str() {
-                        "def!" => {
-                            debug!("eval, > def!");
-                            let result = eval_def(env.clone(), list.get(1), list.get(2));
-                            debug!("eval, < def!");
-                            return result;
-                        }
-
-                        "defmacro!" => {
-                            debug!("eval, > defmacro!");
-                            let result = eval_defmacro(env.clone(), list.get(1), list.get(2));
-                            debug!("eval, < defmacro!");
-                            return result;
-                        }
-
-                        "let*" => {
-                            debug!("eval, > let*");
-                            let_body = list.get(2).unwrap();
-
-                            let let_bindings = match list.get(1) {
-                                Some(&MalData::List(ref bindings, _)) | Some(&MalData::Vector(ref bindings, _)) =>
-                                    bindings,
-                                _ =>
-                                    return Err(EvalError::General("let* bindings".to_string())) // TODO
-                            };
-
-                            debug!("let body: {:?}", let_body);
-
-                            let mut iter = let_bindings.iter();
-
-                            {
-                                // TODO binds, exprs
-                                let let_env = wrapped_env_type(Env::new(Some(env.clone()), &[], &[])?);
-
-                                loop {
-                                    match ( iter.next(), iter.next() ) {
-                                        ( Some(&MalData::Symbol(ref sym)), Some(ref def) ) => {
-                                            let evaluated_def = eval(let_env.clone(), &def.clone())?;
-
-                                            debug!("bind sym: {:?}, def: {:?} -eval-> {:?}", sym, def, evaluated_def);
-
-                                            let_env.borrow_mut().set(&sym.clone(), &evaluated_def);
-                                        }
-
-                                        ( None, None ) => break,
-
-                                        ( sym, def ) => {
-                                            let err_msg = format!("error in let* binding; sym: {:?}, def: {:?}", sym, def);
-                                            return Err(EvalError::General(err_msg))
-                                        }
-                                    }
-                                }
-
-                                // TCO: let-rumpf im folgenden schleifendurchgang evaluieren
-                                env = let_env;
-                                tco_ast = let_body.clone();
-                                continue;
-                            }
-
-                            // debug!("eval, < let*, result: {:?}", result);
-                            // return result;
-                        }
-
-                        "do" => {
-                            trace!("eval, > do");
-                            let mut result = Ok(MalData::Nil);
-                            // liste aller mittels eval_ast zu evaluierender formen, letzte form wird hier im rahmen
-                            // der TCO im folgenden schleifendurchgang evaluiert
-                            let forms = &list[1..list.len() - 1];
-                            trace!("eval_do, forms: {:?}", forms);
-
-                            result = eval_ast(env.clone(), &make_mal_list_from_vec(forms.to_vec()));
-
-                            tco_ast = list.last().unwrap().clone();  // TODO fehlerbehandlung
-                            continue;
-                        }
-
-                        "if" => {
-                            debug!("eval, > if");
-                            let cond_form = &list[1];
-                            let then_form = &list[2];
-
-                            let cond = eval(env.clone(), cond_form);
-
-                            match cond {
-                                Ok(MalData::Nil)  | Ok(MalData::False)  => {
-                                    if let Some(else_form) = list.get(3) {
-                                        tco_ast = else_form.clone()
-                                    } else {
-                                        return Ok(MalData::Nil)
-                                    }
-                                }
-
-                                Ok(_) =>
-                                    tco_ast = then_form.clone(),
-
-                                Err(err) =>
-                                    return Err(err)
-                            }
-
-                            debug!("eval, if; tco_ast: {:?}; cont", tco_ast);
-                            continue;
-                        }
-
-                        "fn*" => {
-                            debug!("eval, > fn*");
-                            let res = eval_fn(env.clone(), &list[1], &list[2]);
-                            debug!("eval, < fn*");
-                            return res;
-                        }
-
-                        "quote" => {
-                            debug!("eval, quote; arg: {:?}", &list[1]);
-                            return Ok((&list[1]).clone());
-                        }
-
-                        "quasiquote" => {
-                            debug!("eval, quasiquote");
-                            tco_ast = quasiquote(list.get(1).ok_or("form required")?)?;
-                            continue;
-                        }
-
-                        "macroexpand" => {
-                            let arg = &list[1];
-                            debug!("macroexpand, arg: {:?}", arg);
-                            return macroexpand(env.clone(), arg);
-                        }
-
-                        "try*" => {
-                            return eval_try(env.clone(), &list[1], &list[2]);
-                        }
-
-                        _ => (),
-                    }
-                }
-
-                let eval_list = eval_ast(env.clone(), &tco_ast); 
-                debug!("eval, eval_list: {:?}", eval_list);
-
-                match eval_list {
-                    Ok(MalData::List(ref l, _)) => {
-                        match l.first() {
-                            Some(&MalData::Function(ref f)) => {
-                                debug!("(fun ...), f: {:?}", f);
-                                return call_function(env, f, &l[1..]);
-                            }
-
-                            Some(&MalData::FnClosure(ref fnc)) => {
-                                debug!("(funcl ...), fnc: {:?}", fnc);
-                                tco_ast = *fnc.body.clone();
-
-                                let fn_closure = fnc;
-                                let parameters = &l[1..].to_owned();
-
-                                debug!("apply_fn_closure, cl: {:?}, parameters: {:?}", fn_closure, parameters);
-
-                                let outer_env = fn_closure.outer_env.clone();
-                                let fn_env = Env::new(Some(outer_env), fn_closure.binds.as_slice(), parameters)?;
-                                env = Rc::from(RefCell::from(fn_env));
-
-                                continue;
-                            }
-
-                            Some(el) => {
-                                let err_msg = format!("first element is not a function ({:?})", el);
-                                return Err(EvalError::General(err_msg));
-                            }
-
-                            None =>
-                                return Ok(MalData::Nil)
-                        }
-
-                    }
-
-                    Ok(_) => return Ok(MalData::Nil),
-
-                    Err(err) => {
-                        return Err(err)
-                    }
-                }
-            }
-
-            // keine liste
-            _ => {
-                let eval_res = eval_ast(env, &tco_ast);
-                debug!("eval, eval_res: {:?}", eval_res);
-                return eval_res
-            }
-        }
-    }
-}
-
-fn eval_ast(env: EnvType, ast: & MalData) -> Result<MalData, EvalError> {
-    trace!("eval_ast");
-
-    match ast {
-        & MalData::Vector(ref vec, _) => {
-            let mut eval_vec: Vec<MalData> = Vec::new();
-
-            for el in vec.deref() {
-                let res = eval(env.clone(), &el);
-
-                if res.is_err() {
-                    return res;
-                } else {
-                    eval_vec.push(res.unwrap());
-                }
-            }
-
-            debug!("eval_ast, eval_vec: {:?}", eval_vec);
-
-            Ok(make_mal_vector_from_vec(&eval_vec))
-        }
-
-        & MalData::Map(ref map, _) => {
-            let mut eval_map: HashMap<MapKey, MalData> = HashMap::new();
-
-            let mut iter = map.into_iter();
-
-            loop {
-                match iter.next() {
-                    None => break,
-
-                    Some(( k, v )) => {
-                        eval_map.insert(k.clone(), eval(env.clone(), &v).unwrap());
-                    }
-                }
-            }
-
-            debug!("eval_ast, eval_map: {:?}", eval_map);
-
-            Ok(MalData::Map(eval_map, None))
-        }
-
-        & MalData::Symbol(ref sym) => {
-            Env::get(&env, sym)
-                .and_then( | v | Some(v.deref().clone()))
-                .ok_or(EvalError::General(format!("'{}' not found", sym)))
-        }
-
-        & MalData::List(ref lst, _) => {
-            let mut eval_list: Vec<MalData> = Vec::new();
-
-            for el in lst.deref() {
-                let res = eval(env.clone(), &el);
-
-                if res.is_err() {
-                    return res;
-                } else {
-                    eval_list.push(res.unwrap());
-                }
-            }
-
-            debug!("eval_ast, eval_list: {:?}", eval_list);
-            Ok(make_mal_list_from_vec(eval_list))
-        }
-
-        _ => {
-            debug!("eval_ast, ast: {:?}", ast);
-            Ok(ast.clone())
-        }
+        unsafe { libc::write(1, EXIT_HINT.as_ptr() as *const libc::c_void, EXIT_HINT.len()) };
     }
 }
 
-fn print(input: &MalData) -> String {
-    printer::pr_str(input, true)
-}
-
-fn env_insert_fun(env: EnvType, name: &str, fun: Rc<CallableFun>) {
-        env.borrow_mut().set(&name.to_owned(), &MalData::Function(NativeFunction::new(name, fun)));
+#[cfg(unix)]
+fn install_sigint_handler() {
+    let handler: extern "C" fn(libc::c_int) = on_sigint;
+    unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
 }
 
+#[cfg(not(unix))]
+fn install_sigint_handler() {}
 
-fn rep<'a, 'i>(repl_env: EnvType, input: &'i str) -> Result<String, EvalError> {
-    let read_out = read(input)?;
-    trace!("rep, read_out: {:?}", read_out);
-
-    if let MalData::Nothing = read_out {
-        return Ok("".to_owned());
-    }
-
-    debug!("rep, > eval");
-    let res = eval(repl_env.clone(), &read_out).map(|r| print(&r));
-    debug!("rep, < eval, res: {:?}", res);
-
-    res
-}
-
-fn make_eval_closure(env_rc: EnvType) -> Rc<CallableFun> {
-    let eval_closure: Rc<CallableFun> = Rc::from(move |fun_ctx: &FunContext, args: &[MalData]| { eval(env_rc.clone(), &args[0]).map_err(|e| format!("{}", e)) });
-
-    eval_closure
-}
-
-// ordnet in der umgebung dem symbol 'eval' eine closure zu, die eval mit der uebergebenen umgebung (REPL-umgebung) und dem ersten
-// parameter der closure aufruft.
-//
-// diese verrenkung mittels separater funktion schien noetig fuer ein erfolgreiches kompilieren. beim versuch, direkt in main
-// eine entsprechende closure zu installieren verweigerte sich rust mit dem hinweis, dass die closure evtl. laenger als env_rc lebt.
-fn insert_eval_closure(env_rc: Rc<RefCell<Env>>) {
-    let dest_env = env_rc.clone();
-
-    env_insert_fun(dest_env, "eval", make_eval_closure(env_rc.clone()));
-}
+// der evaluator rekursiert nativ, die voreingestellte tiefe soll auch in debug-builds erreichbar
+// sein; die auswertung darf die haelfte davon verbrauchen
+const STACK_SIZE: usize = 64 * 1024 * 1024;
 
 fn main() {
     env_logger::init().unwrap();
 
-    let repl_env = Env::new(None, &[], &[]).unwrap();
-    let ns_map = init_ns_map();
-
-    let env_rc = Rc::from(RefCell::from(repl_env));
+    let repl = thread::Builder::new().stack_size(STACK_SIZE).spawn(run).unwrap();
+    repl.join().unwrap();
+}
 
-    for (sym, fun) in ns_map {
-        env_insert_fun(env_rc.clone(), sym, fun);
+// MAL_BACKEND=vm waehlt die bytecode-vm statt des evaluators
+fn backend() -> Backend {
+    match env::var("MAL_BACKEND") {
+        Ok(ref name) if name == "vm" => Backend::Vm,
+        _ => Backend::Evaluator
     }
+}
 
-    // 'eval' einer closure zuordnen, die eval mit REPL-env aufruft
-    insert_eval_closure(env_rc.clone());
-
-    // not
-    rep(env_rc.clone(), "(def! not (fn* [a] (if a false true)))");
-
-    // load-file
-    rep(env_rc.clone(), "(def! load-file (fn* (f) (eval (read-string (str \"(do \" (slurp f) \")\")))))");
-
-    // cond
-    rep(env_rc.clone(), "(defmacro! cond (fn* (& xs) (if (> (count xs) 0) (list 'if (first xs) (if (> (count xs) 1) (nth xs 1) (throw \"odd number of forms to cond\")) (cons 'cond (rest (rest xs)))))))");
-
-    rep(env_rc.clone(), "(def! *gensym-counter* (atom 0))");
-    rep(env_rc.clone(), "(def! gensym (fn* [] (symbol (str \"G__\" (swap! *gensym-counter* (fn* [x] (+ 1 x)))))))");
-    // or
-    rep(env_rc.clone(), "(defmacro! or (fn* (& xs) (if (empty? xs) nil (if (= 1 (count xs)) (first xs) (let* (condvar (gensym)) `(let* (~condvar ~(first xs)) (if ~condvar ~condvar (or ~@(rest xs)))))))))");
-    // rep(env_rc.clone(), "(defmacro! or (fn* (& xs) (if (empty? xs) nil (if (= 1 (count xs)) (first xs) `(let* (or_FIXME ~(first xs)) (if or_FIXME or_FIXME (or ~@(rest xs))))))))");
-
-    let empty_list = make_mal_list_from_vec(vec![]);
-
-    env_rc.borrow_mut().set(&"*host-language*".to_string(), &make_mal_string("mro-rust"));
+fn run() {
+    let interpreter = Interpreter::new()
+        .with_backend(backend())
+        .with_limits(Limits::default().with_max_stack(STACK_SIZE / 2));
 
     if env::args().len() >= 2 {
-        if env::args().len() > 2 {
-            let argv_args: Vec<MalData> = env::args().skip(2).map(|a| MalData::String(a)).collect();
-            let argv = make_mal_list_from_vec(argv_args.clone());
-
-            debug!("argv_args: {:?}, argv: {:?}", &argv_args, &argv);
-            env_rc.borrow_mut().set(&"*ARGV*".to_owned(), &argv);
-        } else {
-            env_rc.borrow_mut().set(&"*ARGV*".to_owned(), &empty_list);
-        }
+        let argv_args: Vec<MalData> = env::args().skip(2).map( |arg| make_mal_string(&arg) ).collect();
+        interpreter.define("*ARGV*", make_mal_list_from_vec(argv_args));
 
         let path_arg = env::args().nth(1).unwrap();
-        let path = Path::new(&path_arg);  // TODO existenz pruefen
-        debug!(r#"(load-file "{}")"#, path.to_str().unwrap());
 
-        let load_file_form = format!(r#"(load-file "{}")"#, path.to_str().unwrap());
-        let res = rep(env_rc, &load_file_form);
-
-        match res {
+        match interpreter.call("load-file", &[make_mal_string(&path_arg)]) {
             Ok(result) =>
-                println!("{}", result),
+                println!("{}", mal::printer::pr_str(&result, true)),
 
-            Err(err) => 
-                println!("error: {}", err)
+            Err(err) =>
+                print_error(&err)
         }
 
         return;
-    } else {
-        env_rc.borrow_mut().set(&"*ARGV*".to_owned(), &empty_list);
     }
 
-    loop {
-        let mut input = &mut String::new();
+    install_sigint_handler();
 
-        trace!("main loop");
+    loop {
+        let mut input = String::new();
 
         print!("user> ");
         #[allow(unused_must_use)]
@@ -708,13 +132,19 @@ fn main() {
             Ok(_) => {}
         }
 
-        match rep(env_rc.clone(), &input) {
+        IDLE_INTERRUPTS.store(0, Ordering::SeqCst);
+        mal::interrupt::clear();
+
+        EVALUATING.store(true, Ordering::SeqCst);
+        let res = interpreter.rep(&input);
+        EVALUATING.store(false, Ordering::SeqCst);
+
+        match res {
             Ok(ref e) if e.is_empty() => {}
 
             Ok(res) => println!("{}", res),
 
-            Err(err) => println!("error: {}", err),
+            Err(err) => print_error(&err),
         }
     }
 }
-
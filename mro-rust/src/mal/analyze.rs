@@ -0,0 +1,913 @@
+// analyse einer form vor der auswertung: makros werden expandiert, sonderformen erkannt und die
+// form in einen baum von Nodes uebersetzt, den eval::eval_node ausfuehrt. der rumpf einer fn* wird
+// dabei einmal analysiert und von allen daraus erzeugten closures geteilt.
+//
+// makros werden zum zeitpunkt der analyse expandiert; ein aufruf eines erst spaeter definierten
+// makros wird vom evaluator erkannt und dann zur laufzeit expandiert.
+//
+// eine expansion gilt, solange ihr name das makro bezeichnet, mit dem sie erzeugt wurde; nach einer
+// neudefinition (siehe macros_changed) wird die form neu analysiert.
+//
+// lokale namen werden dabei zu (tiefe, slot) in den zur laufzeit entstehenden umgebungen
+// aufgeloest, alle uebrigen auf die globale tabelle (siehe Var).
+//
+// recur ist nur in endposition eines loop* derselben funktion erlaubt; ein recur an anderer stelle
+// ist ein fehler der analyse.
+
+use std::fmt;
+use std::rc::Rc;
+use std::slice;
+use std::cell::{Cell, RefCell};
+
+use env::{self, EnvType, Env, Symbol, SymbolId};
+use common::{MalData, MapKey, make_mal_list_from_vec, make_mal_symbol};
+use eval::{self, EvalError, CoreError};
+use printer::pr_str;
+use destructure;
+use dynamic;
+use namespace;
+use protocol;
+
+// aufgeloester name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Var {
+    // (tiefe, slot): slot der umgebung `tiefe` ebenen ueber der aktuellen
+    Local(usize, usize, SymbolId),
+    Global(SymbolId),
+
+    // suche nach dem namen, fuer formen, die in einer lokalen umgebung unbekannten aufbaus
+    // analysiert werden (spaet expandierte makros)
+    Dynamic(SymbolId)
+}
+
+impl Var {
+    pub fn id(&self) -> SymbolId {
+        match *self {
+            Var::Local(_, _, id) | Var::Global(id) | Var::Dynamic(id) => id
+        }
+    }
+
+    pub fn get(&self, env: &EnvType) -> Option<MalData> {
+        match *self {
+            Var::Local(depth, slot, id) => Env::get_slot(env, depth, slot, id),
+            Var::Global(id) => Env::get_global(env, id),
+            Var::Dynamic(id) => Env::get_id(env, id)
+        }
+    }
+}
+
+pub enum Node {
+    Const(MalData),
+    Symbol(Var),
+    Vector(Vec<Rc<Node>>),
+    Map(Vec<( MapKey, Rc<Node> )>),
+    Def(Var, Rc<Node>),
+    DefMacro(Var, Rc<Node>),
+    Let(Vec<( Var, Rc<Node> )>, Rc<Node>),
+    Loop(Rc<Loop>),
+    Recur(Recur),
+    Do(Vec<Rc<Node>>, Rc<Node>),
+    If(Rc<Node>, Rc<Node>, Option<Rc<Node>>),
+    Fn(Rc<Lambda>),
+    Macroexpand(MalData),
+    Try(Try),
+    Call(Call),
+    Expansion(Rc<Expansion>),
+
+    // fehler beim expandieren eines makros im rumpf eines try*, geworfen erst bei der ausfuehrung
+    Raise(EvalError)
+}
+
+// analysierte fn* mit einer oder mehreren arities; `name` ist der name aus (fn* name ...)
+pub struct Lambda {
+    pub name: Option<Rc<str>>,
+    pub arities: Vec<Arity>
+}
+
+// parameter und rumpf einer arity; `form` ist der rumpf vor der analyse
+pub struct Arity {
+    pub binds: Vec<Symbol>,
+    pub params: Vec<SymbolId>,
+    pub variadic: bool,
+    pub body: Rc<Node>,
+    pub form: MalData
+}
+
+impl Arity {
+    // anzahl der festen parameter
+    pub fn required(&self) -> usize {
+        if self.variadic { self.params.len() - 1 } else { self.params.len() }
+    }
+}
+
+impl Lambda {
+    // die arity fuer `argc` argumente; der fehler nennt die funktion noch "fn*"
+    pub fn arity(&self, argc: usize) -> Result<&Arity, CoreError> {
+        let shapes = self.arities.iter().map( |arity| ( arity.required(), arity.variadic ) );
+
+        match env::select_arity(shapes.clone(), argc) {
+            Some(i) => Ok(&self.arities[i]),
+            None => Err(CoreError::arities("fn*", shapes, argc))
+        }
+    }
+}
+
+impl fmt::Debug for Lambda {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let arities = self.arities.iter().map( |arity| ( &arity.binds, &arity.form ) ).collect::<Vec<_>>();
+        write!(f, "Lambda {{ name: {:?}, arities: {:?} }}", self.name, arities)
+    }
+}
+
+// (loop* [bindings...] body); die bindungen liegen wie bei let* in einer neuen umgebung
+pub struct Loop {
+    pub bindings: Vec<( Var, Rc<Node> )>,
+    pub body: Rc<Node>,
+
+    // eine im loop* erzeugte closure benutzt dessen namen; jeder durchlauf braucht dann eine
+    // eigene umgebung, statt die namen an ort und stelle neu zu binden
+    pub captured: bool
+}
+
+// (recur args...) in endposition des innersten loop*; `depth` ist der abstand zu dessen umgebung,
+// `slots` sind die slots der bindungen
+pub struct Recur {
+    pub depth: usize,
+    pub slots: Vec<( usize, SymbolId )>,
+    pub args: Vec<Rc<Node>>
+}
+
+pub struct Try {
+    pub body: Rc<Node>,
+    pub catch: Option<( Var, Rc<Node> )>,
+    pub finally: Option<Rc<Node>>
+}
+
+// `form` ist die urspruengliche liste, fuer den aufrufstapel und die expansion spaeter makros
+pub struct Call {
+    pub form: MalData,
+    pub fun: Rc<Node>,
+    pub args: Vec<Rc<Node>>,
+    pub late: LateExpansion
+}
+
+pub struct Expansion {
+    pub form: MalData,
+    pub node: Rc<Node>,
+    name: Var,
+    mac: MalData,
+
+    // stand von macros_changed, zu dem `mac` zuletzt als aktuell bestaetigt wurde
+    checked: Cell<u64>,
+
+    redone: RefCell<Option<Rc<Node>>>
+}
+
+impl Expansion {
+    pub fn is_current(&self, env: &EnvType) -> bool {
+        let epoch = macro_epoch();
+
+        if self.checked.get() == epoch {
+            return true;
+        }
+
+        let current = self.name.get(env).is_some_and( |value| same_fn(&value, &self.mac) );
+
+        if current {
+            self.checked.set(epoch);
+        }
+
+        current
+    }
+
+    // die form, neu analysiert fuer die auswertung in `env`
+    pub fn reanalyzed(&self, env: &EnvType) -> Result<Rc<Node>, EvalError> {
+        if let Some(ref node) = *self.redone.borrow() {
+            return Ok(node.clone());
+        }
+
+        let node = Rc::new(analyze(env, &self.form)?);
+        *self.redone.borrow_mut() = Some(node.clone());
+
+        Ok(node)
+    }
+}
+
+// expansion einer aufrufstelle, deren makro erst nach der analyse definiert wurde; sie wird mit dem
+// makro zusammen gemerkt und bei einem anderen makro neu erzeugt
+#[derive(Default)]
+pub struct LateExpansion(RefCell<Option<( MalData, Rc<Node> )>>);
+
+impl LateExpansion {
+    pub fn node(&self, env: &EnvType, mac: &MalData, form: &MalData) -> Result<Rc<Node>, EvalError> {
+        if let Some(( ref cached, ref node )) = *self.0.borrow() {
+            if same_fn(cached, mac) {
+                return Ok(node.clone());
+            }
+        }
+
+        let macro_args = match *form {
+            MalData::List(ref list, _) => &list[1..],
+            _ => &[]
+        };
+
+        let expanded = eval::apply(env.clone(), mac, macro_args)?;
+        let node = Rc::new(analyze(env, &expanded)?);
+
+        *self.0.borrow_mut() = Some(( mac.clone(), node.clone() ));
+
+        Ok(node)
+    }
+}
+
+fn same_fn(a: &MalData, b: &MalData) -> bool {
+    match ( a, b ) {
+        ( MalData::FnClosure(a), MalData::FnClosure(b) ) => a.same(b),
+        _ => false
+    }
+}
+
+thread_local! {
+    static MACRO_EPOCH: Cell<u64> = const { Cell::new(0) };
+}
+
+fn macro_epoch() -> u64 {
+    MACRO_EPOCH.with(Cell::get)
+}
+
+// ein makro wurde (neu) definiert oder ueberschrieben; alle expansionen pruefen ihr makro erneut
+pub fn macros_changed() {
+    MACRO_EPOCH.with( |epoch| epoch.set(epoch.get() + 1) );
+}
+
+// analysiert `form` fuer die auswertung in `env`
+pub fn analyze(env: &EnvType, form: &MalData) -> Result<Node, EvalError> {
+    let global = env.borrow().is_root();
+    Analyzer { env: env.clone(), global, scopes: Vec::new(), recur: None, in_loop: false, catching: false }.analyze(form)
+}
+
+// liefert die elemente nach dem kopf, falls form eine liste der gestalt (name ...) ist
+pub fn clause_named<'a>(form: &'a MalData, name: &str) -> Option<&'a [MalData]> {
+    match *form {
+        MalData::List(ref list, _) if list.first().is_some_and( |head| is_symbol_named(head, name) ) =>
+            Some(&list[1..]),
+
+        _ =>
+            None
+    }
+}
+
+fn is_symbol_named(form: &MalData, name: &str) -> bool {
+    if let MalData::Symbol(ref sym) = *form { &**sym == name } else { false }
+}
+
+struct Analyzer {
+    env: EnvType,
+
+    // `env` ist die aeusserste umgebung; sonst sind nicht lokal gebundene namen Var::Dynamic
+    global: bool,
+
+    // die um die form herum entstehenden lokalen umgebungen, die innerste zuletzt. lokale namen
+    // verdecken gleichnamige makros der umgebung.
+    scopes: Vec<Scope>,
+
+    // der loop*, in dessen endposition die gerade analysierte form steht
+    recur: Option<Rc<RecurTarget>>,
+
+    // innerhalb eines loop* derselben funktion, fuer die fehlermeldung eines recur
+    in_loop: bool,
+
+    // im rumpf eines try* mit catch* derselben funktion; ein fehler beim expandieren eines makros
+    // wird dort erst bei der ausfuehrung geworfen, damit ihn catch* faengt
+    catching: bool
+}
+
+struct RecurTarget {
+    // index der umgebung des loop* in `scopes`
+    scope: usize,
+    slots: Vec<( usize, SymbolId )>
+}
+
+// bindungen einer zur laufzeit entstehenden umgebung
+struct Scope {
+    // der rumpf einer fn* wird erst beim aufruf ausgewertet
+    is_fn: bool,
+
+    // (name, slot, noch nicht gebunden); ein name kann mehrfach, mit demselben slot, vorkommen
+    names: Vec<( SymbolId, usize, bool )>,
+    slots: usize,
+
+    // ein name wurde in einer darin erzeugten closure benutzt
+    captured: Cell<bool>
+}
+
+impl Scope {
+    fn new(is_fn: bool) -> Scope {
+        Scope { is_fn, names: Vec::new(), slots: 0, captured: Cell::new(false) }
+    }
+
+    // gibt den index des eintrags und den slot zurueck; wie Env::set_id erhaelt ein schon
+    // gebundener name denselben slot
+    fn declare(&mut self, id: SymbolId, pending: bool) -> ( usize, usize ) {
+        let slot = match self.names.iter().find( |&&( name, _, _ )| name == id ) {
+            Some(&( _, slot, _ )) => slot,
+            None => {
+                self.slots += 1;
+                self.slots - 1
+            }
+        };
+
+        self.names.push(( id, slot, pending ));
+        ( self.names.len() - 1, slot )
+    }
+
+    // die von einem def! in `forms` gebundenen namen gelten von anfang an als noch nicht
+    // gebundene namen der umgebung; eine vorher erzeugte closure sieht so die spaetere bindung
+    fn declare_defined(&mut self, forms: &[MalData]) {
+        for form in forms {
+            let list = match *form {
+                MalData::List(ref list, _) | MalData::Vector(ref list, _) => list,
+                _ => continue
+            };
+
+            match list.first() {
+                Some(MalData::Symbol(head)) if &**head == "def!" || &**head == "defmacro!" => {
+                    if let Some(MalData::Symbol(name)) = list.get(1) {
+                        self.declare(SymbolId::intern(name), true);
+                    }
+
+                    self.declare_defined(&list[2..]);
+                }
+
+                // diese formen binden in einer eigenen umgebung oder werten nicht aus
+                Some(MalData::Symbol(head)) if ["fn*", "let*", "loop*", "catch*", "quote", "quasiquote", "macroexpand"].contains(&&**head) =>
+                    (),
+
+                _ =>
+                    self.declare_defined(list)
+            }
+        }
+    }
+}
+
+impl Analyzer {
+    fn analyze(&mut self, form: &MalData) -> Result<Node, EvalError> {
+        if let Some(( name, mac )) = self.macro_for(form) {
+            return self.analyze_expansion(form, name, mac);
+        }
+
+        let list = match *form {
+            MalData::Symbol(ref sym) =>
+                return Ok(Node::Symbol(self.resolve(SymbolId::intern(sym)))),
+
+            MalData::Vector(ref vec, _) =>
+                return Ok(Node::Vector(self.analyze_all(vec)?)),
+
+            MalData::Map(ref map, _) => {
+                let mut entries = Vec::with_capacity(map.len());
+
+                for ( key, value ) in map.iter() {
+                    entries.push(( key.clone(), Rc::new(self.analyze_operand(value)?) ));
+                }
+
+                return Ok(Node::Map(entries));
+            }
+
+            MalData::List(ref list, _) if !list.is_empty() =>
+                list.clone(),
+
+            _ =>
+                return Ok(Node::Const(form.clone()))
+        };
+
+        if let MalData::Symbol(ref sym) = list[0] {
+            match &**sym {
+                "def!" =>
+                    return self.analyze_def("def!", &list[1..]).map( |( name, value )| Node::Def(name, value) ),
+
+                "defmacro!" =>
+                    return self.analyze_def("defmacro!", &list[1..]).map( |( name, value )| Node::DefMacro(name, value) ),
+
+                "let*" =>
+                    return self.analyze_let(&list[1..]),
+
+                "loop*" =>
+                    return self.analyze_loop(&list[1..]),
+
+                "recur" =>
+                    return self.analyze_recur(&list[1..]).map(Node::Recur),
+
+                "do" =>
+                    return self.analyze_do(&list[1..]),
+
+                "if" =>
+                    return self.analyze_if(&list[1..]),
+
+                "fn*" =>
+                    return self.analyze_fn_form(&list[1..]),
+
+                "quote" =>
+                    return Ok(Node::Const(list.get(1).ok_or("form required")?.clone())),
+
+                "quasiquote" =>
+                    return self.analyze(&eval::quasiquote(list.get(1).ok_or("form required")?)?),
+
+                "macroexpand" =>
+                    return Ok(Node::Macroexpand(list.get(1).ok_or("form required")?.clone())),
+
+                "try*" =>
+                    return self.analyze_try(&list[1..]).map(Node::Try),
+
+                "binding" =>
+                    return self.analyze_operand(&dynamic::binding_form(&self.env, &list[1..])?),
+
+                "ns" =>
+                    return self.analyze(&namespace::ns_form(&list[1..])?),
+
+                "defprotocol" =>
+                    return self.analyze(&protocol::defprotocol_form(&list[1..])?),
+
+                "extend-type" =>
+                    return self.analyze(&protocol::extend_type_form(&list[1..])?),
+
+                "extend-protocol" =>
+                    return self.analyze(&protocol::extend_protocol_form(&list[1..])?),
+
+                _ => ()
+            }
+        }
+
+        Ok(Node::Call(Call {
+            form: form.clone(),
+            fun: Rc::new(self.analyze_operand(&list[0])?),
+            args: self.analyze_all(&list[1..])?,
+            late: LateExpansion::default()
+        }))
+    }
+
+    // ein noch nicht gebundener name einer umgebung bezeichnet in den werten ihrer bindungen den
+    // aeusseren namen, in darin erzeugten closures aber schon ihn selbst (rekursive lokale fns)
+    fn resolve(&self, id: SymbolId) -> Var {
+        let mut deferred = false;
+
+        for ( depth, scope ) in self.scopes.iter().rev().enumerate() {
+            let found = scope.names.iter().rev().find( |&&( name, _, pending )| name == id && (deferred || !pending) );
+
+            if let Some(&( _, slot, _ )) = found {
+                if deferred {
+                    scope.captured.set(true);
+                }
+
+                return Var::Local(depth, slot, id);
+            }
+
+            deferred = deferred || scope.is_fn;
+        }
+
+        if self.global { Var::Global(id) } else { Var::Dynamic(id) }
+    }
+
+    fn analyze_all(&mut self, forms: &[MalData]) -> Result<Vec<Rc<Node>>, EvalError> {
+        forms.iter().map( |form| self.analyze_operand(form).map(Rc::new) ).collect()
+    }
+
+    // eine form, deren wert noch weiterverwendet wird, steht nicht in endposition
+    fn analyze_operand(&mut self, form: &MalData) -> Result<Node, EvalError> {
+        let recur = self.recur.take();
+        let res = self.analyze(form);
+        self.recur = recur;
+
+        res
+    }
+
+    // makro, das ein nicht lokal verdecktes symbol am kopf der liste bezeichnet
+    fn macro_for(&self, form: &MalData) -> Option<( Var, MalData )> {
+        let sym = match *form {
+            MalData::List(ref list, _) => match list.first() {
+                Some(MalData::Symbol(sym)) => sym,
+                _ => return None
+            },
+
+            _ => return None
+        };
+
+        let name = self.resolve(SymbolId::intern(sym));
+
+        if let Var::Local(..) = name {
+            return None;
+        }
+
+        name.get(&self.env).and_then( |value| match value {
+            MalData::FnClosure(ref fnc) if fnc.is_macro() => Some(( name, value.clone() )),
+            _ => None
+        })
+    }
+
+    fn analyze_expansion(&mut self, form: &MalData, name: Var, mac: MalData) -> Result<Node, EvalError> {
+        let expanded = match *form {
+            MalData::List(ref list, _) => eval::apply(self.env.clone(), &mac, &list[1..]),
+            _ => unreachable!()
+        };
+
+        let expanded = match expanded {
+            Err(err) if self.catching && err.is_catchable() => return Ok(Node::Raise(err)),
+            res => res?
+        };
+
+        let node = Rc::new(self.analyze(&expanded)?);
+
+        Ok(Node::Expansion(Rc::new(Expansion {
+            form: form.clone(), node, name, mac, checked: Cell::new(macro_epoch()), redone: RefCell::new(None)
+        })))
+    }
+
+    // analysiert `body` in einer neuen umgebung mit den namen `binds`
+    fn analyze_scoped(&mut self, is_fn: bool, binds: &[SymbolId], body: &MalData) -> Result<Node, EvalError> {
+        let mut scope = Scope::new(is_fn);
+
+        for bind in binds {
+            scope.declare(*bind, false);
+        }
+
+        scope.declare_defined(slice::from_ref(body));
+
+        self.scopes.push(scope);
+        let res = self.analyze(body);
+        self.scopes.pop();
+
+        res
+    }
+
+    // ein def! innerhalb einer lokalen umgebung bindet dort; der name gilt erst nach der zuweisung
+    fn analyze_def(&mut self, special: &str, args: &[MalData]) -> Result<( Var, Rc<Node> ), EvalError> {
+        // (def! ^:dynamic name value): der name wird schon jetzt als dynamisch gekennzeichnet,
+        // damit ihn ein binding in den folgenden formen binden kann
+        if let Some(( name, meta )) = args.first().and_then( |name| clause_named(name, "with-meta") ).and_then(name_with_meta) {
+            if !dynamic::is_dynamic_meta(meta) {
+                return Err(EvalError::General(format!("{}: unsupported metadata on {}", special, name)));
+            }
+
+            if special != "def!" || !self.scopes.is_empty() || !self.global {
+                return Err(EvalError::General(format!("{}: only global vars can be dynamic, {} is not", special, name)));
+            }
+
+            Env::set_dynamic(&self.env, SymbolId::intern(name));
+
+            let args = [MalData::Symbol(name.clone()), args.get(1).cloned().unwrap_or(MalData::Nil)];
+            return self.analyze_def(special, &args);
+        }
+
+        match ( args.first(), args.get(1) ) {
+            ( Some(MalData::Symbol(name)), Some(value) ) => {
+                let id = SymbolId::intern(name);
+
+                let ( entry, slot ) = match self.scopes.last_mut() {
+                    Some(scope) => scope.declare(id, true),
+                    None => {
+                        let var = if self.global { Var::Global(id) } else { Var::Dynamic(id) };
+                        return Ok(( var, Rc::new(self.analyze_operand(value)?) ));
+                    }
+                };
+
+                let value = self.analyze_operand(value)?;
+                self.scopes.last_mut().unwrap().names[entry].2 = false;
+
+                Ok(( Var::Local(0, slot, id), Rc::new(value) ))
+            }
+
+            ( Some(key), Some(value) ) =>
+                Err(EvalError::General(format!("unhandled in {}, key: {:?}, value: {:?}", special, key, value))),
+
+            _ =>
+                Err(EvalError::General(format!("{} requires a name and a value", special)))
+        }
+    }
+
+    fn analyze_let(&mut self, args: &[MalData]) -> Result<Node, EvalError> {
+        let let_bindings = match args.first() {
+            Some(&MalData::List(ref bindings, _)) | Some(&MalData::Vector(ref bindings, _)) =>
+                destructure::bindings(bindings)?,
+
+            _ =>
+                return Err(EvalError::General("let* bindings".to_string()))
+        };
+
+        // die gebundenen namen gelten in den folgenden bindungen und im rumpf
+        let mut scope = Scope::new(false);
+        scope.declare_defined(args);
+        self.scopes.push(scope);
+
+        let res = self.analyze_bindings("let*", &let_bindings).and_then( |bindings| {
+            let body = self.analyze(args.get(1).unwrap_or(&MalData::Nil))?;
+            Ok(Node::Let(bindings, Rc::new(body)))
+        });
+
+        self.scopes.pop();
+
+        res
+    }
+
+    // (loop* [bindings...] body): wie let*, der rumpf steht in endposition fuer recur
+    fn analyze_loop(&mut self, args: &[MalData]) -> Result<Node, EvalError> {
+        let loop_bindings = match args.first() {
+            Some(&MalData::List(ref bindings, _)) | Some(&MalData::Vector(ref bindings, _)) =>
+                bindings,
+
+            _ =>
+                return Err(EvalError::General("loop* bindings".to_string()))
+        };
+
+        if let Some(form) = destructure::loop_form(loop_bindings, args.get(1).unwrap_or(&MalData::Nil)) {
+            return self.analyze(&form);
+        }
+
+        let mut scope = Scope::new(false);
+        scope.declare_defined(args);
+        self.scopes.push(scope);
+
+        let res = self.analyze_bindings("loop*", loop_bindings).and_then( |bindings| {
+            let slots = bindings.iter().map( |( var, _ )| match *var {
+                Var::Local(_, slot, id) => ( slot, id ),
+                _ => unreachable!()
+            }).collect();
+
+            let target = RecurTarget { scope: self.scopes.len() - 1, slots };
+            let outer = ( self.recur.replace(Rc::new(target)), self.in_loop );
+            self.in_loop = true;
+
+            let body = self.analyze(args.get(1).unwrap_or(&MalData::Nil));
+            ( self.recur, self.in_loop ) = outer;
+
+            let captured = self.scopes.last().unwrap().captured.get();
+            Ok(Node::Loop(Rc::new(Loop { bindings, body: Rc::new(body?), captured })))
+        });
+
+        self.scopes.pop();
+
+        res
+    }
+
+    fn analyze_recur(&mut self, args: &[MalData]) -> Result<Recur, EvalError> {
+        let target = match self.recur {
+            Some(ref target) => target.clone(),
+            None if self.in_loop => return Err(EvalError::from("recur must be in tail position of loop*")),
+            None => return Err(EvalError::from("recur outside of loop*"))
+        };
+
+        if args.len() != target.slots.len() {
+            return Err(EvalError::General(format!("recur: wrong number of arguments, expected {}, got {}", target.slots.len(), args.len())));
+        }
+
+        Ok(Recur {
+            depth: self.scopes.len() - 1 - target.scope,
+            slots: target.slots.clone(),
+            args: self.analyze_all(args)?
+        })
+    }
+
+    fn analyze_bindings(&mut self, special: &str, let_bindings: &[MalData]) -> Result<Vec<( Var, Rc<Node> )>, EvalError> {
+        let mut declared = Vec::with_capacity(let_bindings.len() / 2);
+
+        for pair in let_bindings.chunks(2) {
+            match *pair {
+                [MalData::Symbol(ref sym), ref def] => {
+                    let id = SymbolId::intern(sym);
+                    let ( entry, slot ) = self.scopes.last_mut().unwrap().declare(id, true);
+
+                    declared.push(( entry, Var::Local(0, slot, id), def ));
+                }
+
+                [ref name] =>
+                    return Err(EvalError::General(format!("{}: binding {} has no value", special, pr_str(name, true)))),
+
+                _ =>
+                    return Err(EvalError::General(format!("{}: binding name must be a symbol, got {}", special, pr_str(&pair[0], true))))
+            }
+        }
+
+        let mut bindings = Vec::with_capacity(declared.len());
+
+        for ( entry, var, def ) in declared {
+            let value = self.analyze_operand(def)?;
+
+            self.scopes.last_mut().unwrap().names[entry].2 = false;
+            bindings.push(( var, Rc::new(value) ));
+        }
+
+        Ok(bindings)
+    }
+
+    fn analyze_do(&mut self, forms: &[MalData]) -> Result<Node, EvalError> {
+        match forms.split_last() {
+            None =>
+                Ok(Node::Const(MalData::Nil)),
+
+            Some(( last, [] )) =>
+                self.analyze(last),
+
+            Some(( last, init )) =>
+                Ok(Node::Do(self.analyze_all(init)?, Rc::new(self.analyze(last)?)))
+        }
+    }
+
+    fn analyze_if(&mut self, args: &[MalData]) -> Result<Node, EvalError> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(EvalError::General("if requires a condition, a then form and an optional else form".to_owned()));
+        }
+
+        let else_node = match args.get(2) {
+            Some(else_form) => Some(Rc::new(self.analyze(else_form)?)),
+            None => None
+        };
+
+        Ok(Node::If(Rc::new(self.analyze_operand(&args[0])?), Rc::new(self.analyze(&args[1])?), else_node))
+    }
+
+    // (fn* name ...) wird zu (let* [name (fn* ...)] name): der name bezeichnet im rumpf die
+    // closure selbst
+    fn analyze_fn_form(&mut self, args: &[MalData]) -> Result<Node, EvalError> {
+        let name = match args.first() {
+            Some(MalData::Symbol(name)) => name.clone(),
+            _ => return self.analyze_fn(None, args).map(Node::Fn)
+        };
+
+        let id = SymbolId::intern(&name);
+        let mut scope = Scope::new(false);
+        let ( _, slot ) = scope.declare(id, true);
+
+        self.scopes.push(scope);
+        let lambda = self.analyze_fn(Some(name), &args[1..]);
+        self.scopes.pop();
+
+        let var = Var::Local(0, slot, id);
+        Ok(Node::Let(vec![( var, Rc::new(Node::Fn(lambda?)) )], Rc::new(Node::Symbol(var))))
+    }
+
+    // ([params] body) oder eine oder mehrere arities ([params] body) ...
+    fn analyze_fn(&mut self, name: Option<Rc<str>>, args: &[MalData]) -> Result<Rc<Lambda>, EvalError> {
+        let clauses: Vec<&[MalData]> = if args.first().is_some_and(is_arity_clause) {
+            args.iter().map( |clause| match *clause {
+                MalData::List(ref list, _) if is_arity_clause(clause) => Ok(&list[..]),
+                _ => Err(EvalError::General(format!("fn*: expected arity clause ([params] body), got {}", clause.type_name())))
+            }).collect::<Result<_, _>>()?
+        } else {
+            vec![args]
+        };
+
+        let arities = clauses.into_iter().map( |clause| self.analyze_arity(clause) ).collect::<Result<Vec<Arity>, EvalError>>()?;
+
+        check_arities(&arities)?;
+
+        Ok(Rc::new(Lambda { name, arities }))
+    }
+
+    fn analyze_arity(&mut self, args: &[MalData]) -> Result<Arity, EvalError> {
+        let ( binds, patterns ) = match args.first() {
+            Some(&MalData::Vector(ref b, _)) | Some(&MalData::List(ref b, _)) =>
+                destructure::params(b)?,
+
+            _ =>
+                return Err(EvalError::General("expected vector for binds".to_string()))
+        };
+
+        let ( params, variadic ) = env::params(&binds)?;
+
+        // die muster unter den parametern zerlegt ein let* um den rumpf
+        let form = args.get(1).cloned().unwrap_or(MalData::Nil);
+        let body_form = if patterns.is_empty() {
+            form.clone()
+        } else {
+            make_mal_list_from_vec(vec![make_mal_symbol("let*"), MalData::Vector(Rc::new(patterns), None), form.clone()])
+        };
+
+        // recur und catch* gelten nur innerhalb derselben funktion
+        let outer = ( self.recur.take(), self.in_loop, self.catching );
+        self.in_loop = false;
+        self.catching = false;
+
+        let body = self.analyze_scoped(true, &params, &body_form);
+        ( self.recur, self.in_loop, self.catching ) = outer;
+
+        let body = body?;
+
+        Ok(Arity { binds, params, variadic, body: Rc::new(body), form })
+    }
+
+    // (try* body... [(catch* sym handler...)] [(finally* cleanup...)])
+    fn analyze_try(&mut self, forms: &[MalData]) -> Result<Try, EvalError> {
+        let mut body_forms = forms;
+
+        let finally_forms = match body_forms.last().and_then( |form| clause_named(form, "finally*") ) {
+            Some(cleanup) => {
+                body_forms = &body_forms[..body_forms.len() - 1];
+                Some(cleanup)
+            }
+
+            None =>
+                None
+        };
+
+        let catch_clause = match body_forms.last().and_then( |form| clause_named(form, "catch*") ) {
+            Some(clause) => {
+                body_forms = &body_forms[..body_forms.len() - 1];
+
+                match clause.split_first() {
+                    Some(( MalData::Symbol(catch_bind), handler )) =>
+                        Some(( catch_bind, handler )),
+
+                    _ =>
+                        return Err(EvalError::from("invalid catch* form, expected (catch* symbol handler...)"))
+                }
+            }
+
+            None =>
+                None
+        };
+
+        if body_forms.iter().any( |form| clause_named(form, "catch*").is_some() || clause_named(form, "finally*").is_some() ) {
+            return Err(EvalError::from("try*: catch* and finally* must be the last forms, in that order"));
+        }
+
+        // recur ueber die grenzen eines try* hinweg ist nicht erlaubt
+        let recur = self.recur.take();
+        let res = self.analyze_try_clauses(body_forms, catch_clause, finally_forms);
+        self.recur = recur;
+
+        res
+    }
+
+    fn analyze_try_clauses(&mut self, body_forms: &[MalData], catch_clause: Option<( &Rc<str>, &[MalData] )>, finally_forms: Option<&[MalData]>) -> Result<Try, EvalError> {
+        // einen fehler beim expandieren eines makros im rumpf faengt catch* wie jeden anderen
+        // fehler des rumpfs; fehlerhafte sonderformen bleiben fehler der analyse
+        let catching = self.catching;
+        self.catching = catch_clause.is_some();
+
+        let body = self.analyze_do(body_forms);
+        self.catching = catching;
+
+        let body = Rc::new(body?);
+
+        let catch = match catch_clause {
+            Some(( catch_bind, handler )) => {
+                let handler = do_form(handler);
+                let id = SymbolId::intern(catch_bind);
+                Some(( Var::Local(0, 0, id), Rc::new(self.analyze_scoped(false, &[id], &handler)?) ))
+            }
+
+            None =>
+                None
+        };
+
+        let finally = match finally_forms {
+            Some(cleanup) => Some(Rc::new(self.analyze_do(cleanup)?)),
+            None => None
+        };
+
+        Ok(Try { body, catch, finally })
+    }
+}
+
+// (do forms...) als eine form
+fn do_form(forms: &[MalData]) -> MalData {
+    make_mal_list_from_vec(Some(make_mal_symbol("do")).into_iter().chain(forms.iter().cloned()).collect())
+}
+
+// ([params] body) in einer fn* mit mehreren arities
+fn is_arity_clause(form: &MalData) -> bool {
+    match *form {
+        MalData::List(ref list, _) => matches!(list.first(), Some(MalData::Vector(..))),
+        _ => false
+    }
+}
+
+// hoechstens eine variadische arity, die nicht weniger feste parameter hat als die uebrigen, und
+// keine zwei festen mit gleich vielen parametern
+fn check_arities(arities: &[Arity]) -> Result<(), EvalError> {
+    let variadic: Vec<&Arity> = arities.iter().filter( |arity| arity.variadic ).collect();
+
+    if variadic.len() > 1 {
+        return Err(EvalError::General("fn*: more than one variadic arity".to_owned()));
+    }
+
+    for ( i, arity ) in arities.iter().enumerate().filter( |( _, arity )| !arity.variadic ) {
+        let required = arity.required();
+
+        if arities[..i].iter().any( |other| !other.variadic && other.required() == required ) {
+            return Err(EvalError::General(format!("fn*: more than one arity with {} parameters", required)));
+        }
+
+        if variadic.first().is_some_and( |v| v.required() < required ) {
+            return Err(EvalError::General(format!("fn*: fixed arity with {} parameters has more parameters than the variadic arity", required)));
+        }
+    }
+
+    Ok(())
+}
+
+// (with-meta name meta), gelesen aus ^meta name
+fn name_with_meta(args: &[MalData]) -> Option<( &Rc<str>, &MalData )> {
+    match *args {
+        [MalData::Symbol(ref name), ref meta] => Some(( name, meta )),
+        _ => None
+    }
+}
@@ -0,0 +1,168 @@
+// zugriffsrechte eines interpreters auf den host; core-funktionen, die eine fehlende capability
+// benoetigen, bleiben installiert, liefern beim aufruf aber einen CoreError::CapabilityDenied.
+// Process erlaubt das lesen der umgebung des prozesses, ohne sie ignoriert der loader MAL_PATH
+//
+//     let capabilities = Capabilities::none().with(Capability::FsRead).allow_path("/srv/scripts");
+//     let interpreter = Interpreter::with_capabilities(capabilities);
+
+use std::collections::HashSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use common::{MalData, CallableFun, FunContext};
+use eval::CoreError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    FsRead,
+    FsWrite,
+    Stdin,
+    Stdout,
+    Process,
+    Time,
+    Eval
+}
+
+impl Capability {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Capability::FsRead => "fs-read",
+            Capability::FsWrite => "fs-write",
+            Capability::Stdin => "stdin",
+            Capability::Stdout => "stdout",
+            Capability::Process => "process",
+            Capability::Time => "time",
+            Capability::Eval => "eval"
+        }
+    }
+
+    pub fn all() -> Vec<Capability> {
+        vec![Capability::FsRead, Capability::FsWrite, Capability::Stdin, Capability::Stdout,
+             Capability::Process, Capability::Time, Capability::Eval]
+    }
+
+    // capability, die eine core-funktion benoetigt
+    pub fn required_by(fun: &str) -> Option<Capability> {
+        match fun {
+            "slurp" => Some(Capability::FsRead),
+            "spit" => Some(Capability::FsWrite),
+            "readline" => Some(Capability::Stdin),
+            "prn" | "println" => Some(Capability::Stdout),
+            "time-ms" => Some(Capability::Time),
+            "eval" => Some(Capability::Eval),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    granted: HashSet<Capability>,
+
+    // None: jeder pfad erlaubt, ansonsten nur pfade unterhalb der eintraege
+    allowed_paths: Option<Vec<PathBuf>>
+}
+
+impl Capabilities {
+    pub fn all() -> Capabilities {
+        Capabilities { granted: Capability::all().into_iter().collect(), allowed_paths: None }
+    }
+
+    pub fn none() -> Capabilities {
+        Capabilities { granted: HashSet::new(), allowed_paths: None }
+    }
+
+    pub fn with(mut self, capability: Capability) -> Capabilities {
+        self.granted.insert(capability);
+        self
+    }
+
+    pub fn without(mut self, capability: Capability) -> Capabilities {
+        self.granted.remove(&capability);
+        self
+    }
+
+    // beschraenkt dateizugriffe auf `path` und die darunter liegenden pfade, mehrfach aufrufbar
+    pub fn allow_path<P: AsRef<Path>>(mut self, path: P) -> Capabilities {
+        self.allowed_paths.get_or_insert_with(Vec::new).push(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn has(&self, capability: Capability) -> bool {
+        self.granted.contains(&capability)
+    }
+
+    pub fn check(&self, fun: &str, capability: Capability) -> Result<(), CoreError> {
+        if self.has(capability) {
+            Ok(())
+        } else {
+            Err(CoreError::CapabilityDenied { fun: fun.to_owned(), message: capability.name().to_owned() })
+        }
+    }
+
+    pub fn check_path(&self, fun: &str, path: &str) -> Result<(), CoreError> {
+        let allowed = match self.allowed_paths {
+            None =>
+                return Ok(()),
+
+            Some(ref allowed) =>
+                allowed
+        };
+
+        let denied = || CoreError::CapabilityDenied { fun: fun.to_owned(), message: format!("{} is outside the allowed paths", path) };
+        let path = resolve_path(Path::new(path)).map_err( |_| denied() )?;
+
+        if allowed.iter().filter_map( |root| root.canonicalize().ok() ).any( |root| path.starts_with(root) ) {
+            Ok(())
+        } else {
+            Err(denied())
+        }
+    }
+
+    // core-funktion `name` mit der pruefung der benoetigten capability (und ggf. des pfades) umgeben
+    pub fn guard(&self, name: &str, fun: Rc<CallableFun>) -> Rc<CallableFun> {
+        let capability = match Capability::required_by(name) {
+            Some(capability) if !self.has(capability) || self.allowed_paths.is_some() =>
+                capability,
+
+            _ =>
+                return fun
+        };
+
+        let capabilities = self.clone();
+        let fun_name = name.to_owned();
+
+        Rc::new(move |ctx: &FunContext, args: &[MalData]| {
+            capabilities.check(&fun_name, capability)?;
+
+            if let ( Capability::FsRead, Some(&MalData::String(ref path)) ) |
+                   ( Capability::FsWrite, Some(&MalData::String(ref path)) ) = ( capability, args.first() ) {
+                capabilities.check_path(&fun_name, path)?;
+            }
+
+            fun(ctx, args)
+        })
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities::all()
+    }
+}
+
+// kanonischer pfad; fuer noch nicht vorhandene dateien (spit) der des verzeichnisses plus dateiname
+fn resolve_path(path: &Path) -> io::Result<PathBuf> {
+    path.canonicalize().or_else( |err| {
+        match ( path.parent(), path.file_name() ) {
+            ( Some(parent), Some(file_name) ) => {
+                let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+                parent.canonicalize().map( |dir| dir.join(file_name) )
+            }
+
+            _ =>
+                Err(err)
+        }
+    })
+}
@@ -1,13 +1,17 @@
+use std::any::Any;
 use std::fmt;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use env::{Symbol, EnvType, Env};
-use eval::EvalError;
+use env::{EnvType, Env};
+use analyze::Lambda;
+use vm::Proto;
+use gc;
+use eval::{EvalError, CoreError, CallStack};
 
 pub trait MalFun: fmt::Debug {
-    fn apply(&self, args: &[MalData]) -> Result<MalData, String>;
+    fn apply(&self, args: &[MalData]) -> Result<MalData, EvalError>;
 }
 
 // fn eval(mut env: EnvType, ast: & MalData) -> Result<MalData, EvalError> {
@@ -19,60 +23,197 @@ pub struct FunContext {
     pub env: Option<Rc<Env>>
 }
 
-pub type CallableFun = Fn(&FunContext, &[MalData]) -> Result<MalData, String>;
+pub type CallableFun = Fn(&FunContext, &[MalData]) -> Result<MalData, EvalError>;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum MapKey {
     True,
     False,
-    String(String),
-    Symbol(String),
-    Keyword(String),
-    Number(i32),
+    String(Rc<str>),
+    Symbol(Rc<str>),
+    Keyword(Rc<str>),
+    Number(i64),
 }
 
-type MalDataMetaType = Box<MalData>;
+type MalDataMetaType = Rc<MalData>;
+
+// ausfuehrbarer rumpf einer closure
+#[derive(Clone)]
+pub enum FnCode {
+    // Node-baum fuer den evaluator, ausgewertet in einer umgebung unterhalb von outer_env
+    Tree(Rc<Lambda>),
+
+    // uebersetzter rumpf fuer die vm mit den eingefangenen werten
+    Bytecode(Rc<Proto>, Rc<Vec<MalData>>)
+}
 
 #[derive(Clone)]
 pub struct FnClosure {
     pub outer_env: EnvType,
-    pub binds: Vec<Symbol>,
-    pub body: Box<MalData>,
+    pub code: FnCode,
     pub is_macro: bool,
+    pub name: Option<Rc<str>>,
     meta: Option<MalDataMetaType>
 }
 
 impl fmt::Debug for FnClosure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "FnClosure {{ binds: {:?}, body: {:?} }}", self.binds, self.body)
+        match self.code {
+            FnCode::Tree(ref lambda) =>
+                write!(f, "FnClosure {{ {:?} }}", lambda),
+
+            FnCode::Bytecode(ref proto, _) =>
+                write!(f, "FnClosure {{ binds: {:?}, body: {:?}, compiled }}", proto.binds(), proto.form())
+        }
     }
 }
 
 
 impl FnClosure {
-    pub fn new(outer_env: EnvType, binds: &Vec<Symbol>, body: &MalData) -> FnClosure {
-        FnClosure { outer_env: outer_env, binds: binds.clone(), body: Box::new(body.clone()), is_macro: false, meta: None }
+    pub fn new(outer_env: EnvType, lambda: Rc<Lambda>) -> FnClosure {
+        gc::track_env(&outer_env);
+        let name = lambda.name.clone();
+        FnClosure { outer_env, code: FnCode::Tree(lambda), is_macro: false, name, meta: None }
+    }
+
+    pub fn compiled(outer_env: EnvType, proto: Rc<Proto>, upvalues: Rc<Vec<MalData>>) -> FnClosure {
+        gc::track_env(&outer_env);
+        let name = proto.name();
+        FnClosure { outer_env, code: FnCode::Bytecode(proto, upvalues), is_macro: false, name, meta: None }
     }
 
     pub fn to_macro(&self) -> FnClosure {
-        FnClosure { outer_env: self.outer_env.clone(), binds: self.binds.clone(), body: Box::new((*self.body).clone()),
-                    is_macro: true, meta: self.meta.clone() }
+        FnClosure { is_macro: true, ..self.clone() }
     }
 
     pub fn with_meta(&self, meta: &MalData) -> FnClosure {
-        FnClosure { outer_env: self.outer_env.clone(), binds: self.binds.clone(), body: self.body.clone(), is_macro: false,
-                    meta: Some(Box::from(meta.clone())) }
+        FnClosure { is_macro: false, meta: Some(Rc::from(meta.clone())), ..self.clone() }
+    }
+
+    pub fn with_name(&self, name: &str) -> FnClosure {
+        FnClosure { name: Some(Rc::from(name)), ..self.clone() }
     }
 
     pub fn is_macro(&self) -> bool {
         self.is_macro
     }
 
+    // dieselbe funktion, unabhaengig von name und metadaten
+    pub fn same(&self, other: &FnClosure) -> bool {
+        let same_code = match ( &self.code, &other.code ) {
+            ( FnCode::Tree(a), FnCode::Tree(b) ) => Rc::ptr_eq(a, b),
+            ( FnCode::Bytecode(a, x), FnCode::Bytecode(b, y) ) => Rc::ptr_eq(a, b) && Rc::ptr_eq(x, y),
+            _ => false
+        };
+
+        same_code && Rc::ptr_eq(&self.outer_env, &other.outer_env) && self.is_macro == other.is_macro
+    }
+
+    pub fn get_meta(&self) -> Option<MalDataMetaType> {
+        self.meta.clone()
+    }
+}
+
+#[derive(Debug)]
+pub struct ExInfo {
+    pub message: String,
+    pub data: MalData,
+    pub cause: Option<MalData>,
+    pub stack: RefCell<Option<CallStack>>
+}
+
+type OpaquePrinter = Rc<Fn(&Any) -> String>;
+type OpaqueChildren = Rc<Fn(&Any, &mut VisitFun)>;
+
+pub type VisitFun<'a> = FnMut(&MalData) + 'a;
+
+// objekt des einbettenden programms, das unveraendert durch mal-code gereicht wird; kopien teilen
+// sich den wert, gleichheit ist identitaet
+#[derive(Clone)]
+pub struct Opaque {
+    type_name: Rc<str>,
+    value: Rc<Any>,
+    printer: Option<OpaquePrinter>,
+    children: Option<OpaqueChildren>,
+    meta: Option<MalDataMetaType>
+}
+
+impl Opaque {
+    pub fn new<T: Any>(type_name: &str, value: T) -> Opaque {
+        Opaque { type_name: Rc::from(type_name), value: Rc::new(value), printer: None, children: None, meta: None }
+    }
+
+    // darstellung fuer pr-str und str, ohne printer #<type_name>
+    pub fn with_printer<T: Any, F: Fn(&T) -> String + 'static>(&self, printer: F) -> Opaque {
+        let printer = move |value: &Any| value.downcast_ref::<T>().map_or_else( || "#<opaque>".to_owned(), &printer);
+
+        Opaque { printer: Some(Rc::new(printer)), ..self.clone() }
+    }
+
+    // die mal-werte, die der rust-wert haelt; ohne sie sieht der zyklensammler (siehe gc) keine
+    // zyklen, die durch den wert fuehren
+    pub fn with_children<T: Any, F: Fn(&T, &mut VisitFun) + 'static>(&self, children: F) -> Opaque {
+        let children = move |value: &Any, visit: &mut VisitFun| {
+            if let Some(value) = value.downcast_ref::<T>() {
+                children(value, visit);
+            }
+        };
+
+        Opaque { children: Some(Rc::new(children)), ..self.clone() }
+    }
+
+    pub fn visit_children(&self, visit: &mut VisitFun) {
+        if let Some(ref children) = self.children {
+            children(self.value.as_ref(), visit);
+        }
+    }
+
+    // der geteilte wert selbst, fuer den zyklensammler
+    pub fn shared(&self) -> &Rc<Any> {
+        &self.value
+    }
+
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.value.downcast_ref::<T>()
+    }
+
+    pub fn downcast<T: Any>(&self) -> Option<Rc<T>> {
+        self.value.clone().downcast::<T>().ok()
+    }
+
+    pub fn print(&self) -> String {
+        self.printer.as_ref().map_or_else( || format!("#<{}>", self.type_name), |printer| printer(self.value.as_ref()) )
+    }
+
+    pub fn with_meta(&self, meta: &MalData) -> Opaque {
+        Opaque { meta: Some(Rc::from(meta.clone())), ..self.clone() }
+    }
+
     pub fn get_meta(&self) -> Option<MalDataMetaType> {
         self.meta.clone()
     }
 }
 
+impl fmt::Debug for Opaque {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Opaque {{ type_name: {:?} }}", self.type_name)
+    }
+}
+
+impl PartialEq for Opaque {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+}
+
 type MalMapType = HashMap<MapKey, MalData>;
 
 // #[derive(Debug, Clone, PartialEq)]
@@ -82,17 +223,40 @@ pub enum MalData {
     Nil,
     True,
     False,
-    String(String),
-    Symbol(String),
-    Keyword(String),
-    Number(i32),
+    String(Rc<str>),
+    Symbol(Rc<str>),
+    Keyword(Rc<str>),
+    Number(i64),
     List(Rc<Vec<MalData>>, Option<MalDataMetaType>),
     Vector(Rc<Vec<MalData>>, Option<MalDataMetaType>),
-    Map(MalMapType, Option<MalDataMetaType>),
+    Map(Rc<MalMapType>, Option<MalDataMetaType>),
     Atom(Rc<RefCell<MalData>>),
     Function(NativeFunction),
     FnClosure(FnClosure),
-    Exception(Box<MalData>),
+    ExInfo(Rc<ExInfo>),
+    Opaque(Opaque),
+}
+
+impl MalData {
+    // typname fuer fehlermeldungen
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            MalData::Nothing => "nothing",
+            MalData::Nil => "nil",
+            MalData::True | MalData::False => "boolean",
+            MalData::String(_) => "string",
+            MalData::Symbol(_) => "symbol",
+            MalData::Keyword(_) => "keyword",
+            MalData::Number(_) => "number",
+            MalData::List(_, _) => "list",
+            MalData::Vector(_, _) => "vector",
+            MalData::Map(_, _) => "map",
+            MalData::Atom(_) => "atom",
+            MalData::Function(_) | MalData::FnClosure(_) => "fn",
+            MalData::ExInfo(_) => "ex-info",
+            MalData::Opaque(_) => "opaque",
+        }
+    }
 }
 
 impl PartialEq for MalData {
@@ -149,6 +313,12 @@ impl PartialEq for MalData {
             ( &MalData::FnClosure(ref f1), &MalData::FnClosure(ref f2) ) =>
                 f1 == f2,
 
+            ( &MalData::ExInfo(ref e1), &MalData::ExInfo(ref e2) ) =>
+                Rc::ptr_eq(e1, e2),
+
+            ( &MalData::Opaque(ref o1), &MalData::Opaque(ref o2) ) =>
+                o1 == o2,
+
             _ => {
                 debug!("eq, default, self: {:?}, other: {:?} -> false", self, other);
                 false
@@ -181,9 +351,17 @@ impl PartialEq for FnClosure {
 
 #[derive(Clone)]
 pub struct NativeFunction {
-    name: String,
+    name: Rc<str>,
     pub callable: Rc<CallableFun>,
-    meta: Option<MalDataMetaType>
+    meta: Option<MalDataMetaType>,
+
+    // zustand hinter der funktion, den andere core-funktionen erreichen (z.b. die methoden
+    // einer multimethode)
+    state: Option<Opaque>,
+
+    // hilfsfunktion des interpreters (z.b. beim zerlegen von bindungen), ohne eintrag im
+    // aufrufstapel
+    internal: bool
 }
 
 impl fmt::Debug for NativeFunction {
@@ -197,14 +375,40 @@ impl fmt::Debug for NativeFunction {
 impl NativeFunction {
     pub fn new(name: &str, callable: Rc<CallableFun>) -> NativeFunction {
         NativeFunction {
-            name: name.to_owned(),
+            name: Rc::from(name),
             callable: callable,
-            meta: None
+            meta: None,
+            state: None,
+            internal: false
         }
     }
 
+    pub fn internal(self) -> NativeFunction {
+        NativeFunction { internal: true, ..self }
+    }
+
+    pub fn is_internal(&self) -> bool {
+        self.internal
+    }
+
+    pub fn with_state(self, state: Opaque) -> NativeFunction {
+        NativeFunction { state: Some(state), ..self }
+    }
+
+    pub fn state<T: Any>(&self) -> Option<Rc<T>> {
+        self.state.as_ref().and_then(Opaque::downcast)
+    }
+
+    pub fn state_opaque(&self) -> Option<&Opaque> {
+        self.state.as_ref()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     pub fn with_meta(&self, meta: &MalData) -> NativeFunction {
-        NativeFunction { name: self.name.clone(), callable: self.callable.clone(), meta: Some(Box::from(meta.clone())) }
+        NativeFunction { meta: Some(Rc::from(meta.clone())), ..self.clone() }
     }
 
     pub fn get_meta(&self) -> Option<MalDataMetaType> {
@@ -223,9 +427,11 @@ pub fn is_list_like(value: &MalData) -> bool {
     }
 }
 
-pub fn are_lists_equal(l1: &MalData, l2: &MalData) -> Result<bool, String> {
-    if !is_list_like(l1) || !is_list_like(l2) {
-        return Err(format!("l1 und l2 muessen listenartig sein (l1: {:?}, l2: {:?})", l1, l2));
+pub fn are_lists_equal(l1: &MalData, l2: &MalData) -> Result<bool, CoreError> {
+    if !is_list_like(l1) {
+        return Err(CoreError::type_error("=", 1, "a list or vector", l1));
+    } else if !is_list_like(l2) {
+        return Err(CoreError::type_error("=", 2, "a list or vector", l2));
     }
 
     match ( l1, l2 ) {
@@ -252,7 +458,7 @@ pub fn are_lists_equal(l1: &MalData, l2: &MalData) -> Result<bool, String> {
 
 
 pub fn make_mal_string(string: &str) -> MalData {
-    MalData::String(string.to_owned())
+    MalData::String(Rc::from(string))
 }
 
 pub fn is_mal_string(ast: &MalData) -> bool {
@@ -260,7 +466,7 @@ pub fn is_mal_string(ast: &MalData) -> bool {
 }
 
 pub fn make_mal_symbol(sym: &str) -> MalData {
-    MalData::Symbol(sym.to_string())
+    MalData::Symbol(Rc::from(sym))
 }
 
 pub fn make_mal_list_from_iter(iter: &mut Iterator<Item=&MalData>) -> MalData {
@@ -276,7 +482,7 @@ pub fn make_mal_list_from_vec(vec: Vec<MalData>) -> MalData {
 }
 
 pub fn make_mal_list_from_vec_with_meta(vec: &Vec<MalData>, meta: &MalData) -> MalData {
-    MalData::List(Rc::from(vec.clone()), Some(Box::from(meta.clone())))
+    MalData::List(Rc::from(vec.clone()), Some(Rc::from(meta.clone())))
 }
 
 pub fn make_mal_list_from_slice(slice: &[MalData]) -> MalData {
@@ -288,13 +494,21 @@ pub fn make_mal_vector_from_slice(slice: &[MalData]) -> MalData {
 }
 
 pub fn make_mal_vector_from_vec_with_meta(vec: &Vec<MalData>, meta: &MalData) -> MalData {
-    MalData::Vector(Rc::from(vec.clone()), Some(Box::from(meta.clone())))
+    MalData::Vector(Rc::from(vec.clone()), Some(Rc::from(meta.clone())))
 }
 
 pub fn make_mal_vector_from_vec(vec: &Vec<MalData>) -> MalData {
     MalData::Vector(Rc::from(vec.clone()), None)
 }
 
+// atome koennen sich ueber ihren wert selbst erreichen und sind daher kandidaten des zyklensammlers
+pub fn make_mal_atom(value: MalData) -> MalData {
+    let atom = Rc::new(RefCell::new(value));
+    gc::track_atom(&atom);
+
+    MalData::Atom(atom)
+}
+
 pub fn is_mal_symbol(ast: &MalData) -> bool {
     if let &MalData::Symbol(_) = ast { true } else { false }
 }
@@ -322,7 +536,7 @@ pub fn mal_symbol_name(ast: &MalData) -> Option<String> {
 }
 
 pub fn make_mal_keyword(kw: &str) -> MalData {
-    MalData::Keyword(format!("\u{29e}{}", kw)) 
+    MalData::Keyword(Rc::from(format!("\u{29e}{}", kw)))
 }
 
 pub fn is_mal_keyword(value: &MalData) -> bool {
@@ -365,7 +579,7 @@ pub fn mapkey_for(value: &MalData) -> Result<MapKey, String> {
             Ok(MapKey::String(string.clone())),
 
         MalData::Symbol(ref string) =>
-            Ok(MapKey::Symbol(string.to_string())),
+            Ok(MapKey::Symbol(string.clone())),
 
         MalData::Keyword(ref string) =>
             Ok(MapKey::Keyword(string.clone())),
@@ -378,6 +592,29 @@ pub fn mapkey_for(value: &MalData) -> Result<MapKey, String> {
     }
 }
 
+// umkehrung von mapkey_for
+pub fn mal_value_for(key: &MapKey) -> MalData {
+    match *key {
+        MapKey::String(ref string) =>
+            MalData::String(string.clone()),
+
+        MapKey::Symbol(ref symbol) =>
+            MalData::Symbol(symbol.clone()),
+
+        MapKey::Keyword(ref kw) =>
+            MalData::Keyword(kw.clone()),
+
+        MapKey::Number(number) =>
+            MalData::Number(number),
+
+        MapKey::True =>
+            MalData::True,
+
+        MapKey::False =>
+            MalData::False
+    }
+}
+
 pub fn make_hashmap_from_kv_list(iter: &mut Iterator<Item=&MalData>) -> Result<HashMap<MapKey, MalData>, String> {
     let mut map: HashMap<MapKey, MalData> = HashMap::new();
     // let mut iter = kvs.iter();
@@ -402,9 +639,17 @@ pub fn make_hashmap_from_kv_list(iter: &mut Iterator<Item=&MalData>) -> Result<H
 }
 
 pub fn make_mal_map_from_kv_list(iter: &mut Iterator<Item=&MalData>) -> Result<MalData, String> {
-    Ok(MalData::Map(make_hashmap_from_kv_list(iter)?, None))
+    Ok(MalData::Map(Rc::new(make_hashmap_from_kv_list(iter)?), None))
+}
+
+pub fn make_mal_opaque<T: Any>(type_name: &str, value: T) -> MalData {
+    MalData::Opaque(Opaque::new(type_name, value))
+}
+
+pub fn make_mal_ex_info(message: &str, data: MalData, cause: Option<MalData>) -> MalData {
+    MalData::ExInfo(Rc::from(ExInfo { message: message.to_owned(), data, cause, stack: RefCell::new(None) }))
 }
 
-pub fn make_mal_map_from_map_with_meta(map: &MalMapType, meta: &MalData) -> Result<MalData, String> {
-    Ok(MalData::Map(map.clone(), Some(Box::from(meta.clone()))))
+pub fn make_mal_map_from_map_with_meta(map: &Rc<MalMapType>, meta: &MalData) -> Result<MalData, String> {
+    Ok(MalData::Map(map.clone(), Some(Rc::from(meta.clone()))))
 }
@@ -0,0 +1,366 @@
+// umwandlung zwischen MalData und rust-typen sowie registrierung typisierter nativer funktionen
+//
+//     fn repeat(n: i64, s: String) -> Result<Vec<String>, String> { ... }
+//
+//     let fun = native_fn("repeat", repeat);
+//     env.set(&"repeat".to_owned(), &MalData::Function(fun));
+//
+// anzahl und typen der argumente werden beim aufruf geprueft, fehler der funktion selbst kommen
+// als CoreError::Native in mal an.
+
+use std::any::{self, Any};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::Hash;
+use std::rc::Rc;
+
+use common::{MalData, MapKey, NativeFunction, FunContext, Opaque};
+use common::{make_mal_list_from_vec, make_mal_string, mal_bool_value, mapkey_for, mal_value_for};
+use eval::{EvalError, CoreError};
+
+// ein wert passt nicht zum erwarteten rust-typ; `got` ist der typname des betroffenen wertes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeMismatch {
+    pub expected: String,
+    pub got: &'static str
+}
+
+impl TypeMismatch {
+    pub fn new(expected: &str, got: &MalData) -> TypeMismatch {
+        TypeMismatch { expected: expected.to_owned(), got: got.type_name() }
+    }
+
+    // als fehler des argumentes `arg` (ab 1) der funktion `fun`
+    pub fn in_arg(self, fun: &str, arg: usize) -> CoreError {
+        CoreError::Type { fun: fun.to_owned(), arg, expected: self.expected, got: self.got }
+    }
+}
+
+impl fmt::Display for TypeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, got {}", self.expected, self.got)
+    }
+}
+
+pub trait FromMal: Sized {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch>;
+}
+
+pub trait IntoMal {
+    fn into_mal(self) -> MalData;
+}
+
+// typen, die als schluessel einer mal-map taugen
+pub trait IntoMapKey {
+    fn into_map_key(self) -> MapKey;
+}
+
+
+impl FromMal for MalData {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoMal for MalData {
+    fn into_mal(self) -> MalData {
+        self
+    }
+}
+
+impl FromMal for bool {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        match *value {
+            MalData::True => Ok(true),
+            MalData::False => Ok(false),
+            _ => Err(TypeMismatch::new("a boolean", value))
+        }
+    }
+}
+
+impl IntoMal for bool {
+    fn into_mal(self) -> MalData {
+        mal_bool_value(self)
+    }
+}
+
+impl FromMal for String {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        if let MalData::String(ref string) = *value {
+            Ok(string.to_string())
+        } else {
+            Err(TypeMismatch::new("a string", value))
+        }
+    }
+}
+
+impl IntoMal for String {
+    fn into_mal(self) -> MalData {
+        MalData::String(Rc::from(self))
+    }
+}
+
+impl IntoMal for &str {
+    fn into_mal(self) -> MalData {
+        make_mal_string(self)
+    }
+}
+
+impl IntoMal for () {
+    fn into_mal(self) -> MalData {
+        MalData::Nil
+    }
+}
+
+// mal-zahlen sind i64; gelesen werden nur werte, die in den rust-typ passen
+macro_rules! integer_conversions {
+    ( $( $int:ty => $expected:expr ),* ) => {
+        $(
+            impl FromMal for $int {
+                fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+                    match *value {
+                        MalData::Number(num) =>
+                            <$int>::try_from(num).map_err( |_| TypeMismatch::new($expected, value) ),
+
+                        _ =>
+                            Err(TypeMismatch::new($expected, value))
+                    }
+                }
+            }
+        )*
+    }
+}
+
+integer_conversions!(i8 => "a number between -128 and 127", u8 => "a number between 0 and 255",
+                     i16 => "a number between -32768 and 32767", u16 => "a number between 0 and 65535",
+                     i32 => "a number between -2147483648 and 2147483647", i64 => "a number",
+                     u32 => "a number between 0 and 4294967295",
+                     u64 => "a non-negative number", usize => "a non-negative number");
+
+macro_rules! into_mal_number {
+    ( $( $int:ty ),* ) => {
+        $(
+            impl IntoMal for $int {
+                fn into_mal(self) -> MalData {
+                    MalData::Number(self as i64)
+                }
+            }
+
+            impl IntoMapKey for $int {
+                fn into_map_key(self) -> MapKey {
+                    MapKey::Number(self as i64)
+                }
+            }
+        )*
+    }
+}
+
+into_mal_number!(i8, u8, i16, u16, i32, u32, i64);
+
+// breitere typen: werte jenseits des zahlenbereichs bleiben bei dessen groesstem wert stehen, wie bei count
+macro_rules! into_mal_saturating {
+    ( $( $int:ty ),* ) => {
+        $(
+            impl IntoMal for $int {
+                fn into_mal(self) -> MalData {
+                    MalData::Number(i64::try_from(self).unwrap_or(i64::MAX))
+                }
+            }
+
+            impl IntoMapKey for $int {
+                fn into_map_key(self) -> MapKey {
+                    MapKey::Number(i64::try_from(self).unwrap_or(i64::MAX))
+                }
+            }
+        )*
+    }
+}
+
+into_mal_saturating!(u64, usize);
+
+impl<T: FromMal> FromMal for Option<T> {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        match *value {
+            MalData::Nil =>
+                Ok(None),
+
+            _ =>
+                T::from_mal(value).map(Some).map_err( |err| TypeMismatch { expected: format!("{} or nil", err.expected), ..err } )
+        }
+    }
+}
+
+impl<T: IntoMal> IntoMal for Option<T> {
+    fn into_mal(self) -> MalData {
+        self.map_or(MalData::Nil, IntoMal::into_mal)
+    }
+}
+
+impl<T: FromMal> FromMal for Vec<T> {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        match *value {
+            MalData::List(ref list, _) | MalData::Vector(ref list, _) =>
+                list.iter().enumerate()
+                    .map( |( index, el )| T::from_mal(el).map_err( |err|
+                        TypeMismatch { expected: format!("a list or vector, element {} must be {}", index + 1, err.expected), ..err } ))
+                    .collect(),
+
+            _ =>
+                Err(TypeMismatch::new("a list or vector", value))
+        }
+    }
+}
+
+impl<T: IntoMal> IntoMal for Vec<T> {
+    fn into_mal(self) -> MalData {
+        make_mal_list_from_vec(self.into_iter().map(IntoMal::into_mal).collect())
+    }
+}
+
+impl<K: FromMal + Eq + Hash, V: FromMal> FromMal for HashMap<K, V> {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        if let MalData::Map(ref map, _) = *value {
+            map.iter()
+                .map( |( key, val )| {
+                    let key = K::from_mal(&mal_value_for(key)).map_err( |err|
+                        TypeMismatch { expected: format!("a map, keys must be {}", err.expected), ..err } )?;
+                    let val = V::from_mal(val).map_err( |err|
+                        TypeMismatch { expected: format!("a map, values must be {}", err.expected), ..err } )?;
+
+                    Ok(( key, val ))
+                })
+                .collect()
+        } else {
+            Err(TypeMismatch::new("a map", value))
+        }
+    }
+}
+
+impl<K: IntoMapKey, V: IntoMal> IntoMal for HashMap<K, V> {
+    fn into_mal(self) -> MalData {
+        MalData::Map(Rc::new(self.into_iter().map( |( key, val )| ( key.into_map_key(), val.into_mal() ) ).collect()), None)
+    }
+}
+
+impl IntoMapKey for MapKey {
+    fn into_map_key(self) -> MapKey {
+        self
+    }
+}
+
+impl IntoMapKey for bool {
+    fn into_map_key(self) -> MapKey {
+        if self { MapKey::True } else { MapKey::False }
+    }
+}
+
+impl IntoMapKey for String {
+    fn into_map_key(self) -> MapKey {
+        MapKey::String(Rc::from(self))
+    }
+}
+
+impl IntoMapKey for &str {
+    fn into_map_key(self) -> MapKey {
+        MapKey::String(Rc::from(self))
+    }
+}
+
+impl FromMal for MapKey {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        mapkey_for(value).map_err( |_| TypeMismatch::new("a string, keyword, symbol, number or boolean", value) )
+    }
+}
+
+
+impl FromMal for Opaque {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        if let MalData::Opaque(ref opaque) = *value {
+            Ok(opaque.clone())
+        } else {
+            Err(TypeMismatch::new("an opaque value", value))
+        }
+    }
+}
+
+impl IntoMal for Opaque {
+    fn into_mal(self) -> MalData {
+        MalData::Opaque(self)
+    }
+}
+
+// der in einem opaken wert abgelegte rust-wert
+impl<T: Any> FromMal for Rc<T> {
+    fn from_mal(value: &MalData) -> Result<Self, TypeMismatch> {
+        match *value {
+            MalData::Opaque(ref opaque) =>
+                opaque.downcast::<T>().ok_or_else( || TypeMismatch {
+                    expected: format!("an opaque {}", any::type_name::<T>()),
+                    got: "opaque"
+                }),
+
+            _ =>
+                Err(TypeMismatch::new(&format!("an opaque {}", any::type_name::<T>()), value))
+        }
+    }
+}
+
+// rust-funktionen, die sich als NativeFunction registrieren lassen; `Args` ist das tupel der
+// argumenttypen und dient nur der unterscheidung der implementierungen
+pub trait IntoNativeFn<Args> {
+    fn into_native_fn(self, name: &str) -> NativeFunction;
+}
+
+pub fn native_fn<Args, F: IntoNativeFn<Args>>(name: &str, fun: F) -> NativeFunction {
+    fun.into_native_fn(name)
+}
+
+macro_rules! count_args {
+    () => { 0 };
+    ( $head:ident $( $tail:ident )* ) => { 1 + count_args!($( $tail )*) };
+}
+
+macro_rules! impl_into_native_fn {
+    ( $( $arg:ident ),* ) => {
+        impl<Fun, Res, Err, $( $arg ),*> IntoNativeFn<( $( $arg, )* )> for Fun
+            where Fun: Fn($( $arg ),*) -> Result<Res, Err> + 'static,
+                  Res: IntoMal,
+                  Err: fmt::Display,
+                  $( $arg: FromMal ),*
+        {
+            #[allow(unused_variables, unused_mut, unused_assignments)]
+            fn into_native_fn(self, name: &str) -> NativeFunction {
+                let fun_name = name.to_owned();
+
+                let callable = move |ctx: &FunContext, args: &[MalData]| -> Result<MalData, EvalError> {
+                    let arity = count_args!($( $arg )*);
+
+                    if args.len() != arity {
+                        return Err(CoreError::arity(&fun_name, arity, Some(arity), args.len()).into());
+                    }
+
+                    let mut index = 0;
+
+                    let result = self($( {
+                        index += 1;
+                        $arg::from_mal(&args[index - 1]).map_err( |err| err.in_arg(&fun_name, index) )?
+                    } ),*);
+
+                    result.map( IntoMal::into_mal )
+                          .map_err( |err| CoreError::Native { fun: fun_name.clone(), message: err.to_string() }.into() )
+                };
+
+                NativeFunction::new(name, Rc::new(callable))
+            }
+        }
+    }
+}
+
+impl_into_native_fn!();
+impl_into_native_fn!(A1);
+impl_into_native_fn!(A1, A2);
+impl_into_native_fn!(A1, A2, A3);
+impl_into_native_fn!(A1, A2, A3, A4);
+impl_into_native_fn!(A1, A2, A3, A4, A5);
+impl_into_native_fn!(A1, A2, A3, A4, A5, A6);
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::cell::RefCell;
+use std::rc::Rc;
 
 use std::fs::File;
 use std::io;
@@ -16,173 +16,127 @@ use itertools;
 use reader;
 use printer::pr_str;
 
-use common::{MalData, CallableFun, FunContext};
+use common::{MalData, NativeFunction, Opaque, CallableFun, VisitFun, FunContext};
 use common::{make_mal_list_from_vec, get_wrapped_list, make_mal_keyword, mal_bool_value, is_mal_keyword, is_mal_vector, is_mal_nil, is_mal_true, is_mal_false, make_mal_vector_from_slice, make_mal_map_from_kv_list, is_mal_map, make_mal_list_from_vec_with_meta};
-use common::{MapKey, mapkey_for, make_mal_list_from_iter, is_list_like, are_lists_equal, is_mal_string, make_mal_string};
-use common::{make_mal_vector_from_vec_with_meta, make_mal_map_from_map_with_meta};
+use common::{MapKey, mapkey_for, mal_value_for, make_mal_list_from_iter, is_list_like, are_lists_equal, is_mal_string, make_mal_string};
+use common::{make_mal_vector_from_vec_with_meta, make_mal_map_from_map_with_meta, make_mal_ex_info, make_mal_atom, ExInfo};
 
-use env::{Env, wrapped_env_type};
+use vm;
+use gc;
+use protocol;
+use eval::{self, EvalError, CoreError, caught_call_stack, call_stack_as_mal_list};
 
-type MalCoreFunResult = Result<MalData, String>;
+type MalCoreFunResult = Result<MalData, EvalError>;
 
+// mal rechnet mit i64, damit reichen auch zwischenergebnisse wie (* max-ms iters) in perf.mal;
+// ueberlaeufe laufen wie in release-builds ueber
 #[allow(unused_variables)]
-fn mal_core_add(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    match ( args.get(0).map_or(None, |arg| number_arg(arg)), args.get(1).map_or(None, |arg| number_arg(arg)) ) {
-        ( Some(num1), Some(num2) ) =>
-            Ok(MalData::Number(num1 + num2)),
-
-        _ =>
-            Err("add: two number arguments required".to_string())
-    }
+fn mal_core_add(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( num1, num2 ) = number_args("+", args)?;
+    Ok(MalData::Number(num1.wrapping_add(num2)))
 }
 
 #[allow(unused_variables)]
-fn mal_core_sub(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    match ( args.get(0).map_or(None, |arg| number_arg(arg)), args.get(1).map_or(None, |arg| number_arg(arg)) ) {
-        ( Some(num1), Some(num2) ) =>
-            Ok(MalData::Number(num1 - num2)),
-
-        _ =>
-            Err("sub: two number arguments required".to_string())
-    }
+fn mal_core_sub(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( num1, num2 ) = number_args("-", args)?;
+    Ok(MalData::Number(num1.wrapping_sub(num2)))
 }
 
 #[allow(unused_variables)]
-fn mal_core_mul(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    match ( args.get(0).map_or(None, |arg| number_arg(arg)), args.get(1).map_or(None, |arg| number_arg(arg)) ) {
-        ( Some(num1), Some(num2) ) =>
-            Ok(MalData::Number(num1 * num2)),
-
-        _ =>
-            Err("mul: two number arguments required".to_string())
-    }
+fn mal_core_mul(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( num1, num2 ) = number_args("*", args)?;
+    Ok(MalData::Number(num1.wrapping_mul(num2)))
 }
 
 #[allow(unused_variables)]
-fn mal_core_div(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    match ( args.get(0).map_or(None, |arg| number_arg(arg)), args.get(1).map_or(None, |arg| number_arg(arg)) ) {
-        ( Some(num1), Some(num2) ) =>
-            Ok(MalData::Number(num1.checked_div(num2).ok_or("division failed")?)),
+fn mal_core_div(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( num1, num2 ) = number_args("/", args)?;
 
-        _ =>
-            Err("div: two number arguments required".to_string())
+    if num2 == 0 {
+        Err(CoreError::Arithmetic { fun: "/".to_owned(), message: "division by zero".to_owned() }.into())
+    } else {
+        Ok(MalData::Number(num1.checked_div(num2).ok_or_else( || overflow_error("/") )?))
     }
 }
 
 #[allow(unused_variables)]
-fn mal_core_list(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_list(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
     Ok(make_mal_list_from_vec(args.to_vec()))
 }
 
 #[allow(unused_variables)]
-fn mal_core_list_p(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    if args.is_empty() {
-        Err("argument required".to_owned())
-    } else {
-        match args[0] {
-            MalData::List(_, _) | MalData::Nil =>
-                Ok(MalData::True),
+fn mal_core_list_p(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("list?", args, 1, Some(1))?;
 
-            _ =>
-                Ok(MalData::False)
-        }
+    match args[0] {
+        MalData::List(_, _) | MalData::Nil =>
+            Ok(MalData::True),
+
+        _ =>
+            Ok(MalData::False)
     }
 }
 
 #[allow(unused_variables)]
-fn mal_core_empty_p(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    if args.is_empty() {
-        Err("argument required".to_owned())
-    } else {
-        match args[0] {
-            MalData::List(ref l, _) | MalData::Vector(ref l, _) =>
-                Ok(if l.is_empty() { MalData::True } else { MalData::False }),
+fn mal_core_empty_p(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("empty?", args, 1, Some(1))?;
 
-            MalData::Nil =>
-                Ok(MalData::True),
+    match args[0] {
+        MalData::List(ref l, _) | MalData::Vector(ref l, _) =>
+            Ok(if l.is_empty() { MalData::True } else { MalData::False }),
 
-            _ =>
-                Ok(MalData::False)
-        }
+        MalData::Nil =>
+            Ok(MalData::True),
+
+        _ =>
+            Ok(MalData::False)
     }
 }
 
 #[allow(unused_variables)]
-fn mal_core_count(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    if args.is_empty() {
-        Err("argument required".to_owned())
-    } else {
-        match args[0] {
-            MalData::Nil =>
-                Ok(MalData::Number(0)),
+fn mal_core_count(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("count", args, 1, Some(1))?;
 
-            MalData::List(ref l, _) | MalData::Vector(ref l, _) =>
-                Ok(MalData::Number(l.len() as i32)),
+    match args[0] {
+        MalData::Nil =>
+            Ok(MalData::Number(0)),
 
-            _ =>
-                Err("list argument required".to_owned())
-        }
+        MalData::List(ref l, _) | MalData::Vector(ref l, _) =>
+            Ok(MalData::Number(l.len() as i64)),
+
+        ref arg =>
+            Err(CoreError::type_error("count", 1, "a list, vector or nil", arg).into())
     }
 }
 
 #[allow(unused_variables)]
-fn mal_core_lt(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    // TODO fehlerbehandlung
-    if args.is_empty() {
-        Err("2 arguments required".to_owned())
-    } else {
-        if let ( &MalData::Number(n1), &MalData::Number(n2) ) = ( &args[0], &args[1] ) {
-            Ok(if n1 < n2 { MalData::True } else { MalData::False })
-        } else {
-            Ok(MalData::False)
-        }
-    }
+fn mal_core_lt(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( n1, n2 ) = number_args("<", args)?;
+    Ok(mal_bool_value(n1 < n2))
 }
 
 #[allow(unused_variables)]
-fn mal_core_le(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    // TODO fehlerbehandlung
-    if args.is_empty() {
-        Err("2 arguments required".to_owned())
-    } else {
-        if let ( &MalData::Number(n1), &MalData::Number(n2) ) = ( &args[0], &args[1] ) {
-            Ok(if n1 <= n2 { MalData::True } else { MalData::False })
-        } else {
-            Ok(MalData::False)
-        }
-    }
+fn mal_core_le(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( n1, n2 ) = number_args("<=", args)?;
+    Ok(mal_bool_value(n1 <= n2))
 }
 
 #[allow(unused_variables)]
-fn mal_core_gt(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    // TODO fehlerbehandlung
-    if args.is_empty() {
-        Err("2 arguments required".to_owned())
-    } else {
-        if let ( &MalData::Number(n1), &MalData::Number(n2) ) = ( &args[0], &args[1] ) {
-            Ok(if n1 > n2 { MalData::True } else { MalData::False })
-        } else {
-            Ok(MalData::False)
-        }
-    }
+fn mal_core_gt(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( n1, n2 ) = number_args(">", args)?;
+    Ok(mal_bool_value(n1 > n2))
 }
 
 #[allow(unused_variables)]
-fn mal_core_ge(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    // TODO fehlerbehandlung
-    if args.is_empty() {
-        Err("2 arguments required".to_owned())
-    } else {
-        if let ( &MalData::Number(n1), &MalData::Number(n2) ) = ( &args[0], &args[1] ) {
-            Ok(if n1 >= n2 { MalData::True } else { MalData::False })
-        } else {
-            Ok(MalData::False)
-        }
-    }
+fn mal_core_ge(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    let ( n1, n2 ) = number_args(">=", args)?;
+    Ok(mal_bool_value(n1 >= n2))
 }
 
 #[allow(unused_variables)]
-fn mal_core_equals(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_equals(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("=", args, 2, Some(2))?;
+
     match ( args[0].clone(), args[1].clone() ) {
         ( MalData::True, MalData::True ) =>
             Ok(MalData::True),
@@ -223,6 +177,12 @@ fn mal_core_equals(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String
             Ok(res)
         }
 
+        ( MalData::ExInfo(e1), MalData::ExInfo(e2) ) =>
+            Ok(mal_bool_value(Rc::ptr_eq(&e1, &e2))),
+
+        ( MalData::Opaque(o1), MalData::Opaque(o2) ) =>
+            Ok(mal_bool_value(o1 == o2)),
+
         ( l, r ) => {
             debug!("equals, default -> false; l: {:?}, r: {:?}", l, r);
             Ok(MalData::False)
@@ -231,7 +191,7 @@ fn mal_core_equals(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String
 }
 
 #[allow(unused_variables)]
-fn mal_core_prn(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_prn(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
     let print_readably = true;
     let res = itertools::join(args.iter().map(|e| pr_str(e, print_readably)), " ");
     println!("{}", res);
@@ -240,7 +200,7 @@ fn mal_core_prn(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
 }
 
 #[allow(unused_variables)]
-fn mal_core_println(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_println(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
     let print_readably = false;
     let res = itertools::join(args.iter().map(|e| pr_str(e, print_readably)), " ");
     println!("{}", res);
@@ -249,63 +209,79 @@ fn mal_core_println(ctx: &FunContext, args: &[MalData]) -> Result<MalData, Strin
 }
 
 #[allow(unused_variables)]
-fn mal_core_pr_str(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_pr_str(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
     let print_readably = true;
 
     let res = itertools::join(args.iter().map(|e| pr_str(e, print_readably) ), " ");
 
-    Ok(MalData::String(res))
+    Ok(MalData::String(Rc::from(res)))
 }
 
 #[allow(unused_variables)]
-fn mal_core_str(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_str(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
     let print_readably = false;
     let mut res = String::new();
-    
+
     res.push_str(itertools::join(args.iter().map(|e| pr_str(e, print_readably)), "").as_str());
 
-    Ok(MalData::String(res))
+    Ok(MalData::String(Rc::from(res)))
 }
 
 #[allow(unused_variables)]
-fn mal_core_read_string(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    if let Some(&MalData::String(ref string)) = args.get(0) {
-        reader::read_str(&string)
-    } else {
-        Err("string argument required".to_owned())
-    }
+fn mal_core_read_string(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("read-string", args, 1, Some(1))?;
+    let string = string_arg("read-string", args, 0)?;
+
+    reader::read_str(string).map_err( |message| CoreError::Reader { fun: "read-string".to_owned(), message }.into() )
 }
 
 #[allow(unused_variables)]
-fn mal_core_slurp(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    if let Some(&MalData::String(ref filename)) = args.get(0) {
-        let mut file = File::open(filename).map_err( |err| err.to_string() )?;  // TODO fehlerbehandlung
-        let mut buffer = String::new();
+fn mal_core_slurp(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("slurp", args, 1, Some(1))?;
+    let filename = string_arg("slurp", args, 0)?;
 
-        file.read_to_string(&mut buffer).map_err( |err| err.to_string() )?;  // TODO fehlerbehandlung
+    let mut file = File::open(filename).map_err( |err| CoreError::io("slurp", &format!("{}: {}", filename, err)) )?;
+    let mut buffer = String::new();
 
-        Ok(MalData::String(buffer))
-    } else {
-        Err("file name argument required".to_owned())
-    }
+    file.read_to_string(&mut buffer).map_err( |err| CoreError::io("slurp", &format!("{}: {}", filename, err)) )?;
+
+    Ok(MalData::String(Rc::from(buffer)))
+}
+
+#[allow(unused_variables)]
+fn mal_core_spit(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("spit", args, 2, Some(2))?;
+    let filename = string_arg("spit", args, 0)?;
+    let content = string_arg("spit", args, 1)?;
+
+    let mut file = File::create(filename).map_err( |err| CoreError::io("spit", &format!("{}: {}", filename, err)) )?;
+
+    file.write_all(content.as_bytes()).map_err( |err| CoreError::io("spit", &format!("{}: {}", filename, err)) )?;
+
+    Ok(MalData::Nil)
 }
 
 #[allow(unused_variables)]
-fn mal_core_atom(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_atom(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("atom", args, 1, Some(1))?;
     let value = args[0].clone();
 
     debug!("atom, value: {:?}", value);
 
-    Ok(MalData::Atom(Rc::from(RefCell::from(value))))
+    Ok(make_mal_atom(value))
 }
 
 #[allow(unused_variables)]
-fn mal_core_atom_p(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_atom_p(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("atom?", args, 1, Some(1))?;
+
     if let MalData::Atom(_) = args[0] { Ok(MalData::True) } else { Ok(MalData::False) }
 }
 
 #[allow(unused_variables)]
-fn mal_core_deref(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_deref(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("deref", args, 1, Some(1))?;
+
     if let MalData::Atom(ref atom) = args[0] {
         Ok(atom.borrow().clone())
     } else {
@@ -314,7 +290,9 @@ fn mal_core_deref(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String>
 }
 
 #[allow(unused_variables)]
-fn mal_core_reset(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String> {
+fn mal_core_reset(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("reset!", args, 2, Some(2))?;
+
     if let MalData::Atom(ref atom) = args[0] {
         let ref new_value = args[1];
 
@@ -322,12 +300,12 @@ fn mal_core_reset(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String
 
         Ok(new_value.clone())
     } else {
-        Err("atom expected".to_owned())
+        Err(CoreError::type_error("reset!", 1, "an atom", &args[0]).into())
     }
 }
 
 // mro TODO geeigneten platz finden und dorthin verfrachten
-// fn apply_fn_closure(fn_closure: &FnClosure, parameters: &[MalData]) -> Result<MalData, String> {
+// fn apply_fn_closure(fn_closure: &FnClosure, parameters: &[MalData]) -> MalCoreFunResult {
 //     debug!("apply_fn_closure, cl: {:?}, parameters: {:?}", fn_closure, parameters);
 
 //     let outer_env = fn_closure.outer_env.clone();
@@ -337,7 +315,9 @@ fn mal_core_reset(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String
 // }
 
 #[allow(unused_variables)]
-fn mal_core_swap(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_swap(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("swap!", args, 2, None)?;
+
     if let MalData::Atom(ref atom) = args[0] {
         let ref atom_fn = args[1];
         let old_value = atom.borrow().clone();
@@ -370,89 +350,64 @@ fn mal_core_swap(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String>
         //         }
         //     }
 
-        //     _ => 
+        //     _ =>
         //         return Err("atom value update function expected".to_owned())
         // }
 
     } else {
-        Err("atom expected".to_owned())
+        Err(CoreError::type_error("swap!", 1, "an atom", &args[0]).into())
     }
 }
 
 #[allow(unused_variables)]
-fn mal_core_cons(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String> {
-    match ( args.get(0), args.get(1) ) {
-        ( Some(head), Some(&MalData::List(ref tail, _)) ) |
-        ( Some(head), Some(&MalData::Vector(ref tail, _)) ) => {
-            let new_len = 1 + tail.len();
-            let mut new_vec: Vec<MalData> = Vec::with_capacity(new_len);
+fn mal_core_cons(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("cons", args, 2, Some(2))?;
 
-            new_vec.push(head.clone());
-            new_vec.extend_from_slice(&tail[..]);
+    let head = &args[0];
+    let tail = seq_arg("cons", args, 1)?;
 
-            Ok(make_mal_list_from_vec(new_vec))
-        }
+    let new_len = 1 + tail.len();
+    let mut new_vec: Vec<MalData> = Vec::with_capacity(new_len);
 
-        _ =>
-            Err("head and tail required".to_owned())
-    }
-}
+    new_vec.push(head.clone());
+    new_vec.extend_from_slice(&tail[..]);
 
-fn is_mal_list_or_vector(ast: &MalData) -> bool {
-    if let &MalData::List(_, _) = ast {
-        true
-    } else if let &MalData::Vector(_, _) = ast {
-        true
-    } else {
-        false
-    }
+    Ok(make_mal_list_from_vec(new_vec))
 }
 
 #[allow(unused_variables)]
-fn mal_core_concat(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String> {
-    if !args.iter().all( |arg| is_mal_list_or_vector(arg)) {
-        return Err("only list and vector arguments allowed".to_owned())
-    }
-
+fn mal_core_concat(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
     let mut new_vec = Vec::new();
 
-    for arg in args {
-        if let &MalData::List(ref list, _) = arg {
-            new_vec.extend_from_slice(&list[..]);
-        } else if let &MalData::Vector(ref list, _) = arg {
-            new_vec.extend_from_slice(&list[..]);
-        }
+    for index in 0..args.len() {
+        new_vec.extend_from_slice(&seq_arg("concat", args, index)?[..]);
     }
 
     Ok(make_mal_list_from_vec(new_vec))
 }
 
-fn mal_number_value(mal_num: &MalData) -> Option<i32> {
-    if let &MalData::Number(num) = mal_num {
-        Some(num)
-    } else {
-        None
-    }
-}
+fn mal_core_nth(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("nth", args, 2, Some(2))?;
 
-fn mal_core_nth(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
-    let list = args.get(0).map( |l| get_wrapped_list(l) ).ok_or("list argument required")?.unwrap();
-    let index = args.get(1).map( |n| mal_number_value(n) ).ok_or("index argument required")?.unwrap();
+    let list = seq_arg("nth", args, 0)?;
+    let index = number_arg("nth", args, 1)?;
 
-    if index < 0 || index >= list.len() as i32 {
-        Err(format!("index {} out of range for list of size {}", index, list.len()))
+    if index < 0 || index >= list.len() as i64 {
+        Err(CoreError::IndexOutOfBounds { fun: "nth".to_owned(), index, size: list.len() }.into())
     } else {
         Ok(list[index as usize].clone())
     }
 }
 
-fn mal_core_first(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_first(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("first", args, 1, Some(1))?;
+
     // first von nil -> nil
-    if args.get(0).map(|l| is_mal_nil(l) ).unwrap_or(false) {
+    if is_mal_nil(&args[0]) {
         return Ok(MalData::Nil)
     }
 
-    let list = args.get(0).map_or(None, |l| get_wrapped_list(l) ).ok_or("list argument required")?;
+    let list = seq_arg("first", args, 0)?;
 
     if list.is_empty() {
         Ok(MalData::Nil)
@@ -465,13 +420,15 @@ fn mal_empty_list() -> MalData {
     make_mal_list_from_vec(vec![])
 }
 
-fn mal_core_rest(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String> {
+fn mal_core_rest(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+    check_arity("rest", args, 1, Some(1))?;
+
     // rest(nil) -> ()
-    if args.get(0).map(|l| is_mal_nil(l) ).unwrap_or(false) {
+    if is_mal_nil(&args[0]) {
         return Ok(mal_empty_list());
     }
 
-    let list = args.get(0).map_or(None, |l| get_wrapped_list(l) ).ok_or("list argument required")?;
+    let list = seq_arg("rest", args, 0)?;
 
     if list.is_empty() {
         Ok(mal_empty_list())
@@ -482,14 +439,57 @@ fn mal_core_rest(ctx: &FunContext, args: &[MalData]) -> Result<MalData, String>
 }
 
 fn mal_core_throw(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() != 1 {
-        Err("exception argument required".to_owned())
-    } else {
-        Ok(MalData::Exception(Box::from(args[0].clone())))
+    check_arity("throw", args, 1, Some(1))?;
+
+    Err(EvalError::Exception(args[0].clone()))
+}
+
+fn mal_core_ex_info(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("ex-info", args, 2, Some(3))?;
+
+    let message = string_arg("ex-info", args, 0)?;
+
+    if !is_mal_map(&args[1]) {
+        return Err(CoreError::type_error("ex-info", 2, "a map", &args[1]).into());
     }
+
+    Ok(make_mal_ex_info(message, args[1].clone(), args.get(2).cloned()))
 }
 
-fn apply_fun(ctx: &FunContext, fun: &MalData, args: &[MalData]) -> MalCoreFunResult {
+fn ex_info_arg<'a>(fun_name: &str, args: &'a [MalData]) -> Result<Option<&'a ExInfo>, EvalError> {
+    check_arity(fun_name, args, 1, Some(1))?;
+
+    if let MalData::ExInfo(ref ex_info) = args[0] { Ok(Some(ex_info)) } else { Ok(None) }
+}
+
+fn mal_core_ex_message(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    Ok(ex_info_arg("ex-message", args)?.map_or(MalData::Nil, |ex_info| make_mal_string(&ex_info.message)))
+}
+
+fn mal_core_ex_data(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    Ok(ex_info_arg("ex-data", args)?.map_or(MalData::Nil, |ex_info| ex_info.data.clone()))
+}
+
+fn mal_core_ex_cause(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    Ok(ex_info_arg("ex-cause", args)?.and_then( |ex_info| ex_info.cause.clone() ).unwrap_or(MalData::Nil))
+}
+
+// stapel eines ex-info, ansonsten der der zuletzt in catch* gefangenen exception
+fn mal_core_ex_stack(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("ex-stack", args, 0, Some(1))?;
+
+    let stack = match args.first() {
+        Some(&MalData::ExInfo(ref ex_info)) =>
+            ex_info.stack.borrow().clone(),
+
+        _ =>
+            None
+    };
+
+    Ok(stack.or_else(caught_call_stack).map_or(MalData::Nil, |s| call_stack_as_mal_list(&s)))
+}
+
+pub fn apply_fun(ctx: &FunContext, fun: &MalData, args: &[MalData]) -> MalCoreFunResult {
     match fun {
         &MalData::Function(ref fun) => {
             let callable = fun.callable.clone();
@@ -501,12 +501,7 @@ fn apply_fun(ctx: &FunContext, fun: &MalData, args: &[MalData]) -> MalCoreFunRes
         }
 
         &MalData::FnClosure(ref fnc) => {
-            let ref eval = ctx.eval2;  // FIXME eval in ctx
-
-            let outer_env = fnc.outer_env.clone();
-            let fn_env = Env::new(Some(outer_env), fnc.binds.as_slice(), args)?;
-
-            let res = eval(wrapped_env_type(fn_env), fnc.body.as_ref()).map_err( |e| e.to_string())?;
+            let res = eval::apply(fnc.outer_env.clone(), fun, args)?;
 
             debug!("apply_fun, fnc: {:?}, args: {:?}\n-> {:?}", fnc, args, res);
 
@@ -514,23 +509,20 @@ fn apply_fun(ctx: &FunContext, fun: &MalData, args: &[MalData]) -> MalCoreFunRes
         }
 
         _ => {
-            Err(format!("cannot apply {:?}", fun))
+            Err(CoreError::illegal_argument("apply", &format!("cannot apply {}", fun.type_name())).into())
         }
     }
 }
 
 fn mal_core_apply(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
-    if args.len() < 2 {
-        return Err("apply: function and argument vector required".to_owned());
-    }
+    check_arity("apply", args, 2, None)?;
 
     let ref fun_arg = args[0];
-    let ref args_arg = args[args.len() - 1];
-    let args_arg_list = get_wrapped_list(args_arg).ok_or("apply: invalid argument vector")?;
+    let args_arg_list = seq_arg("apply", args, args.len() - 1)?;
 
-    let prepend_args = if args.len() > 2 { &args[1..args.len() - 1] } else { &[] };
+    let prepend_args = &args[1..args.len() - 1];
 
-    debug!("apply, fun: {:?}, args: {:?}, prepend: {:?}", fun_arg, args_arg, prepend_args);
+    debug!("apply, fun: {:?}, args: {:?}, prepend: {:?}", fun_arg, args_arg_list, prepend_args);
 
     // TODO erstellung der parameterliste optimieren
     let mut eff_args: Vec<MalData> = Vec::with_capacity(prepend_args.len() + args_arg_list.len());
@@ -543,36 +535,35 @@ fn mal_core_apply(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
 }
 
 fn mal_core_map(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
-    let fun_arg = args.get(0).ok_or("map: function argument required")?;
-    let seq_arg = args.get(1).ok_or("map: sequence argument required")?;
+    check_arity("map", args, 2, Some(2))?;
 
-    let seq = get_wrapped_list(seq_arg).ok_or("map: invalid sequence argument")?;
+    let fun_arg = &args[0];
+    let seq = seq_arg("map", args, 1)?;
 
     let mut mapped = Vec::with_capacity(seq.len());
 
     for el in seq {
         let map_res = apply_fun(ctx, fun_arg, vec![el.clone()].as_slice())?;
 
-        if let MalData::Exception(_) = map_res {
-            return Ok(map_res.clone())
-        } else {
-            mapped.push(map_res);
-        }
+        mapped.push(map_res);
     }
 
     Ok(make_mal_list_from_vec(mapped))
 }
 
-fn mal_core_nil_p(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String> {
-    args.get(0).map( |arg| mal_bool_value(is_mal_nil(arg)) ).ok_or("nil?: argument required".to_owned())
+fn mal_core_nil_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("nil?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_nil(&args[0])))
 }
 
-fn mal_core_true_p(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String> {
-    args.get(0).map( |arg| mal_bool_value(is_mal_true(arg)) ).ok_or("true?: argument required".to_owned())
+fn mal_core_true_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("true?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_true(&args[0])))
 }
 
-fn mal_core_false_p(ctx: &FunContext, args: & [MalData]) -> Result<MalData, String> {
-    args.get(0).map( |arg| mal_bool_value(is_mal_false(arg)) ).ok_or("false?: argument required".to_owned())
+fn mal_core_false_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("false?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_false(&args[0])))
 }
 fn is_mal_symbol(value: &MalData) -> bool {
     if let &MalData::Symbol(_) = value { true } else { false }
@@ -580,33 +571,27 @@ fn is_mal_symbol(value: &MalData) -> bool {
 
 
 fn mal_core_symbol_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    args.get(0).map( |arg| mal_bool_value(is_mal_symbol(arg)) ).ok_or("symbol?: argument required".to_owned())
-}
-
-fn mal_string_as_string(value: &MalData) -> Option<String> {
-    if let &MalData::String(ref string) = value {
-        Some(string.clone())
-    } else {
-        None
-    }
+    check_arity("symbol?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_symbol(&args[0])))
 }
 
 fn make_mal_symbol(string: &str) -> MalData {
-    MalData::Symbol(string.to_string())
+    MalData::Symbol(Rc::from(string))
 }
 
 fn mal_core_symbol(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    let string = args.get(0).ok_or("symbol: argument required".to_owned())?;
-    mal_string_as_string(string).map( |s| make_mal_symbol(&s)).ok_or("symbol: name must be string".to_owned())
+    check_arity("symbol", args, 1, Some(1))?;
+    Ok(make_mal_symbol(string_arg("symbol", args, 0)?))
 }
 
 fn mal_core_keyword(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    let string = args.get(0).ok_or("keyword: argument required".to_owned())?;
-    mal_string_as_string(string).map( |s| make_mal_keyword(&s)).ok_or("keyword: name must be string".to_owned())
+    check_arity("keyword", args, 1, Some(1))?;
+    Ok(make_mal_keyword(string_arg("keyword", args, 0)?))
 }
 
 fn mal_core_keyword_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    args.get(0).map( |arg| mal_bool_value(is_mal_keyword(arg)) ).ok_or("keyword?: argument required".to_owned())
+    check_arity("keyword?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_keyword(&args[0])))
 }
 
 fn mal_core_vector(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
@@ -614,187 +599,168 @@ fn mal_core_vector(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
 }
 
 fn mal_core_vector_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    args.get(0).map( |arg| mal_bool_value(is_mal_vector(arg)) ).ok_or("vector?: argument required".to_owned())
+    check_arity("vector?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_vector(&args[0])))
 }
 
 fn mal_core_hashmap(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
     if args.len() % 2 != 0 {
-        return Err("hash-map: even number of arguments required".to_owned());
+        return Err(CoreError::illegal_argument("hash-map", "even number of arguments required").into());
+    }
+
+    for index in (0..args.len()).step_by(2) {
+        map_key_arg("hash-map", args, index)?;
     }
 
-    Ok(make_mal_map_from_kv_list(&mut args.iter())?)
+    make_mal_map_from_kv_list(&mut args.iter()).map_err( |message| CoreError::illegal_argument("hash-map", &message).into() )
 }
 
 fn mal_core_map_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    args.get(0).map( |arg| mal_bool_value(is_mal_map(arg)) ).ok_or("map?: argument required".to_owned())
+    check_arity("map?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_map(&args[0])))
 }
 
 fn mal_core_contains_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() != 2 {
-        return Err("map and key arguments required".to_owned());
-    }
+    check_arity("contains?", args, 2, Some(2))?;
 
-    if let ( &MalData::Map(ref map, _), ref key ) = ( &args[0], &args[1] ) {
-        Ok(mal_bool_value(map.contains_key(&mapkey_for(&key)?)))
-    } else {
-        Err("invalid arguments".to_owned())
-    }
+    let map = map_arg("contains?", args, 0)?;
+    Ok(mal_bool_value(map.contains_key(&map_key_arg("contains?", args, 1)?)))
 }
 
 fn mal_core_sequential_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    args.get(0).map( |arg| mal_bool_value(is_list_like(arg)) ).ok_or("sequential?: argument required".to_owned())
-}
-
-fn mal_map_key_as_mal_value(key: &MapKey) -> MalData {
-    match key {
-        &MapKey::String(ref string) => 
-            MalData::String(string.clone()),
-
-        &MapKey::Symbol(ref symbol) => 
-            MalData::Symbol(symbol.clone()),
-
-        &MapKey::Keyword(ref kw) => 
-            MalData::Keyword(kw.clone()),
-
-        &MapKey::Number(number) => 
-            MalData::Number(number),
-
-        &MapKey::True => 
-            MalData::True,
-
-        &MapKey::False => 
-            MalData::False,
-
-        // _ =>
-        //     panic!("unhandled map key type: {:?}", key)
-    }
+    check_arity("sequential?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_list_like(&args[0])))
 }
 
 fn mal_core_keys(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
-    if let &MalData::Map(ref map, _) = &args[0] {
-        let keys = map.keys().map( |k| mal_map_key_as_mal_value(k) ).collect::<Vec<MalData>>();
+    check_arity("keys", args, 1, Some(1))?;
 
-        Ok(make_mal_list_from_vec(keys))
-    } else {
-        Err("keys: map argument required".to_owned())
-    }
+    let map = map_arg("keys", args, 0)?;
+    let keys = map.keys().map(mal_value_for).collect::<Vec<MalData>>();
+
+    Ok(make_mal_list_from_vec(keys))
 }
 
 fn mal_core_vals(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
-    if let &MalData::Map(ref map, _) = &args[0] {
-        let iter: &mut Iterator<Item=&MalData> = &mut map.values();
+    check_arity("vals", args, 1, Some(1))?;
 
-        Ok(make_mal_list_from_iter(iter))
-    } else {
-        Err("keys: map argument required".to_owned())
-    }
+    let map = map_arg("vals", args, 0)?;
+    let iter: &mut Iterator<Item=&MalData> = &mut map.values();
+
+    Ok(make_mal_list_from_iter(iter))
 }
 
 fn mal_core_assoc(ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
-    if args.len() < 2 {
-        return Err("map and key/value arguments required".to_owned());
-    } else if args.len() % 2 != 1 {
-        return Err("key/value pairs required".to_owned());
+    check_arity("assoc", args, 1, None)?;
+
+    if args.len() % 2 != 1 {
+        return Err(CoreError::illegal_argument("assoc", "key/value pairs required").into());
     }
 
     if let &MalData::Map(ref map, ref meta) = &args[0] {
-        let mut new_map = map.clone();
+        let mut new_map = (**map).clone();
 
-        let kvs = &args[1..].to_vec();
-        let mut kv_iter = kvs.iter();
-
-        while let ( Some(key_arg), Some(value_arg) ) = ( kv_iter.next(), kv_iter.next() ) {
-            new_map.insert(mapkey_for(key_arg)?, value_arg.clone());
+        for index in (1..args.len()).step_by(2) {
+            new_map.insert(map_key_arg("assoc", args, index)?, args[index + 1].clone());
         }
 
-        Ok(MalData::Map(new_map, meta.clone()))
+        Ok(MalData::Map(Rc::new(new_map), meta.clone()))
     } else {
-        Err("invalid arguments".to_owned())
+        Err(CoreError::type_error("assoc", 1, "a map", &args[0]).into())
     }
-    
+
 }
 
 fn mal_core_dissoc(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() < 2 {
-        return Err("map and keys arguments required".to_owned());
-    }
+    check_arity("dissoc", args, 1, None)?;
 
     if let &MalData::Map(ref map, ref meta) = &args[0] {
-        let mut new_map = map.clone();
+        let mut new_map = (**map).clone();
 
-        for key_arg in &args[1..] {
-            new_map.remove(&mapkey_for(key_arg)?);
+        for index in 1..args.len() {
+            new_map.remove(&map_key_arg("dissoc", args, index)?);
         }
 
-        Ok(MalData::Map(new_map, meta.clone()))
+        Ok(MalData::Map(Rc::new(new_map), meta.clone()))
     } else {
-        Err("invalid arguments".to_owned())
+        Err(CoreError::type_error("dissoc", 1, "a map", &args[0]).into())
     }
 }
 
 fn mal_core_get(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() != 2 {
-        return Err("map and key arguments required".to_owned());
-    }
+    check_arity("get", args, 2, Some(2))?;
 
     if let &MalData::Nil = &args[0] {
         Ok(MalData::Nil)
-    } else if let ( &MalData::Map(ref map, _), ref key ) = ( &args[0], &args[1] ) {
-        Ok(map.get(&mapkey_for(&key)?).map_or(MalData::Nil, |v| v.clone()))
     } else {
-        Err("invalid arguments".to_owned())
+        let map = map_arg("get", args, 0)?;
+        Ok(map.get(&map_key_arg("get", args, 1)?).map_or(MalData::Nil, |v| v.clone()))
     }
 }
 
 fn mal_core_readline(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    let prompt = args.get(0).ok_or("prompt argument required")?;
+    check_arity("readline", args, 1, Some(1))?;
+    let prompt = string_arg("readline", args, 0)?;
 
-    print!("{}", mal_string_as_string(prompt).ok_or("prompt must be a string")?);
-    io::stdout().flush();
+    print!("{}", prompt);
+    io::stdout().flush().map_err( |e| CoreError::io("readline", &e.to_string()) )?;
 
     let mut line = String::new();
 
     io::stdin().read_line(&mut line)
-        .map( |c| if c > 0 { MalData::String(line[0..c - 1].to_string()) } else { MalData::String("".to_string()) })
-        .map_err( |e| format!("{}", e))
-} 
+        .map( |c| if c > 0 { make_mal_string(&line[0..c - 1]) } else { make_mal_string("") })
+        .map_err( |e| CoreError::io("readline", &e.to_string()).into() )
+}
 
 fn mal_core_string_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    args.get(0).ok_or("argument required".to_string()).map( |arg| mal_bool_value(is_mal_string(arg)) )
+    check_arity("string?", args, 1, Some(1))?;
+    Ok(mal_bool_value(is_mal_string(&args[0])))
+}
+
+// typ als keyword, nach dem protokolle verzweigen
+#[allow(unused_variables)]
+fn mal_core_type(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("type", args, 1, Some(1))?;
+    Ok(protocol::type_keyword(&args[0]))
+}
+
+#[allow(unused_variables)]
+fn mal_core_satisfies_p(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("satisfies?", args, 2, Some(2))?;
+    let protocol = protocol::protocol_of(&args[0]).ok_or_else( || CoreError::type_error("satisfies?", 1, "protocol", &args[0]) )?;
+
+    Ok(mal_bool_value(protocol.extends(protocol::type_of(&args[1]))))
 }
 
 fn mal_core_seq(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    match args.get(0) {
-        Some(&MalData::List(ref lst, _)) | Some(&MalData::Vector(ref lst, _)) if lst.is_empty() =>
+    check_arity("seq", args, 1, Some(1))?;
+
+    match args[0] {
+        MalData::List(ref lst, _) | MalData::Vector(ref lst, _) if lst.is_empty() =>
             Ok(MalData::Nil),
 
-        Some(&MalData::List(ref lst, _)) | Some(&MalData::Vector(ref lst, _)) =>
+        MalData::List(ref lst, _) | MalData::Vector(ref lst, _) =>
             Ok(make_mal_list_from_iter(&mut lst.iter())),
 
-        Some(&MalData::String(ref string)) if string.is_empty() =>
+        MalData::String(ref string) if string.is_empty() =>
             Ok(MalData::Nil),
 
-        Some(&MalData::String(ref string)) => {
-            let mut vec = string.chars().map( |c| make_mal_string(&c.to_string())).collect();
+        MalData::String(ref string) => {
+            let vec = string.chars().map( |c| make_mal_string(&c.to_string())).collect();
 
             Ok(make_mal_list_from_vec(vec))
         }
 
-        Some(&MalData::Nil) =>
+        MalData::Nil =>
             Ok(MalData::Nil),
 
-        Some(arg) =>
-            Err(format!("seq: argument of illegal type: {:?}", arg)),
-
-        None =>
-            Err("seq: argument required".to_string())
+        ref arg =>
+            Err(CoreError::type_error("seq", 1, "a list, vector, string or nil", arg).into())
     }
 }
 
 fn mal_core_conj(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() < 2 {
-        return Err("conj: seq arguments required".to_string());
-    }
+    check_arity("conj", args, 2, None)?;
 
     match ( &args[0], &args[1..] ) {
         ( &MalData::List(ref lst, _), xs) => {
@@ -820,30 +786,30 @@ fn mal_core_conj(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
         }
 
         ( seq, _ ) =>
-            Err(format!("illegal type for argument: {:?}", seq))
+            Err(CoreError::type_error("conj", 1, "a list or vector", seq).into())
     }
 }
 
-fn make_mal_number(number: i32) -> MalData {
+fn make_mal_number(number: i64) -> MalData {
     MalData::Number(number)
 }
 
 fn mal_core_time_ms(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("time-ms", args, 0, Some(0))?;
+
     let de = SystemTime::now().duration_since(time::UNIX_EPOCH);
     debug!("time-ms, de: {:?}", de);
     let msecs_since_epoch = SystemTime::now().duration_since(time::UNIX_EPOCH)
         .map( |dur| dur.as_secs() * 1_000 + (dur.subsec_nanos() / 1_000_000) as u64)
-        .map_err( |err| format!("{}", err))?;
+        .map_err( |err| CoreError::io("time-ms", &err.to_string()))?;
 
-    let res = make_mal_number(msecs_since_epoch as i32);
+    let res = make_mal_number(msecs_since_epoch as i64);
     debug!("time-ms, res: {:?}", res);
-    Ok(res)  // FIXME datentyp fuer zahlen
+    Ok(res)
 }
 
 fn mal_core_with_meta(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() < 2 {
-        return Err("value and metadate arguments required".to_string());
-    }
+    check_arity("with-meta", args, 2, Some(2))?;
 
     let with_meta = match &args[0] {
         &MalData::FnClosure(ref fnc) =>
@@ -852,6 +818,9 @@ fn mal_core_with_meta(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
         &MalData::Function(ref fun) =>
             MalData::Function(fun.with_meta(&args[1])),
 
+        &MalData::Opaque(ref opaque) =>
+            MalData::Opaque(opaque.with_meta(&args[1])),
+
         &MalData::List(ref lst, _) => {
             make_mal_list_from_vec_with_meta(lst, &args[1])
         }
@@ -861,7 +830,7 @@ fn mal_core_with_meta(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
         }
 
         &MalData::Map(ref map, _) => {
-            make_mal_map_from_map_with_meta(map, &args[1])?
+            make_mal_map_from_map_with_meta(map, &args[1]).map_err( |message| CoreError::illegal_argument("with-meta", &message) )?
         }
 
         _ => {
@@ -874,29 +843,31 @@ fn mal_core_with_meta(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
 }
 
 fn mal_core_meta(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
-    if args.len() != 1 {
-        return Err("value argument required".to_string());
-    }
+    check_arity("meta", args, 1, Some(1))?;
 
     match &args[0] {
         &MalData::FnClosure(ref fnc) => {
-            Ok(fnc.get_meta().map_or(MalData::Nil, |m| *m.clone()))
+            Ok(fnc.get_meta().map_or(MalData::Nil, |m| (*m).clone()))
         }
 
         &MalData::Function(ref fun ) => {
-            Ok(fun.get_meta().map_or(MalData::Nil, |m| *m.clone()))
+            Ok(fun.get_meta().map_or(MalData::Nil, |m| (*m).clone()))
+        }
+
+        &MalData::Opaque(ref opaque) => {
+            Ok(opaque.get_meta().map_or(MalData::Nil, |m| (*m).clone()))
         }
 
         &MalData::List(_, Some(ref meta) ) => {
-            Ok(*meta.clone())
+            Ok((**meta).clone())
         }
 
         &MalData::Vector(_, Some(ref meta) ) => {
-            Ok(*meta.clone())
+            Ok((**meta).clone())
         }
 
         &MalData::Map(_, Some(ref meta) ) => {
-            Ok(*meta.clone())
+            Ok((**meta).clone())
         }
 
         _ =>
@@ -904,6 +875,228 @@ fn mal_core_meta(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
     }
 }
 
+// bytecode einer closure als text, siehe vm::disassemble
+fn mal_core_disassemble(_ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("disassemble", args, 1, Some(1))?;
+
+    match args[0] {
+        MalData::FnClosure(ref fnc) =>
+            vm::disassemble(fnc).map( |listing| MalData::String(Rc::from(listing)) ),
+
+        ref arg =>
+            Err(CoreError::type_error("disassemble", 1, "a fn* closure", arg).into())
+    }
+}
+
+// sammelt die zyklen unerreichbarer umgebungen und atome (siehe gc); liefert deren anzahl
+fn mal_core_gc(_ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("gc", args, 0, Some(0))?;
+
+    Ok(MalData::Number(count_value(gc::collect() as u64)))
+}
+
+fn mal_core_gc_stats(_ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("gc-stats", args, 0, Some(0))?;
+    let stats = gc::stats();
+
+    let kv = [
+        make_mal_keyword("collections"), MalData::Number(count_value(stats.collections)),
+        make_mal_keyword("freed"), MalData::Number(count_value(stats.freed)),
+        make_mal_keyword("scanned"), MalData::Number(count_value(stats.scanned as u64)),
+        make_mal_keyword("tracked"), MalData::Number(count_value(stats.tracked as u64))
+    ];
+
+    make_mal_map_from_kv_list(&mut kv.iter()).map_err( |message| CoreError::illegal_argument("gc-stats", &message).into() )
+}
+
+// multimethoden: (defmulti name dispatch-fn) aus dem prelude legt mit multi-fn eine multimethode
+// an, (defmethod name dispatch-wert [params] rumpf...) ergaenzt sie mit add-method um eine methode.
+// ein aufruf wendet die dispatch-funktion auf die argumente an und ruft die methode fuer deren
+// ergebnis auf, ohne eine solche die fuer :default (bzw. den mit (defmulti name f :default wert)
+// gewaehlten wert). ein keyword als dispatch-funktion liest den wert aus der map im ersten
+// argument:
+//
+//     (defmulti handle :type)
+//     (defmethod handle :click [event] ...)
+//     (defmethod handle :default [event] nil)
+//
+// dispatch-werte vergleicht die multimethode wie =, es taugt also jeder wert, auch ein vektor.
+struct MultiFn {
+    name: Rc<str>,
+    dispatch: Dispatch,
+    default: MalData,
+
+    // (dispatch-wert, methode) in der reihenfolge der definition
+    methods: RefCell<Vec<( MalData, MalData )>>
+}
+
+enum Dispatch {
+    Key(MapKey),
+    Fun(MalData)
+}
+
+impl MultiFn {
+    fn call(&self, ctx: &FunContext, args: &[MalData]) -> MalCoreFunResult {
+        let value = match self.dispatch {
+            Dispatch::Key(ref key) => match args.first() {
+                Some(MalData::Map(map, _)) => map.get(key).cloned().unwrap_or(MalData::Nil),
+                _ => MalData::Nil
+            },
+
+            Dispatch::Fun(ref fun) =>
+                apply_fun(ctx, fun, args)?
+        };
+
+        let method = self.method(&value).ok_or_else( || self.no_method(&value) )?;
+        apply_fun(ctx, &method, args)
+    }
+
+    fn find(&self, value: &MalData) -> Option<MalData> {
+        self.methods.borrow().iter().find( |( dispatch_value, _ )| dispatch_value == value ).map( |( _, method )| method.clone() )
+    }
+
+    fn method(&self, value: &MalData) -> Option<MalData> {
+        self.find(value).or_else( || self.find(&self.default) )
+    }
+
+    fn add(&self, value: &MalData, method: &MalData) {
+        let mut methods = self.methods.borrow_mut();
+
+        match methods.iter_mut().find( |( dispatch_value, _ )| dispatch_value == value ) {
+            Some(entry) => entry.1 = method.clone(),
+            None => methods.push(( value.clone(), method.clone() ))
+        }
+    }
+
+    fn remove(&self, value: &MalData) {
+        self.methods.borrow_mut().retain( |( dispatch_value, _ )| dispatch_value != value );
+    }
+
+    fn no_method(&self, value: &MalData) -> EvalError {
+        let mut known: Vec<String> = self.methods.borrow().iter().map( |( dispatch_value, _ )| pr_str(dispatch_value, true) ).collect();
+        known.sort();
+
+        let message = if known.is_empty() {
+            format!("no method for dispatch value {}, no methods are defined", pr_str(value, true))
+        } else {
+            format!("no method for dispatch value {}, methods exist for {}", pr_str(value, true), known.join(", "))
+        };
+
+        CoreError::illegal_argument(&self.name, &message).into()
+    }
+
+    // die vom zyklensammler zu durchlaufenden werte: dispatch-funktion und methoden
+    fn children(&self, visit: &mut VisitFun) {
+        if let Dispatch::Fun(ref fun) = self.dispatch {
+            visit(fun);
+        }
+
+        // waehrend add-method gilt die multimethode als gehalten
+        if let Ok(methods) = self.methods.try_borrow() {
+            for ( dispatch_value, method ) in methods.iter() {
+                visit(dispatch_value);
+                visit(method);
+            }
+        }
+    }
+}
+
+fn multi_arg(fun: &str, args: &[MalData], index: usize) -> Result<Rc<MultiFn>, CoreError> {
+    let arg = arg_at(fun, args, index)?;
+
+    match *arg {
+        MalData::Function(ref function) => function.state::<MultiFn>(),
+        _ => None
+    }.ok_or_else( || CoreError::type_error(fun, index + 1, "a multimethod", arg) )
+}
+
+// (multi-fn 'name dispatch-fn) oder (multi-fn 'name dispatch-fn :default wert)
+#[allow(unused_variables)]
+fn mal_core_multi_fn(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("multi-fn", args, 2, Some(4))?;
+
+    let name = match args[0] {
+        MalData::Symbol(ref name) => name.clone(),
+        ref other => return Err(CoreError::type_error("multi-fn", 1, "a symbol", other).into())
+    };
+
+    let dispatch = match args[1] {
+        MalData::Keyword(_) => Dispatch::Key(map_key_arg("multi-fn", args, 1)?),
+        MalData::Function(_) | MalData::FnClosure(_) => Dispatch::Fun(args[1].clone()),
+        ref other => return Err(CoreError::type_error("multi-fn", 2, "a function or keyword", other).into())
+    };
+
+    let default = match args[2..] {
+        [] => make_mal_keyword("default"),
+        [ref option, ref value] if *option == make_mal_keyword("default") => value.clone(),
+        _ => return Err(CoreError::illegal_argument("multi-fn", "the only option is :default followed by a dispatch value").into())
+    };
+
+    let state = Opaque::new("multimethod", MultiFn { name: name.clone(), dispatch, default, methods: RefCell::new(Vec::new()) })
+        .with_children(MultiFn::children);
+
+    // die funktion haelt die multimethode nur ueber ihren zustand, den der zyklensammler sieht
+    let multi = Rc::downgrade(&state.downcast::<MultiFn>().expect("state holds the multimethod"));
+    let multi_name = name.clone();
+    let callable: Rc<CallableFun> = Rc::new(move |ctx: &FunContext, args: &[MalData]| match multi.upgrade() {
+        Some(multi) => multi.call(ctx, args),
+        None => Err(CoreError::illegal_argument(&multi_name, "the state of this multimethod no longer exists").into())
+    });
+
+    Ok(MalData::Function(NativeFunction::new(&name, callable).with_state(state)))
+}
+
+// (add-method multi dispatch-wert fn), ersetzt eine vorhandene methode fuer den wert
+#[allow(unused_variables)]
+fn mal_core_add_method(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("add-method", args, 3, Some(3))?;
+    let multi = multi_arg("add-method", args, 0)?;
+
+    match args[2] {
+        MalData::Function(_) | MalData::FnClosure(_) => multi.add(&args[1], &args[2]),
+        ref other => return Err(CoreError::type_error("add-method", 3, "a function", other).into())
+    };
+
+    Ok(args[0].clone())
+}
+
+#[allow(unused_variables)]
+fn mal_core_remove_method(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("remove-method", args, 2, Some(2))?;
+    let multi = multi_arg("remove-method", args, 0)?;
+
+    multi.remove(&args[1]);
+    Ok(args[0].clone())
+}
+
+// [dispatch-wert methode]-paare in der reihenfolge der definition; eine map kann nicht jeden
+// dispatch-wert als schluessel aufnehmen
+#[allow(unused_variables)]
+fn mal_core_methods(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("methods", args, 1, Some(1))?;
+    let multi = multi_arg("methods", args, 0)?;
+
+    let methods = multi.methods.borrow().iter()
+        .map( |( dispatch_value, method )| make_mal_vector_from_slice(&[dispatch_value.clone(), method.clone()]) )
+        .collect();
+
+    Ok(make_mal_list_from_vec(methods))
+}
+
+// methode, die ein aufruf mit dem dispatch-wert waehlt, oder nil
+#[allow(unused_variables)]
+fn mal_core_get_method(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
+    check_arity("get-method", args, 2, Some(2))?;
+    let multi = multi_arg("get-method", args, 0)?;
+
+    Ok(multi.method(&args[1]).unwrap_or(MalData::Nil))
+}
+
+// zaehler jenseits des zahlenbereichs bleiben beim groessten wert stehen
+fn count_value(count: u64) -> i64 {
+    count.min(i64::MAX as u64) as i64
+}
+
 // fn mal_core_(ctx: &FunContext, args: & [MalData]) -> MalCoreFunResult {
 // }
 
@@ -931,6 +1124,7 @@ pub fn init_ns_map() -> HashMap<&'static str, Rc<CallableFun>> {
 
     ns_map.insert("read-string", Rc::new(mal_core_read_string));
     ns_map.insert("slurp", Rc::new(mal_core_slurp));
+    ns_map.insert("spit", Rc::new(mal_core_spit));
 
     ns_map.insert("atom", Rc::new(mal_core_atom));
     ns_map.insert("atom?", Rc::new(mal_core_atom_p));
@@ -946,6 +1140,11 @@ pub fn init_ns_map() -> HashMap<&'static str, Rc<CallableFun>> {
     ns_map.insert("rest", Rc::new(mal_core_rest));
 
     ns_map.insert("throw", Rc::new(mal_core_throw));
+    ns_map.insert("ex-info", Rc::new(mal_core_ex_info));
+    ns_map.insert("ex-message", Rc::new(mal_core_ex_message));
+    ns_map.insert("ex-data", Rc::new(mal_core_ex_data));
+    ns_map.insert("ex-cause", Rc::new(mal_core_ex_cause));
+    ns_map.insert("ex-stack", Rc::new(mal_core_ex_stack));
     ns_map.insert("apply", Rc::new(mal_core_apply));
     ns_map.insert("map", Rc::new(mal_core_map));
     ns_map.insert("nil?", Rc::new(mal_core_nil_p));
@@ -971,21 +1170,90 @@ pub fn init_ns_map() -> HashMap<&'static str, Rc<CallableFun>> {
     ns_map.insert("string?", Rc::new(mal_core_string_p));
     ns_map.insert("seq", Rc::new(mal_core_seq));
     ns_map.insert("conj", Rc::new(mal_core_conj));
+    ns_map.insert("type", Rc::new(mal_core_type));
+    ns_map.insert("satisfies?", Rc::new(mal_core_satisfies_p));
+
+    ns_map.insert("multi-fn", Rc::new(mal_core_multi_fn));
+    ns_map.insert("add-method", Rc::new(mal_core_add_method));
+    ns_map.insert("remove-method", Rc::new(mal_core_remove_method));
+    ns_map.insert("methods", Rc::new(mal_core_methods));
+    ns_map.insert("get-method", Rc::new(mal_core_get_method));
 
     ns_map.insert("time-ms", Rc::new(mal_core_time_ms));
     ns_map.insert("meta", Rc::new(mal_core_meta));
     ns_map.insert("with-meta", Rc::new(mal_core_with_meta));
+    ns_map.insert("disassemble", Rc::new(mal_core_disassemble));
+    ns_map.insert("gc", Rc::new(mal_core_gc));
+    ns_map.insert("gc-stats", Rc::new(mal_core_gc_stats));
 
     ns_map
 }
 
 
-fn number_arg(arg: &MalData) -> Option<i32> {
+// pruefung der argumente; `index` ist die position in args (ab 0), in fehlermeldungen wird ab 1 gezaehlt
+
+fn check_arity(fun: &str, args: &[MalData], min: usize, max: Option<usize>) -> Result<(), CoreError> {
+    if args.len() < min || max.is_some_and( |max| args.len() > max ) {
+        Err(CoreError::arity(fun, min, max, args.len()))
+    } else {
+        Ok(())
+    }
+}
+
+fn arg_at<'a>(fun: &str, args: &'a [MalData], index: usize) -> Result<&'a MalData, CoreError> {
+    args.get(index).ok_or_else( || CoreError::arity(fun, index + 1, None, args.len()) )
+}
+
+fn number_arg(fun: &str, args: &[MalData], index: usize) -> Result<i64, CoreError> {
+    let arg = arg_at(fun, args, index)?;
     trace!("number_arg, arg: {:?}", arg);
 
     if let &MalData::Number(num) = arg {
-        Some(num)
+        Ok(num)
     } else {
-        None
+        Err(CoreError::type_error(fun, index + 1, "a number", arg))
     }
 }
+
+// die beiden zahlen-argumente der arithmetik- und vergleichsfunktionen
+fn number_args(fun: &str, args: &[MalData]) -> Result<( i64, i64 ), CoreError> {
+    check_arity(fun, args, 2, Some(2))?;
+
+    Ok(( number_arg(fun, args, 0)?, number_arg(fun, args, 1)? ))
+}
+
+fn overflow_error(fun: &str) -> CoreError {
+    CoreError::Arithmetic { fun: fun.to_owned(), message: "integer overflow".to_owned() }
+}
+
+fn string_arg<'a>(fun: &str, args: &'a [MalData], index: usize) -> Result<&'a str, CoreError> {
+    let arg = arg_at(fun, args, index)?;
+
+    if let &MalData::String(ref string) = arg {
+        Ok(string)
+    } else {
+        Err(CoreError::type_error(fun, index + 1, "a string", arg))
+    }
+}
+
+fn seq_arg<'a>(fun: &str, args: &'a [MalData], index: usize) -> Result<&'a Vec<MalData>, CoreError> {
+    let arg = arg_at(fun, args, index)?;
+
+    get_wrapped_list(arg).ok_or_else( || CoreError::type_error(fun, index + 1, "a list or vector", arg) )
+}
+
+fn map_arg<'a>(fun: &str, args: &'a [MalData], index: usize) -> Result<&'a HashMap<MapKey, MalData>, CoreError> {
+    let arg = arg_at(fun, args, index)?;
+
+    if let &MalData::Map(ref map, _) = arg {
+        Ok(map)
+    } else {
+        Err(CoreError::type_error(fun, index + 1, "a map", arg))
+    }
+}
+
+fn map_key_arg(fun: &str, args: &[MalData], index: usize) -> Result<MapKey, CoreError> {
+    let arg = arg_at(fun, args, index)?;
+
+    mapkey_for(arg).map_err( |_| CoreError::type_error(fun, index + 1, "a string, keyword, symbol, number or boolean", arg) )
+}
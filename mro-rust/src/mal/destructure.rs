@@ -0,0 +1,397 @@
+// destrukturierung in den bindungen von let*, loop* und fn*. ein muster wird vor der analyse in eine
+// folge einfacher bindungen uebersetzt, deren werte von kleinen nativen funktionen aus dem wert
+// des musters geholt werden:
+//
+//     [a [b c] & rest :as all]           sequentiell, fehlende elemente sind nil
+//     {:keys [x y] :or {y 0} :as m}      assoziativ, ebenso :strs, :syms und {name key}
+//
+// die funktionen stehen als werte in den erzeugten formen, ein lokaler name kann sie daher nicht
+// verdecken. hilfsnamen enthalten ein leerzeichen und kollidieren so mit keinem namen des
+// quelltexts.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use env::Symbol;
+use common::{MalData, MapKey, NativeFunction, CallableFun, FunContext};
+use common::{make_mal_list_from_vec, make_mal_symbol, make_mal_keyword, make_mal_string, mapkey_for, mal_value_for};
+use common::{is_mal_symbol, mal_symbol_name};
+use printer::pr_str;
+use eval::{CoreError, EvalError};
+
+pub fn is_pattern(form: &MalData) -> bool {
+    matches!(*form, MalData::Vector(..) | MalData::Map(..))
+}
+
+// die bindungen [name wert ...] eines let*, muster zerlegt in einfache bindungen
+pub fn bindings(bindings: &[MalData]) -> Result<Vec<MalData>, CoreError> {
+    let mut destructurer = Destructurer { temps: 0, bindings: Vec::new() };
+
+    for pair in bindings.chunks(2) {
+        match *pair {
+            [ref pattern, ref value] if is_pattern(pattern) =>
+                destructurer.bind(pattern, value.clone())?,
+
+            _ =>
+                destructurer.bindings.extend_from_slice(pair)
+        }
+    }
+
+    Ok(destructurer.bindings)
+}
+
+// die parameter einer fn*: ein muster wird durch einen hilfsnamen ersetzt und in einem let* um
+// den rumpf zerlegt. liefert die namen und die bindungen dieses let*, leer ohne muster
+pub fn params(binds: &[MalData]) -> Result<( Vec<Symbol>, Vec<MalData> ), CoreError> {
+    let mut names = Vec::with_capacity(binds.len());
+    let mut bindings = Vec::new();
+
+    for ( i, bind ) in binds.iter().enumerate() {
+        match *bind {
+            MalData::Symbol(ref sym) =>
+                names.push(sym.to_string()),
+
+            ref pattern if is_pattern(pattern) => {
+                let name = format!("arg {}", i);
+
+                bindings.push(pattern.clone());
+                bindings.push(make_mal_symbol(&name));
+                names.push(name);
+            }
+
+            ref other =>
+                return Err(binding_error(format!("unsupported binding form {} in fn* parameters", pr_str(other, true))))
+        }
+    }
+
+    Ok(( names, bindings ))
+}
+
+// ein loop* mit mustern wird zu
+//
+//     (let* [t1 v1, p1 t1, ...] (loop* [t1 t1, ...] (let* [p1 t1, ...] body)))
+//
+// die anfangswerte werden also wie in let* der reihe nach zerlegt; recur uebergibt je muster einen
+// wert, der zu beginn jedes durchlaufs zerlegt wird. None ohne muster
+pub fn loop_form(bindings: &[MalData], body: &MalData) -> Option<MalData> {
+    if !bindings.chunks(2).any( |pair| pair.len() == 2 && is_pattern(&pair[0]) ) {
+        return None;
+    }
+
+    let ( mut outer, mut loop_bindings, mut inner ) = ( Vec::new(), Vec::new(), Vec::new() );
+
+    for ( i, pair ) in bindings.chunks(2).enumerate() {
+        match *pair {
+            [ref pattern, ref value] if is_pattern(pattern) => {
+                let temp = make_mal_symbol(&format!("loop {}", i));
+
+                outer.extend(vec![temp.clone(), value.clone(), pattern.clone(), temp.clone()]);
+                loop_bindings.extend(vec![temp.clone(), temp.clone()]);
+                inner.extend(vec![pattern.clone(), temp]);
+            }
+
+            [ref name, ref value] => {
+                outer.extend(vec![name.clone(), value.clone()]);
+                loop_bindings.extend(vec![name.clone(), name.clone()]);
+            }
+
+            // unvollstaendige bindung, loop* meldet den fehler
+            _ =>
+                loop_bindings.extend_from_slice(pair)
+        }
+    }
+
+    let inner = list(vec![make_mal_symbol("let*"), vector(inner), body.clone()]);
+    let looped = list(vec![make_mal_symbol("loop*"), vector(loop_bindings), inner]);
+
+    Some(list(vec![make_mal_symbol("let*"), vector(outer), looped]))
+}
+
+struct Destructurer {
+    temps: usize,
+    bindings: Vec<MalData>
+}
+
+impl Destructurer {
+    fn temp(&mut self) -> MalData {
+        self.temps += 1;
+        make_mal_symbol(&format!("destructure {}", self.temps - 1))
+    }
+
+    fn push(&mut self, name: MalData, value: MalData) {
+        self.bindings.push(name);
+        self.bindings.push(value);
+    }
+
+    fn bind(&mut self, pattern: &MalData, value: MalData) -> Result<(), CoreError> {
+        match *pattern {
+            MalData::Symbol(_) => {
+                self.push(pattern.clone(), value);
+                Ok(())
+            }
+
+            MalData::Vector(ref items, _) =>
+                self.bind_seq(pattern, items, value),
+
+            MalData::Map(ref entries, _) =>
+                self.bind_map(pattern, entries, value),
+
+            _ =>
+                Err(binding_error(format!("unsupported binding form {}", pr_str(pattern, true))))
+        }
+    }
+
+    // [a b & rest :as all]
+    fn bind_seq(&mut self, pattern: &MalData, items: &[MalData], value: MalData) -> Result<(), CoreError> {
+        let mut items = items;
+        let mut whole = None;
+
+        if items.len() >= 2 && is_keyword_named(&items[items.len() - 2], "as") {
+            match items[items.len() - 1] {
+                MalData::Symbol(_) => whole = Some(items[items.len() - 1].clone()),
+                _ => return Err(binding_error(format!(":as must be followed by a symbol in {}", pr_str(pattern, true))))
+            }
+
+            items = &items[..items.len() - 2];
+        }
+
+        let ( fixed, rest ) = match items.iter().position( |item| is_symbol_named(item, "&") ) {
+            Some(at) if at + 2 == items.len() =>
+                ( &items[..at], Some(&items[at + 1]) ),
+
+            Some(_) =>
+                return Err(binding_error(format!("& must be followed by exactly one binding form in {}", pr_str(pattern, true)))),
+
+            None =>
+                ( items, None )
+        };
+
+        if let Some(item) = fixed.iter().chain(rest).find( |item| !is_mal_symbol(item) && !is_pattern(item) ) {
+            return Err(binding_error(format!("unsupported binding form {} in {}", pr_str(item, true), pr_str(pattern, true))));
+        }
+
+        let seq = whole.unwrap_or_else( || self.temp() );
+        self.push(seq.clone(), call(check_seq(pattern), vec![value]));
+
+        for ( i, item ) in fixed.iter().enumerate() {
+            self.bind(item, call(native("nth", nth), vec![seq.clone(), MalData::Number(i as i64)]))?;
+        }
+
+        if let Some(rest) = rest {
+            self.bind(rest, call(native("nthrest", rest_from), vec![seq, MalData::Number(fixed.len() as i64)]))?;
+        }
+
+        Ok(())
+    }
+
+    // {:keys [x] :strs [y] :syms [z] name key :or {x 0} :as m}
+    fn bind_map(&mut self, pattern: &MalData, entries: &HashMap<MapKey, MalData>, value: MalData) -> Result<(), CoreError> {
+        let mut whole = None;
+        let mut defaults = None;
+        let mut lookups = Vec::new();
+
+        // in fester reihenfolge, damit die bindungen nicht von der reihenfolge der hashmap abhaengen
+        let mut keys: Vec<&MapKey> = entries.keys().collect();
+        keys.sort_by_key( |key| format!("{:?}", key) );
+
+        for key in keys {
+            let entry = &entries[key];
+
+            match *key {
+                MapKey::Keyword(ref kw) => match keyword_name(kw) {
+                    "as" => match *entry {
+                        MalData::Symbol(_) => whole = Some(entry.clone()),
+                        _ => return Err(binding_error(format!(":as must be followed by a symbol in {}", pr_str(pattern, true))))
+                    },
+
+                    "or" => match *entry {
+                        MalData::Map(ref map, _) => defaults = Some(map.clone()),
+                        _ => return Err(binding_error(format!(":or must be followed by a map in {}", pr_str(pattern, true))))
+                    },
+
+                    option @ "keys" | option @ "strs" | option @ "syms" => {
+                        let names = match *entry {
+                            MalData::Vector(ref names, _) | MalData::List(ref names, _) if names.iter().all(is_mal_symbol) =>
+                                names,
+
+                            _ =>
+                                return Err(binding_error(format!(":{} must be followed by a vector of symbols in {}", option, pr_str(pattern, true))))
+                        };
+
+                        for name in names.iter() {
+                            let text = symbol_name(name);
+
+                            let lookup = match option {
+                                "keys" => make_mal_keyword(&text),
+                                "strs" => make_mal_string(&text),
+                                _ => quote(make_mal_symbol(&text))
+                            };
+
+                            lookups.push(( name.clone(), lookup ));
+                        }
+                    }
+
+                    option =>
+                        return Err(binding_error(format!("unknown option :{} in {}", option, pr_str(pattern, true))))
+                },
+
+                MapKey::Symbol(ref name) =>
+                    lookups.push(( make_mal_symbol(name), quote(entry.clone()) )),
+
+                _ =>
+                    return Err(binding_error(format!("unsupported binding form {} in {}", pr_str(&mal_key(key), true), pr_str(pattern, true))))
+            }
+        }
+
+        // :or nennt nur namen, die das muster bindet
+        if let Some(ref defaults) = defaults {
+            for name in defaults.keys() {
+                let bound = match *name {
+                    MapKey::Symbol(ref name) => lookups.iter().any( |( bound, _ )| symbol_name(bound) == **name ),
+                    _ => false
+                };
+
+                if !bound {
+                    return Err(binding_error(format!(":or names {}, which is not bound by {}", pr_str(&mal_key(name), true), pr_str(pattern, true))));
+                }
+            }
+        }
+
+        let map = whole.unwrap_or_else( || self.temp() );
+        self.push(map.clone(), call(check_map(pattern), vec![value]));
+
+        for ( name, lookup ) in lookups {
+            let default = defaults.as_ref().and_then( |defaults| defaults.get(&MapKey::Symbol(Rc::from(symbol_name(&name)))) );
+
+            let mut args = vec![map.clone(), lookup];
+            args.extend(default.cloned());
+
+            self.push(name, call(native("get", get_or), args));
+        }
+
+        Ok(())
+    }
+}
+
+fn binding_error(message: String) -> CoreError {
+    CoreError::Binding { message }
+}
+
+fn list(items: Vec<MalData>) -> MalData {
+    make_mal_list_from_vec(items)
+}
+
+fn vector(items: Vec<MalData>) -> MalData {
+    MalData::Vector(Rc::new(items), None)
+}
+
+fn quote(form: MalData) -> MalData {
+    list(vec![make_mal_symbol("quote"), form])
+}
+
+fn call(fun: MalData, args: Vec<MalData>) -> MalData {
+    list(Some(fun).into_iter().chain(args).collect())
+}
+
+fn native<F: Fn(&[MalData]) -> Result<MalData, CoreError> + 'static>(name: &str, fun: F) -> MalData {
+    let callable: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, args: &[MalData]| fun(args).map_err(EvalError::from) );
+    MalData::Function(NativeFunction::new(name, callable).internal())
+}
+
+fn mal_key(key: &MapKey) -> MalData {
+    mal_value_for(key)
+}
+
+fn symbol_name(sym: &MalData) -> String {
+    mal_symbol_name(sym).unwrap_or_default()
+}
+
+fn is_symbol_named(form: &MalData, name: &str) -> bool {
+    if let MalData::Symbol(ref sym) = *form { &**sym == name } else { false }
+}
+
+fn is_keyword_named(form: &MalData, name: &str) -> bool {
+    if let MalData::Keyword(ref kw) = *form { keyword_name(kw) == name } else { false }
+}
+
+// name eines keywords ohne das kennzeichnende erste zeichen
+fn keyword_name(kw: &str) -> &str {
+    kw.char_indices().nth(1).map_or("", |( at, _ )| &kw[at..])
+}
+
+
+// zur laufzeit aufgerufene funktionen; die pruefenden nennen das muster in ihrer fehlermeldung
+
+fn check_seq(pattern: &MalData) -> MalData {
+    let text = pr_str(pattern, true);
+
+    native("destructure", move |args| match args[0] {
+        MalData::List(..) | MalData::Vector(..) | MalData::Nil =>
+            Ok(args[0].clone()),
+
+        ref other =>
+            Err(binding_error(format!("{} expects a sequence, got {}", text, other.type_name())))
+    })
+}
+
+// eine folge von schluesseln und werten, z.b. die restlichen argumente einer fn*, gilt als map
+fn check_map(pattern: &MalData) -> MalData {
+    let text = pr_str(pattern, true);
+
+    native("destructure", move |args| match args[0] {
+        MalData::Map(..) | MalData::Nil =>
+            Ok(args[0].clone()),
+
+        MalData::List(ref items, _) | MalData::Vector(ref items, _) if items.len() % 2 == 0 => {
+            let mut map = HashMap::with_capacity(items.len() / 2);
+
+            for pair in items.chunks(2) {
+                let key = mapkey_for(&pair[0]).map_err( |err| binding_error(format!("{}: {}", text, err)) )?;
+                map.insert(key, pair[1].clone());
+            }
+
+            Ok(MalData::Map(Rc::new(map), None))
+        }
+
+        MalData::List(..) | MalData::Vector(..) =>
+            Err(binding_error(format!("{} expects a map, got a sequence of odd length", text))),
+
+        ref other =>
+            Err(binding_error(format!("{} expects a map, got {}", text, other.type_name())))
+    })
+}
+
+fn nth(args: &[MalData]) -> Result<MalData, CoreError> {
+    match ( &args[0], &args[1] ) {
+        ( MalData::List(items, _), &MalData::Number(i) ) | ( MalData::Vector(items, _), &MalData::Number(i) ) =>
+            Ok(items.get(i as usize).cloned().unwrap_or(MalData::Nil)),
+
+        _ =>
+            Ok(MalData::Nil)
+    }
+}
+
+// die elemente ab `n` als liste, nil wenn keine uebrig sind
+fn rest_from(args: &[MalData]) -> Result<MalData, CoreError> {
+    match ( &args[0], &args[1] ) {
+        ( MalData::List(items, _), &MalData::Number(n) ) | ( MalData::Vector(items, _), &MalData::Number(n) ) if items.len() > n as usize =>
+            Ok(make_mal_list_from_vec(items[n as usize..].to_vec())),
+
+        _ =>
+            Ok(MalData::Nil)
+    }
+}
+
+fn get_or(args: &[MalData]) -> Result<MalData, CoreError> {
+    let default = args.get(2).cloned().unwrap_or(MalData::Nil);
+
+    match args[0] {
+        MalData::Map(ref map, _) => {
+            let key = mapkey_for(&args[1]).map_err(binding_error)?;
+            Ok(map.get(&key).cloned().unwrap_or(default))
+        }
+
+        _ =>
+            Ok(default)
+    }
+}
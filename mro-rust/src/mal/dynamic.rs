@@ -0,0 +1,108 @@
+// dynamische vars: ein mit (def! ^:dynamic name wert) definierter globaler name laesst sich mit
+// (binding [name wert ...] rumpf...) fuer die dauer des rumpfs neu binden. alle aus dem rumpf
+// aufgerufenen funktionen sehen die neuen werte.
+//
+// jeder globale name hat einen stapel von bindungen, deren oberste seinen eintrag in der globalen
+// tabelle verdeckt: binding legt die werte darauf und nimmt sie beim verlassen des rumpfs, auch
+// durch einen fehler, wieder ab. ein def! des namens im rumpf aendert den eintrag der tabelle, der
+// nach dem binding weiter gilt. die globale tabelle gehoert zum interpreter und damit zu dessen
+// thread.
+//
+// ob ein name dynamisch ist, steht bereits bei der analyse des def! fest; ein binding auf einen
+// anderen namen ist ein fehler der analyse. ein aus einem anderen namensraum referierter name wird
+// dort gebunden, wo er definiert ist.
+
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use env::{Env, EnvType, SymbolId};
+use common::{MalData, NativeFunction, CallableFun, FunContext};
+use common::{make_mal_list_from_vec, make_mal_symbol, make_mal_keyword, mapkey_for};
+use eval::EvalError;
+
+// metadaten ^:dynamic oder ^{:dynamic true} am namen eines def!
+pub fn is_dynamic_meta(meta: &MalData) -> bool {
+    let dynamic = make_mal_keyword("dynamic");
+
+    match *meta {
+        MalData::Map(ref map, _) =>
+            mapkey_for(&dynamic).ok().and_then( |key| map.get(&key) ).is_some_and( |value| *value == MalData::True ),
+
+        ref meta =>
+            *meta == dynamic
+    }
+}
+
+// (binding [name wert ...] rumpf...) wird zu (do (push wert ...) (try* (do rumpf...) (finally* (pop)))),
+// der rumpf steht also in derselben umgebung wie das binding. push und pop sind native funktionen,
+// die die bei der analyse aufgeloesten namen kennen; erst nachdem alle werte ausgewertet sind,
+// bindet push sie der reihe nach.
+pub fn binding_form(env: &EnvType, args: &[MalData]) -> Result<MalData, EvalError> {
+    let bindings = match args.first() {
+        Some(MalData::Vector(bindings, _)) if bindings.len() % 2 == 0 => bindings,
+        _ => return Err(EvalError::General("binding requires a vector of name/value pairs".to_owned()))
+    };
+
+    let mut vars = Vec::with_capacity(bindings.len() / 2);
+    let mut push = vec![MalData::Nil];
+
+    for pair in bindings.chunks(2) {
+        let id = match pair[0] {
+            MalData::Symbol(ref name) if Env::is_dynamic(env, SymbolId::intern(name)) => SymbolId::intern(name),
+
+            MalData::Symbol(ref name) =>
+                return Err(EvalError::General(format!("binding: {} is not a dynamic var, define it with ^:dynamic", name))),
+
+            ref other =>
+                return Err(EvalError::General(format!("binding: expected a symbol, got {}", other.type_name())))
+        };
+
+        // die globale tabelle des namensraums, in dem der name definiert ist
+        let ( home, id ) = Env::resolve_global(env, id);
+        vars.push(( Rc::downgrade(&home), id ));
+        push.push(pair[1].clone());
+    }
+
+    let vars = Rc::new(vars);
+    push[0] = native_push(vars.clone());
+
+    let body = make_mal_list_from_vec(Some(make_mal_symbol("do")).into_iter().chain(args[1..].iter().cloned()).collect());
+    let finally = make_mal_list_from_vec(vec![make_mal_symbol("finally*"), make_mal_list_from_vec(vec![native_pop(vars)])]);
+
+    Ok(make_mal_list_from_vec(vec![
+        make_mal_symbol("do"),
+        make_mal_list_from_vec(push),
+        make_mal_list_from_vec(vec![make_mal_symbol("try*"), body, finally])
+    ]))
+}
+
+type Vars = Rc<Vec<( Weak<RefCell<Env>>, SymbolId )>>;
+
+fn native_push(vars: Vars) -> MalData {
+    let callable: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, args: &[MalData]| {
+        for ( ( home, id ), value ) in vars.iter().zip(args) {
+            Env::push_binding(&upgrade(home)?, *id, value.clone());
+        }
+
+        Ok(MalData::Nil)
+    });
+
+    MalData::Function(NativeFunction::new("binding", callable))
+}
+
+// loest die bindungen in umgekehrter reihenfolge wieder
+fn native_pop(vars: Vars) -> MalData {
+    let callable: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, _args: &[MalData]| {
+        for &( ref home, id ) in vars.iter().rev() {
+            Env::pop_binding(&upgrade(home)?, id);
+        }
+
+        Ok(MalData::Nil)
+    });
+
+    MalData::Function(NativeFunction::new("binding", callable))
+}
+
+fn upgrade(home: &Weak<RefCell<Env>>) -> Result<EnvType, EvalError> {
+    home.upgrade().ok_or_else( || EvalError::General("binding: the namespace of this var no longer exists".to_owned()) )
+}
@@ -1,111 +1,370 @@
-use std::collections::HashMap;
-use std::hash::Hash;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
 use std::rc::Rc;
 use std::cell::RefCell;
 use common::MalData;
+use eval::CoreError;
+use namespace::Namespace;
 
 pub type Symbol = String;
-type EnvKey = Symbol;
 pub type EnvType = Rc<RefCell<Env>>;
 
 
+// symbolnamen werden beim ersten auftreten auf kleine zahlen abgebildet, die fuer die laufzeit des
+// threads gelten; umgebungen vergleichen und indizieren nur noch diese zahlen
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+
+struct Interner {
+    ids: HashMap<Rc<str>, SymbolId>,
+    names: Vec<Rc<str>>
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner { ids: HashMap::new(), names: Vec::new() });
+}
+
+impl SymbolId {
+    pub fn intern(name: &str) -> SymbolId {
+        INTERNER.with( |interner| {
+            let mut interner = interner.borrow_mut();
+
+            if let Some(&id) = interner.ids.get(name) {
+                return id;
+            }
+
+            let id = SymbolId(interner.names.len() as u32);
+            let name: Rc<str> = Rc::from(name);
+
+            interner.names.push(name.clone());
+            interner.ids.insert(name, id);
+
+            id
+        })
+    }
+
+    pub fn name(&self) -> Rc<str> {
+        INTERNER.with( |interner| interner.borrow().names[self.index()].clone() )
+    }
+
+    fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl fmt::Debug for SymbolId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}#{}", self.name(), self.0)
+    }
+}
+
+
+// die aeusserste umgebung haelt die globalen namen in einer nach SymbolId indizierten tabelle.
+// eine lokale umgebung (fn*, let*, catch*) haelt ihre bindungen in slots, deren anordnung die
+// analyse kennt (siehe analyze::Var); die suche nach dem namen bleibt fuer formen moeglich, die
+// erst zur laufzeit analysiert werden.
 #[derive(Debug, Clone)]
-pub struct Env<K=Symbol, V=Rc<MalData>> where K: Eq+Hash {
+pub struct Env {
     outer: Option<EnvType>,
-    data: HashMap<K, V>
+
+    // die aeusserste umgebung der kette; None in dieser selbst
+    root: Option<EnvType>,
+
+    // ein slot ohne wert gehoert zu einem def!, das noch nicht ausgefuehrt wurde
+    slots: Vec<( SymbolId, Option<MalData> )>,
+
+    globals: Vec<Option<MalData>>,
+
+    // mit binding gebundene werte globaler namen, der innerste zuletzt; sie verdecken den eintrag
+    // der globalen tabelle, ohne ihn zu ersetzen (siehe dynamic)
+    bindings: HashMap<SymbolId, Vec<MalData>>,
+
+    // die mit ^:dynamic definierten globalen namen (siehe dynamic)
+    dynamic: HashSet<SymbolId>,
+
+    // der namensraum einer aeussersten umgebung, ueber den nicht selbst definierte namen gesucht
+    // werden (siehe namespace)
+    ns: Option<Rc<Namespace>>
 }
 
 impl Env {
-    pub fn new(outer: Option<EnvType>, binds: &[Symbol], exprs: &[MalData]) -> Result<Env, String> {
-        let mut env = Env { outer: outer, data: HashMap::new() };
+    pub fn new(outer: Option<EnvType>, binds: &[Symbol], exprs: &[MalData]) -> Result<Env, CoreError> {
+        let ( params, variadic ) = params(binds)?;
+        Env::with_args(outer, &params, variadic, exprs)
+    }
 
-        let mut bi = binds.iter();
-        let mut ei = exprs.iter();
+    // bindet `params` der reihe nach an `args`; bei `variadic` erhaelt der letzte parameter die
+    // uebrigen argumente als liste, oder nil, wenn keine uebrig sind
+    pub fn with_args(outer: Option<EnvType>, params: &[SymbolId], variadic: bool, args: &[MalData]) -> Result<Env, CoreError> {
+        let required = if variadic { params.len() - 1 } else { params.len() };
 
-        loop {
-            match ( bi.next(), ei.next() ) {
-                ( Some(and), None ) if and == "&" => {
-                    if let Some(rest_bind) = bi.next() {
-                        env.set(rest_bind, &MalData::Nil);
+        if args.len() < required || (!variadic && args.len() > required) {
+            let max = if variadic { None } else { Some(required) };
+            return Err(CoreError::arity("fn*", required, max, args.len()));
+        }
 
-                        break;
-                    } else {
-                        return Err("symbol expected for rest bind".to_owned())
-                    }
-                }
+        let mut env = Env::empty(outer);
+        env.slots.reserve(params.len());
 
-                ( Some(and), Some(rest_first) ) if and == "&" => {
-                    if let Some(rest_bind) = bi.next() {
-                        let mut rest_exprs: Vec<MalData> = Vec::new();
+        for ( param, arg ) in params.iter().zip(args) {
+            debug!("Env::with_args, bind {} -> {:?}", param, arg);
+            env.set_id(*param, arg);
+        }
 
-                        rest_exprs.push(rest_first.clone());
+        if variadic {
+            let rest = if args.len() > required {
+                MalData::List(Rc::from(args[required..].to_vec()), None)
+            } else {
+                MalData::Nil
+            };
 
-                        while let Some(expr) = ei.next() {
-                            rest_exprs.push(expr.clone());
-                        }
+            env.set_id(params[required], &rest);
+        }
 
-                        env.set(rest_bind, &MalData::List(Rc::from(rest_exprs), None));
+        Ok(env)
+    }
 
-                        break;
-                    } else {
-                        return Err("symbol expected for rest bind".to_owned())
-                    }
-                }
+    // aeusserste umgebung des namensraums `ns`
+    pub fn for_namespace(ns: Rc<Namespace>) -> Env {
+        Env { ns: Some(ns), ..Env::empty(None) }
+    }
 
-                ( Some(bind), Some(expr) ) => {
-                    debug!("Env::new, bind {:?} -> {:?}", bind, expr);
-                    env.set(&bind.clone(), expr);
-                }
+    // leere lokale umgebung, z.b. fuer let*
+    pub fn local(outer: EnvType) -> Env {
+        Env::empty(Some(outer))
+    }
 
-                ( None, None) =>
-                    break,
+    fn empty(outer: Option<EnvType>) -> Env {
+        let root = outer.as_ref().map( |outer| outer.borrow().root.clone().unwrap_or_else( || outer.clone() ) );
 
-                ( bind, expr) =>
-                    return Err(format!("illegal binding: {:?}, {:?}", bind, expr))
-            }
-        };
+        Env { outer, root, slots: Vec::new(), globals: Vec::new(), bindings: HashMap::new(), dynamic: HashSet::new(), ns: None }
+    }
 
-        Ok(env)
+    pub fn is_root(&self) -> bool {
+        self.root.is_none()
     }
 
-    pub fn set(&mut self, key: &EnvKey, value: &MalData) -> () {
-        // debug!("env.set, data: {:?}, k: {:?}, v: {:?}", self.data, key, value);
-        self.data.insert(key.clone(), Rc::from(value.clone()));
+    pub fn set(&mut self, key: &str, value: &MalData) -> () {
+        self.set_id(SymbolId::intern(key), value)
     }
 
-    pub fn find(env: &EnvType, key: &EnvKey) -> Option<EnvType> {
-        if env.borrow().data.contains_key(key) {
-            // debug!("{:?} found in env {:?}", key, env);
-            Some(env.clone())
+    pub fn set_id(&mut self, id: SymbolId, value: &MalData) -> () {
+        let value = Some(value.clone());
+
+        if self.is_root() {
+            let index = id.index();
+
+            if index >= self.globals.len() {
+                self.globals.resize(index + 1, None);
+            }
+
+            self.globals[index] = value;
+        } else if let Some(slot) = self.slots.iter().position( |&( name, _ )| name == id ) {
+            self.slots[slot].1 = value;
         } else {
-            let outer = &env.borrow().outer;
+            self.slots.push(( id, value ));
+        }
+    }
 
-            let value = match outer {
-                & Some(ref outer) => {
-                    Env::find(&outer, key)
-                }
-                & None => None,
-            };
+    // belegt den von der analyse vergebenen slot einer lokalen umgebung
+    pub fn set_slot(&mut self, slot: usize, id: SymbolId, value: &MalData) -> () {
+        if slot >= self.slots.len() {
+            self.slots.resize(slot + 1, ( id, None ));
+        }
 
-            // debug!("{:?} found in outer ({:?}): {:?}", key, outer, value);
-            value
+        self.slots[slot] = ( id, Some(value.clone()) );
+    }
+
+    fn own(&self, id: SymbolId) -> Option<MalData> {
+        if self.is_root() {
+            self.interned(id).or_else( || self.ns.as_ref().and_then( |ns| ns.lookup(id) ) )
+        } else {
+            self.slots.iter().find( |&&( name, _ )| name == id ).and_then( |( _, value )| value.clone() )
         }
     }
 
-    pub fn get<'e>(env: &'e EnvType, key: &EnvKey) -> Option<Rc<MalData>> {
-        let val = match Env::find(env, key) {
-            Some(env) =>
-                Some(env.borrow().data.get(key).unwrap().clone()),
-            None =>
-                None
-        };
+    pub fn get(env: &EnvType, key: &str) -> Option<MalData> {
+        let val = Env::get_id(env, SymbolId::intern(key));
 
         trace!("Env::get, key: {} -> {:?}", key, val);
         val
     }
+
+    // sucht `id` von `env` aus nach aussen
+    pub fn get_id(env: &EnvType, id: SymbolId) -> Option<MalData> {
+        let env = env.borrow();
+
+        match env.own(id) {
+            Some(value) => Some(value),
+            None => env.outer.as_ref().and_then( |outer| Env::get_id(outer, id) )
+        }
+    }
+
+    // slot `slot` der umgebung `depth` ebenen ueber `env`; ist er (noch) nicht mit `id` belegt,
+    // wird wie zuvor nach dem namen gesucht
+    pub fn get_slot(env: &EnvType, depth: usize, slot: usize, id: SymbolId) -> Option<MalData> {
+        let env = env.borrow();
+
+        if depth > 0 {
+            return env.outer.as_ref().and_then( |outer| Env::get_slot(outer, depth - 1, slot, id) );
+        }
+
+        match env.slots.get(slot) {
+            Some(( name, Some(ref value) )) if *name == id =>
+                Some(value.clone()),
+
+            _ => match env.own(id) {
+                Some(value) => Some(value),
+                None => env.outer.as_ref().and_then( |outer| Env::get_id(outer, id) )
+            }
+        }
+    }
+
+    // die von dieser umgebung gehaltenen umgebungen und werte, fuer den zyklensammler (siehe gc)
+    pub fn envs(&self) -> Vec<&EnvType> {
+        self.outer.iter().chain(self.root.iter()).collect()
+    }
+
+    pub fn values(&self) -> Vec<&MalData> {
+        self.slots.iter().filter_map( |( _, value )| value.as_ref() )
+            .chain(self.globals.iter().filter_map( |value| value.as_ref() ))
+            .chain(self.bindings.values().flatten())
+            .collect()
+    }
+
+    // nimmt der umgebung alle bindungen und verweise; der alte inhalt wird zurueckgegeben, damit er
+    // erst nach dem loesen aller borrows freigegeben wird
+    pub fn clear(&mut self) -> Env {
+        mem::replace(self, Env { outer: None, root: None, slots: Vec::new(), globals: Vec::new(), bindings: HashMap::new(), dynamic: HashSet::new(), ns: None })
+    }
+
+    // die umgebung `depth` ebenen ueber `env`
+    pub fn ancestor(env: &EnvType, depth: usize) -> EnvType {
+        let mut env = env.clone();
+
+        for _ in 0..depth {
+            let outer = env.borrow().outer.clone().expect("Env::ancestor: no outer environment");
+            env = outer;
+        }
+
+        env
+    }
+
+    // eintrag der globalen tabelle
+    pub fn get_global(env: &EnvType, id: SymbolId) -> Option<MalData> {
+        let env = env.borrow();
+
+        match env.root {
+            Some(ref root) => root.borrow().own(id),
+            None => env.own(id)
+        }
+    }
+
+    // nur die in der globalen tabelle selbst definierten namen, ohne die des namensraums; eine
+    // bindung verdeckt den eintrag
+    pub fn interned(&self, id: SymbolId) -> Option<MalData> {
+        if !self.bindings.is_empty() {
+            if let Some(value) = self.bindings.get(&id).and_then( |values| values.last() ) {
+                return Some(value.clone());
+            }
+        }
+
+        self.globals.get(id.index()).cloned().unwrap_or(None)
+    }
+
+    pub fn get_interned(env: &EnvType, id: SymbolId) -> Option<MalData> {
+        Env::root_of(env).borrow().interned(id)
+    }
+
+    // die aeusserste umgebung, in der der globale name `id` definiert ist, und sein name dort;
+    // ein unbekannter name gehoert zur eigenen
+    pub fn resolve_global(env: &EnvType, id: SymbolId) -> ( EnvType, SymbolId ) {
+        let root = Env::root_of(env);
+
+        let home = {
+            let root = root.borrow();
+
+            match root.ns {
+                Some(ref ns) if root.interned(id).is_none() => ns.resolve(id),
+                _ => None
+            }
+        };
+
+        home.unwrap_or(( root, id ))
+    }
+
+    pub fn namespace_of(env: &EnvType) -> Option<Rc<Namespace>> {
+        Env::root_of(env).borrow().ns.clone()
+    }
+
+    // bindet den globalen namen `id` bis zum passenden pop_binding an `value`
+    pub fn push_binding(env: &EnvType, id: SymbolId, value: MalData) {
+        Env::root_of(env).borrow_mut().bindings.entry(id).or_default().push(value);
+    }
+
+    pub fn pop_binding(env: &EnvType, id: SymbolId) {
+        let root = Env::root_of(env);
+        let mut root = root.borrow_mut();
+
+        if let Some(values) = root.bindings.get_mut(&id) {
+            values.pop();
+
+            if values.is_empty() {
+                root.bindings.remove(&id);
+            }
+        }
+    }
+
+    pub fn set_dynamic(env: &EnvType, id: SymbolId) {
+        Env::root_of(env).borrow_mut().dynamic.insert(id);
+    }
+
+    // auch fuer einen aus einem anderen namensraum referierten namen
+    pub fn is_dynamic(env: &EnvType, id: SymbolId) -> bool {
+        let ( home, id ) = Env::resolve_global(env, id);
+        let dynamic = home.borrow().dynamic.contains(&id);
+
+        dynamic
+    }
+
+    fn root_of(env: &EnvType) -> EnvType {
+        env.borrow().root.clone().unwrap_or_else( || env.clone() )
+    }
+}
+
+// parameterliste der gestalt [a b & rest]: die namen und ob der letzte die uebrigen argumente erhaelt
+pub fn params(binds: &[Symbol]) -> Result<( Vec<SymbolId>, bool ), CoreError> {
+    let mut params = Vec::with_capacity(binds.len());
+    let mut bi = binds.iter();
+
+    while let Some(bind) = bi.next() {
+        if bind == "&" {
+            let rest_bind = bi.next().ok_or_else( || CoreError::Binding { message: "symbol expected after &".to_owned() } )?;
+
+            params.push(SymbolId::intern(rest_bind));
+            return Ok(( params, true ));
+        }
+
+        params.push(SymbolId::intern(bind));
+    }
+
+    Ok(( params, false ))
+}
+
+// index der arity fuer `argc` argumente unter den arities (feste parameter, variadisch); eine
+// genau passende feste arity geht der variadischen vor
+pub fn select_arity<I: Iterator<Item = ( usize, bool )> + Clone>(arities: I, argc: usize) -> Option<usize> {
+    arities.clone().position( |( required, variadic )| !variadic && required == argc )
+        .or_else( || arities.clone().position( |( required, variadic )| variadic && argc >= required ) )
 }
 
 pub fn wrapped_env_type(env: Env) -> EnvType {
     Rc::from(RefCell::from(env))
 }
-
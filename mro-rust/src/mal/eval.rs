@@ -1,10 +1,99 @@
 use std::fmt;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
 
-use common::MalData;
+use log::LogLevel::Trace;
+
+use printer;
+use printer::pr_str;
+use limits::{self, Limit};
+use interrupt;
+use analyze::{analyze, clause_named, macros_changed, Node, Var, Lambda, Loop, Try};
+use vm;
+use namespace;
+use env::{EnvType, Env, wrapped_env_type};
+
+use common::{MalData, MapKey, NativeFunction, FnClosure, FnCode, CallableFun, FunContext};
+use common::{make_mal_keyword, make_mal_ex_info, make_mal_map_from_kv_list, make_mal_list_from_vec, make_mal_string};
+use common::{make_mal_symbol, mal_symbol_name, make_mal_list_from_slice, get_wrapped_list, make_mal_vector_from_vec};
 
 #[derive(Debug, Clone)]
 pub enum EvalError {
-    General(String)
+    General(String),
+
+    // fehler einer core-funktion; kommt in catch* als ex-info mit :type an
+    Core(CoreError),
+
+    // mittels throw geworfener wert
+    Exception(MalData),
+
+    // abbruch von aussen (interrupt::interrupt), wird nicht von try* gefangen
+    Interrupted,
+
+    // fehler mit dem aufrufstapel zum zeitpunkt seines auftretens
+    Traced(Box<EvalError>, CallStack)
+}
+
+impl EvalError {
+    // haengt den aufrufstapel an, sofern noch keiner vorhanden ist
+    pub fn with_stack(self, stack: CallStack) -> EvalError {
+        match self {
+            EvalError::Traced(_, _) =>
+                self,
+
+            _ =>
+                EvalError::Traced(Box::from(self), stack)
+        }
+    }
+
+    pub fn stack(&self) -> Option<&CallStack> {
+        if let EvalError::Traced(_, ref stack) = *self { Some(stack) } else { None }
+    }
+
+    // ob catch* den fehler behandeln darf; verbrauchte schritte und zeit kommen nicht zurueck,
+    // ein handler koennte die auswertung also nur fortsetzen, indem er die grenze missachtet
+    pub fn is_catchable(&self) -> bool {
+        match *self {
+            EvalError::Interrupted => false,
+            EvalError::Core(CoreError::LimitExceeded { limit: Limit::Steps(_) }) => false,
+            EvalError::Core(CoreError::LimitExceeded { limit: Limit::Time(_) }) => false,
+            EvalError::Traced(ref err, _) => err.is_catchable(),
+            _ => true
+        }
+    }
+
+    // wert, der in catch* an das exception-symbol gebunden wird
+    pub fn to_mal_value(&self) -> MalData {
+        match *self {
+            EvalError::General(ref err_msg) =>
+                MalData::String(Rc::from(err_msg.as_str())),
+
+            EvalError::Core(ref err) =>
+                make_mal_ex_info(&err.to_string(), err.to_mal_data(), None),
+
+            EvalError::Exception(ref value) =>
+                value.clone(),
+
+            EvalError::Interrupted =>
+                make_mal_string("interrupted"),
+
+            EvalError::Traced(ref err, ref stack) => {
+                let value = err.to_mal_value();
+
+                // ex-info merkt sich den stapel des ersten throw
+                if let MalData::ExInfo(ref ex_info) = value {
+                    let mut ex_stack = ex_info.stack.borrow_mut();
+
+                    if ex_stack.is_none() {
+                        *ex_stack = Some(stack.clone());
+                    }
+                }
+
+                value
+            }
+        }
+    }
 }
 
 impl From<&'static str> for EvalError {
@@ -19,15 +108,777 @@ impl From<String> for EvalError {
     }
 }
 
+impl From<CoreError> for EvalError {
+    fn from(err: CoreError) -> Self {
+        EvalError::Core(err)
+    }
+}
+
 impl<'e> fmt::Display for EvalError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             EvalError::General(ref err_msg) => {
                 write!(f, "{}", err_msg)
             }
+
+            EvalError::Core(ref err) => {
+                write!(f, "{}", err)
+            }
+
+            EvalError::Exception(MalData::ExInfo(ref ex_info)) => {
+                write!(f, "{}", ex_info.message)
+            }
+
+            EvalError::Exception(ref value) => {
+                write!(f, "{}", pr_str(value, true))
+            }
+
+            EvalError::Interrupted => {
+                write!(f, "interrupted")
+            }
+
+            EvalError::Traced(ref err, _) => {
+                write!(f, "{}", err)
+            }
         }
     }
 }
 
 pub type MalEvalResult = Result<MalData, EvalError>;
 
+
+// fehler der core-funktionen und der parameterbindung; `fun` ist der name der betroffenen funktion,
+// `arg` die position des argumentes (ab 1)
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoreError {
+    Arity { fun: String, expected: String, got: usize },
+    Type { fun: String, arg: usize, expected: String, got: &'static str },
+    IndexOutOfBounds { fun: String, index: i64, size: usize },
+    Arithmetic { fun: String, message: String },
+    Io { fun: String, message: String },
+    Reader { fun: String, message: String },
+    Binding { message: String },
+    IllegalArgument { fun: String, message: String },
+
+    // fehler, den eine mit native_fn registrierte funktion zurueckgegeben hat
+    Native { fun: String, message: String },
+
+    // aufruf, den die capabilities des interpreters nicht erlauben
+    CapabilityDenied { fun: String, message: String },
+
+    // ueberschreitung einer der grenzen des interpreters
+    LimitExceeded { limit: Limit }
+}
+
+impl CoreError {
+    // arity-fehler fuer min..=max argumente, max None fuer beliebig viele
+    pub fn arity(fun: &str, min: usize, max: Option<usize>, got: usize) -> CoreError {
+        let expected = match max {
+            Some(max) if max == min => min.to_string(),
+            Some(max) if max == min + 1 => format!("{} or {}", min, max),
+            Some(max) => format!("{} to {}", min, max),
+            None => format!("at least {}", min)
+        };
+
+        CoreError::Arity { fun: fun.to_owned(), expected, got }
+    }
+
+    // fuer eine funktion mit mehreren arities (feste parameter, variadisch), z.b. "1, 2 or at least 4"
+    pub fn arities<I: Iterator<Item = ( usize, bool )>>(fun: &str, arities: I, got: usize) -> CoreError {
+        let mut arities: Vec<( usize, bool )> = arities.collect();
+        arities.sort();
+
+        let mut counts: Vec<String> = arities.iter()
+            .map( |&( required, variadic )| if variadic { format!("at least {}", required) } else { required.to_string() } )
+            .collect();
+
+        let expected = match counts.pop() {
+            Some(last) if counts.is_empty() => last,
+            Some(last) => format!("{} or {}", counts.join(", "), last),
+            None => "0".to_owned()
+        };
+
+        CoreError::Arity { fun: fun.to_owned(), expected, got }
+    }
+
+    pub fn type_error(fun: &str, arg: usize, expected: &str, got: &MalData) -> CoreError {
+        CoreError::Type { fun: fun.to_owned(), arg, expected: expected.to_owned(), got: got.type_name() }
+    }
+
+    pub fn io(fun: &str, message: &str) -> CoreError {
+        CoreError::Io { fun: fun.to_owned(), message: message.to_owned() }
+    }
+
+    pub fn illegal_argument(fun: &str, message: &str) -> CoreError {
+        CoreError::IllegalArgument { fun: fun.to_owned(), message: message.to_owned() }
+    }
+
+    // name der funktion in einer fehlermeldung ersetzen, z.b. durch den namen einer closure
+    pub fn in_fun(self, name: &str) -> CoreError {
+        match self {
+            CoreError::Arity { expected, got, .. } =>
+                CoreError::Arity { fun: name.to_owned(), expected, got },
+
+            err =>
+                err
+        }
+    }
+
+    // wert fuer :type in ex-data
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            CoreError::Arity { .. } => "arity",
+            CoreError::Type { .. } => "type",
+            CoreError::IndexOutOfBounds { .. } => "index-out-of-bounds",
+            CoreError::Arithmetic { .. } => "arithmetic",
+            CoreError::Io { .. } => "io",
+            CoreError::Reader { .. } => "reader",
+            CoreError::Binding { .. } => "binding",
+            CoreError::IllegalArgument { .. } => "illegal-argument",
+            CoreError::Native { .. } => "native",
+            CoreError::CapabilityDenied { .. } => "capability-denied",
+            CoreError::LimitExceeded { .. } => "limit-exceeded"
+        }
+    }
+
+    pub fn to_mal_data(&self) -> MalData {
+        let mut kvs = vec![make_mal_keyword("type"), make_mal_keyword(self.kind())];
+
+        match *self {
+            CoreError::Arity { ref fun, ref expected, got } =>
+                kvs.extend(vec![make_mal_keyword("fn"), make_mal_string(fun),
+                                make_mal_keyword("expected"), make_mal_string(expected),
+                                make_mal_keyword("got"), MalData::Number(got as i64)]),
+
+            CoreError::Type { ref fun, arg, ref expected, got } =>
+                kvs.extend(vec![make_mal_keyword("fn"), make_mal_string(fun),
+                                make_mal_keyword("arg"), MalData::Number(arg as i64),
+                                make_mal_keyword("expected"), make_mal_string(expected),
+                                make_mal_keyword("got"), make_mal_keyword(got)]),
+
+            CoreError::IndexOutOfBounds { ref fun, index, size } =>
+                kvs.extend(vec![make_mal_keyword("fn"), make_mal_string(fun),
+                                make_mal_keyword("index"), MalData::Number(index),
+                                make_mal_keyword("size"), MalData::Number(size as i64)]),
+
+            CoreError::Arithmetic { ref fun, .. } | CoreError::Io { ref fun, .. } |
+            CoreError::Reader { ref fun, .. } | CoreError::IllegalArgument { ref fun, .. } |
+            CoreError::Native { ref fun, .. } | CoreError::CapabilityDenied { ref fun, .. } =>
+                kvs.extend(vec![make_mal_keyword("fn"), make_mal_string(fun)]),
+
+            CoreError::LimitExceeded { ref limit } =>
+                kvs.extend(vec![make_mal_keyword("limit"), make_mal_keyword(limit.name()),
+                                make_mal_keyword("max"), MalData::Number(limit.max() as i64)]),
+
+            CoreError::Binding { .. } =>
+                ()
+        }
+
+        make_mal_map_from_kv_list(&mut kvs.iter()).unwrap()
+    }
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CoreError::Arity { ref fun, ref expected, got } =>
+                write!(f, "{}: wrong number of arguments, expected {}, got {}", fun, expected, got),
+
+            CoreError::Type { ref fun, arg, ref expected, got } =>
+                write!(f, "{}: argument {} must be {}, got {}", fun, arg, expected, got),
+
+            CoreError::IndexOutOfBounds { ref fun, index, size } =>
+                write!(f, "{}: index {} out of range for sequence of size {}", fun, index, size),
+
+            CoreError::Arithmetic { ref fun, ref message } | CoreError::Io { ref fun, ref message } |
+            CoreError::Reader { ref fun, ref message } | CoreError::IllegalArgument { ref fun, ref message } |
+            CoreError::Native { ref fun, ref message } =>
+                write!(f, "{}: {}", fun, message),
+
+            CoreError::Binding { ref message } =>
+                write!(f, "invalid binding: {}", message),
+
+            CoreError::CapabilityDenied { ref fun, ref message } =>
+                write!(f, "{}: capability denied: {}", fun, message),
+
+            CoreError::LimitExceeded { ref limit } =>
+                write!(f, "evaluation limit exceeded: {}", limit)
+        }
+    }
+}
+
+
+// aufrufstapel des evaluators: ein eintrag je aktivem funktionsaufruf, bei TCO wird der
+// oberste eintrag ersetzt statt ein neuer angelegt
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+    pub name: Option<String>,
+    pub form: MalData,
+    pub tail_calls: usize
+}
+
+pub type CallStack = Rc<Vec<StackFrame>>;
+
+const MAX_FORM_LEN: usize = 60;
+
+impl StackFrame {
+    pub fn new(name: Option<&str>, form: &MalData) -> StackFrame {
+        StackFrame { name: name.map( |n| n.to_owned() ), form: form.clone(), tail_calls: 0 }
+    }
+
+    pub fn to_mal_value(&self) -> MalData {
+        let kvs = [make_mal_keyword("fn"), self.name.as_ref().map_or(MalData::Nil, |n| make_mal_string(n)),
+                   make_mal_keyword("form"), self.form.clone(),
+                   make_mal_keyword("tail-calls"), MalData::Number(self.tail_calls as i64)];
+
+        make_mal_map_from_kv_list(&mut kvs.iter()).unwrap()
+    }
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut form = pr_str(&self.form, true);
+
+        if form.chars().count() > MAX_FORM_LEN {
+            form = form.chars().take(MAX_FORM_LEN).collect::<String>() + " ...";
+        }
+
+        write!(f, "at {}: {}", self.name.as_ref().map_or("<anonymous>", |n| n.as_str()), form)?;
+
+        if self.tail_calls > 0 {
+            write!(f, " (+{} tail calls)", self.tail_calls)?;
+        }
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CALL_STACK: RefCell<Vec<StackFrame>> = const { RefCell::new(Vec::new()) };
+    static CAUGHT_STACK: RefCell<Option<CallStack>> = const { RefCell::new(None) };
+}
+
+pub fn call_stack_depth() -> usize {
+    CALL_STACK.with( |stack| stack.borrow().len() )
+}
+
+// legt einen eintrag an, sofern der evaluator auf ebene `depth` noch keinen hat, ansonsten
+// handelt es sich um einen endrekursiven aufruf, der den vorhandenen eintrag ersetzt
+pub fn enter_call_frame(depth: usize, frame: StackFrame) {
+    CALL_STACK.with( |stack| {
+        let mut stack = stack.borrow_mut();
+
+        if stack.len() > depth {
+            let tail_calls = stack[depth].tail_calls + 1;
+
+            stack.truncate(depth);
+            stack.push(StackFrame { tail_calls, ..frame });
+        } else {
+            stack.push(frame);
+        }
+    })
+}
+
+// eintrag fuer einen aufruf, der nicht endrekursiv fortgesetzt wird (native funktionen)
+pub fn push_call_frame(frame: StackFrame) {
+    CALL_STACK.with( |stack| stack.borrow_mut().push(frame) )
+}
+
+pub fn leave_call_frames(depth: usize) {
+    CALL_STACK.with( |stack| stack.borrow_mut().truncate(depth) )
+}
+
+pub fn capture_call_stack() -> CallStack {
+    CALL_STACK.with( |stack| Rc::from(stack.borrow().iter().rev().cloned().collect::<Vec<StackFrame>>()) )
+}
+
+// stapel der zuletzt in catch* gefangenen exception, fuer ex-stack
+pub fn set_caught_call_stack(stack: Option<CallStack>) {
+    CAUGHT_STACK.with( |caught| *caught.borrow_mut() = stack )
+}
+
+pub fn caught_call_stack() -> Option<CallStack> {
+    CAUGHT_STACK.with( |caught| caught.borrow().clone() )
+}
+
+pub fn call_stack_as_mal_list(stack: &CallStack) -> MalData {
+    make_mal_list_from_vec(stack.iter().map( |frame| frame.to_mal_value() ).collect())
+}
+
+
+// evaluator
+//
+// eine form wird zuerst analysiert (siehe analyze) und der dabei entstandene Node-baum dann
+// ausgewertet; endpositionen werden in der schleife von exec ohne rekursion fortgesetzt (TCO)
+
+pub fn call_function(env: EnvType, f: &NativeFunction, args: &[MalData]) -> Result<MalData, EvalError> {
+    debug!("call_function, f: {:?}, args: {:?}", f, args);
+
+    let callable = f.callable.clone();
+    let ctx = &FunContext { eval: Some(make_eval_closure(env)), eval2: Box::from(eval), env: None };
+    let result = callable(ctx, args);
+
+    if log_enabled!(Trace) {
+        for arg in args { trace!("arg: {:?}", arg) };
+    }
+
+    debug!("call_function, result: {:?}", result);
+    result
+}
+
+fn is_pair(ast: &MalData) -> bool {
+    match ast {
+        &MalData::List(ref list, _) | &MalData::Vector(ref list, _) =>
+            !list.is_empty(),
+
+        _ =>
+            false
+    }
+}
+
+fn is_symbol_named(ast: &MalData, name: &str) -> bool {
+    if let &MalData::Symbol(ref sym) = ast {
+        &**sym == name
+    } else {
+        false
+    }
+}
+
+pub fn quasiquote(ast: &MalData) -> Result<MalData, EvalError> {
+    debug!("> quasiquote, ast: {:?}", ast);
+
+    if !is_pair(ast) {
+        let res = make_mal_list_from_vec(vec![make_mal_symbol("quote"), ast.clone()]);
+        debug!("< quasiquote, !pair; res: {:?}", res);
+        return Ok(res);
+    } else if let Some(list) = get_wrapped_list(ast) {
+        let first = list.first().unwrap();
+        let first_as_list = if is_pair(first) { get_wrapped_list(first) } else { None };
+
+        if mal_symbol_name(first).map_or(false, |sym| sym == "unquote") {
+            let res = list.get(1).ok_or("expected form to unquote")?.clone();
+            debug!("< unquote, res: {:?}", res);
+
+            return Ok(res)
+        } else if is_pair(first) && first_as_list.map_or(false, |l| l.first().map_or(false, |f| is_symbol_named(f, "splice-unquote"))) {
+            let to_unquote = first_as_list.map( |l| l.get(1) ).ok_or("expected form to unquote")?.unwrap();
+            debug!("splice-unquote, to_unquote: {:?}", to_unquote);
+
+            let quasiquote_rest = if list.len() > 1 {
+                quasiquote(&make_mal_list_from_slice(&list[1..]))?
+            } else {
+                MalData::Nil
+            };
+
+            debug!("splice-unquote, quasiquote_rest: {:?}", quasiquote_rest);
+
+            let res = if list.len() > 1 {
+                make_mal_list_from_vec(vec![make_mal_symbol("concat"), to_unquote.clone(), quasiquote_rest])
+            } else {
+                make_mal_list_from_vec(vec![make_mal_symbol("concat"), to_unquote.clone()])
+            };
+
+            debug!("< splice-unquote, res: {:?}", res);
+
+            return Ok(res)
+        } else {
+            let quasiquote_first = quasiquote(&first)?;
+            debug!("quasiquote, first: {:?}", quasiquote_first);
+
+            let quasiquote_rest = quasiquote(&make_mal_list_from_slice(&list[1..]))?;
+            debug!("quasiquote, rest: {:?}", quasiquote_rest);
+
+            let res = make_mal_list_from_vec(vec![make_mal_symbol("cons"), quasiquote_first, quasiquote_rest]);
+            debug!("< quasiquote, res: {:?}", res);
+
+            return Ok(res);
+        }
+    } else {
+        panic!("quasiquote, ast: {:?}", ast);
+    }
+}
+
+fn mal_list_head(ast: &MalData) -> Option<&MalData> {
+    if let &MalData::List(ref list, _) = ast {
+        list.first()
+    } else {
+        None
+    }
+}
+
+// makro, das der kopf der liste `ast` in `env` bezeichnet
+fn macro_for(env: &EnvType, ast: &MalData) -> Option<MalData> {
+    let res = if let Some(MalData::Symbol(sym)) = mal_list_head(ast) {
+        Env::get(env, sym).filter( |symval| {
+            if let MalData::FnClosure(ref fnc) = *symval { fnc.is_macro() } else { false }
+        })
+    } else {
+        None
+    };
+
+    debug!("macro_for, ast: {:?}, res: {:?}", ast, res);
+
+    res
+}
+
+// umgebung fuer einen aufruf der closure mit den parametern der passenden arity an die argumente
+// gebunden, und der rumpf dieser arity
+fn closure_env(closure: &FnClosure, lambda: &Lambda, args: &[MalData]) -> Result<( EnvType, Rc<Node> ), EvalError> {
+    let in_closure = |err: CoreError| match closure.name { Some(ref name) => err.in_fun(name), None => err };
+
+    let arity = lambda.arity(args.len()).map_err(in_closure)?;
+    let fn_env = Env::with_args(Some(closure.outer_env.clone()), &arity.params, arity.variadic, args).map_err(in_closure)?;
+
+    Ok(( wrapped_env_type(fn_env), arity.body.clone() ))
+}
+
+// wendet eine native funktion oder closure auf bereits ausgewertete argumente an
+pub fn apply(env: EnvType, fun: &MalData, args: &[MalData]) -> MalEvalResult {
+    match *fun {
+        MalData::Function(ref f) =>
+            call_function(env, f, args),
+
+        MalData::FnClosure(ref fnc) => match fnc.code {
+            FnCode::Tree(ref lambda) => {
+                let ( fn_env, body ) = closure_env(fnc, lambda, args)?;
+                eval_node(fn_env, &body)
+            }
+            FnCode::Bytecode(..) => vm::call(fnc, args)
+        },
+
+        _ =>
+            Err(EvalError::General(format!("{} is not a function", printer::pr_str(fun, true))))
+    }
+}
+
+pub fn macroexpand(env: EnvType, ast: &MalData) -> Result<MalData, EvalError> {
+    debug!("macroexpand, ast: {:?}", ast);
+
+    let mut expand_ast = ast.clone();
+
+    while let Some(mac) = macro_for(&env, &expand_ast) {
+        let macro_args = get_wrapped_list(&expand_ast).map_or(vec![], |l| l[1..].to_vec());
+
+        debug!("macro call, mac: {:?}, args: {:?}", mac, macro_args);
+
+        expand_ast = apply(env.clone(), &mac, &macro_args)?;
+    }
+
+    Ok(expand_ast)
+}
+
+// ergebnis von try*: entweder ein wert oder der im rahmen der TCO auszuwertende catch*-rumpf
+enum TryOutcome {
+    Value(MalData),
+    Tail(EnvType, Rc<Node>)
+}
+
+fn eval_try(env: EnvType, try_node: &Try) -> Result<TryOutcome, EvalError> {
+    let body_res = eval_node(env.clone(), &try_node.body);
+
+    let res = match ( body_res, try_node.catch.as_ref() ) {
+        ( Err(err), Some(( catch_bind, handler )) ) if err.is_catchable() => {
+            let exc = err.to_mal_value();
+            set_caught_call_stack(err.stack().cloned());
+
+            let catch_env = wrapped_env_type(Env::local(env.clone()));
+            assign(&catch_env, catch_bind, &exc);
+
+            // ohne finally* steht der handler in endposition
+            if try_node.finally.is_none() {
+                return Ok(TryOutcome::Tail(catch_env, handler.clone()));
+            }
+
+            eval_node(catch_env, handler)
+        }
+
+        ( body_res, _ ) =>
+            body_res
+    };
+
+    // finally* laeuft in jedem fall; ein fehler darin ersetzt das bisherige ergebnis
+    if let Some(ref cleanup) = try_node.finally {
+        eval_node(env, cleanup)?;
+    }
+
+    res.map(TryOutcome::Value)
+}
+
+pub fn eval(env: EnvType, ast: & MalData) -> Result<MalData, EvalError> {
+    // ein do auf oberster ebene wird form fuer form analysiert, damit ein darin definiertes makro
+    // schon in den folgenden formen expandiert wird (load-file); ebenso gilt ein ns ab der
+    // folgenden form
+    if let Some(forms) = clause_named(ast, "do") {
+        let mut res = MalData::Nil;
+
+        for form in forms {
+            res = eval(namespace::current(&env), form)?;
+        }
+
+        return Ok(res);
+    }
+
+    let node = analyze(&env, ast).map_err( |err| err.with_stack(capture_call_stack()) )?;
+
+    eval_node(env, &Rc::new(node))
+}
+
+// wertet einen analysierten knoten aus
+pub fn eval_node(env: EnvType, node: &Rc<Node>) -> MalEvalResult {
+    // konstanten und symbole brauchen weder stapel noch grenzen
+    match **node {
+        Node::Const(ref value) => return Ok(value.clone()),
+        Node::Symbol(ref var) => return lookup(&env, var),
+        _ => ()
+    }
+
+    let depth = call_stack_depth();
+
+    let res = limits::enter().map_err(EvalError::from).and_then( |_| {
+        let res = exec(env, node.clone(), depth);
+        limits::leave();
+        res
+    });
+
+    // der stapel wird beim ersten verlassen eines fehlerhaften eval_node festgehalten, also dort,
+    // wo der fehler aufgetreten ist
+    let res = res.map_err( |err| err.with_stack(capture_call_stack()) );
+    leave_call_frames(depth);
+
+    res
+}
+
+fn lookup(env: &EnvType, var: &Var) -> MalEvalResult {
+    var.get(env)
+        .ok_or_else( || EvalError::General(format!("'{}' not found", var.id())) )
+}
+
+// bindet `var` in `env`, der umgebung, in der die bindende form ausgewertet wird
+fn assign(env: &EnvType, var: &Var, value: &MalData) {
+    match *var {
+        Var::Local(_, slot, id) => env.borrow_mut().set_slot(slot, id, value),
+        Var::Global(id) | Var::Dynamic(id) => env.borrow_mut().set_id(id, value)
+    }
+}
+
+fn eval_nodes(env: &EnvType, nodes: &[Rc<Node>]) -> Result<Vec<MalData>, EvalError> {
+    nodes.iter().map( |node| eval_node(env.clone(), node) ).collect()
+}
+
+pub fn define(env: &EnvType, var: &Var, value: MalData) -> MalData {
+    let value = named(&var.id().name(), value);
+
+    // auch ein ueberschriebenes makro macht seine expansionen ungueltig
+    if let Var::Global(id) | Var::Dynamic(id) = *var {
+        if is_macro(&value) || Env::get_id(env, id).is_some_and( |old| is_macro(&old) ) {
+            macros_changed();
+        }
+    }
+
+    assign(env, var, &value);
+    value
+}
+
+fn is_macro(value: &MalData) -> bool {
+    if let MalData::FnClosure(ref fnc) = *value { fnc.is_macro() } else { false }
+}
+
+// eine closure erhaelt den namen ihrer bindung fuer den aufrufstapel
+pub fn named(name: &str, value: MalData) -> MalData {
+    match value {
+        MalData::FnClosure(ref fnc) if fnc.name.is_none() =>
+            MalData::FnClosure(fnc.with_name(name)),
+
+        _ =>
+            value
+    }
+}
+
+fn exec(mut env: EnvType, mut node: Rc<Node>, depth: usize) -> MalEvalResult {
+    // der zuletzt betretene loop*; ein recur steht immer in dessen endposition
+    let mut looping: Option<Rc<Loop>> = None;
+
+    loop {
+        interrupt::check()?;
+        limits::step()?;
+
+        let next = match *node {
+            Node::Const(ref value) =>
+                return Ok(value.clone()),
+
+            Node::Symbol(ref var) =>
+                return lookup(&env, var),
+
+            Node::Vector(ref nodes) =>
+                return Ok(make_mal_vector_from_vec(&eval_nodes(&env, nodes)?)),
+
+            Node::Map(ref entries) => {
+                let mut eval_map: HashMap<MapKey, MalData> = HashMap::with_capacity(entries.len());
+
+                for ( key, value ) in entries {
+                    eval_map.insert(key.clone(), eval_node(env.clone(), value)?);
+                }
+
+                return Ok(MalData::Map(Rc::new(eval_map), None));
+            }
+
+            Node::Def(ref var, ref value) => {
+                let value = eval_node(env.clone(), value)?;
+                return Ok(define(&env, var, value));
+            }
+
+            Node::DefMacro(ref var, ref value) => {
+                if let MalData::FnClosure(ref fnc) = eval_node(env.clone(), value)? {
+                    let res = MalData::FnClosure(fnc.with_name(&var.id().name()).to_macro());
+
+                    assign(&env, var, &res);
+                    macros_changed();
+                    return Ok(res);
+                } else {
+                    return Err(EvalError::General("closure expected".to_owned()));
+                }
+            }
+
+            Node::Let(ref bindings, ref body) => {
+                let let_env = wrapped_env_type(Env::local(env.clone()));
+
+                for ( var, value ) in bindings {
+                    let evaluated = eval_node(let_env.clone(), value)?;
+                    assign(&let_env, var, &evaluated);
+                }
+
+                // TCO: let-rumpf im folgenden schleifendurchgang evaluieren
+                env = let_env;
+                body.clone()
+            }
+
+            Node::Loop(ref lp) => {
+                let loop_env = wrapped_env_type(Env::local(env.clone()));
+
+                for ( var, value ) in &lp.bindings {
+                    let evaluated = eval_node(loop_env.clone(), value)?;
+                    assign(&loop_env, var, &evaluated);
+                }
+
+                looping = Some(lp.clone());
+                env = loop_env;
+                lp.body.clone()
+            }
+
+            // bindet die namen des loop* an ort und stelle neu, es sei denn, eine closure haelt sie
+            Node::Recur(ref recur) => {
+                let args = eval_nodes(&env, &recur.args)?;
+                let lp = looping.clone().ok_or("recur outside of loop*")?;
+
+                let loop_env = if lp.captured {
+                    wrapped_env_type(Env::local(Env::ancestor(&env, recur.depth + 1)))
+                } else {
+                    Env::ancestor(&env, recur.depth)
+                };
+
+                for ( &( slot, id ), arg ) in recur.slots.iter().zip(&args) {
+                    loop_env.borrow_mut().set_slot(slot, id, arg);
+                }
+
+                env = loop_env;
+                lp.body.clone()
+            }
+
+            Node::Do(ref forms, ref last) => {
+                for form in forms {
+                    eval_node(env.clone(), form)?;
+                }
+
+                last.clone()
+            }
+
+            Node::If(ref cond, ref then_node, ref else_node) => {
+                match eval_node(env.clone(), cond)? {
+                    MalData::Nil | MalData::False => match *else_node {
+                        Some(ref else_node) => else_node.clone(),
+                        None => return Ok(MalData::Nil)
+                    },
+
+                    _ =>
+                        then_node.clone()
+                }
+            }
+
+            Node::Fn(ref lambda) =>
+                return Ok(MalData::FnClosure(FnClosure::new(env.clone(), lambda.clone()))),
+
+            Node::Macroexpand(ref form) =>
+                return macroexpand(env.clone(), form),
+
+            Node::Try(ref try_node) => {
+                match eval_try(env.clone(), try_node)? {
+                    TryOutcome::Value(res) =>
+                        return Ok(res),
+
+                    TryOutcome::Tail(catch_env, handler) => {
+                        env = catch_env;
+                        handler
+                    }
+                }
+            }
+
+            Node::Expansion(ref expansion) =>
+                if expansion.is_current(&env) { expansion.node.clone() } else { expansion.reanalyzed(&env)? },
+
+            Node::Raise(ref err) =>
+                return Err(err.clone()),
+
+            Node::Call(ref call) => {
+                let fun = eval_node(env.clone(), &call.fun)?;
+
+                match fun {
+                    // erst nach der analyse definiertes makro: jetzt expandieren
+                    MalData::FnClosure(ref fnc) if fnc.is_macro() =>
+                        call.late.node(&env, &fun, &call.form)?,
+
+                    MalData::Function(ref f) => {
+                        let args = eval_nodes(&env, &call.args)?;
+
+                        if !f.is_internal() {
+                            push_call_frame(StackFrame::new(Some(f.name()), &call.form));
+                        }
+
+                        return call_function(env, f, &args);
+                    }
+
+                    MalData::FnClosure(ref fnc) => {
+                        let args = eval_nodes(&env, &call.args)?;
+
+                        enter_call_frame(depth, StackFrame::new(fnc.name.as_deref(), &call.form));
+
+                        match fnc.code {
+                            FnCode::Tree(ref lambda) => {
+                                let ( fn_env, body ) = closure_env(fnc, lambda, &args)?;
+
+                                env = fn_env;
+                                body
+                            }
+
+                            // von der vm uebersetzte closure, z.b. nach einem wechsel des backends
+                            FnCode::Bytecode(..) =>
+                                return vm::call(fnc, &args)
+                        }
+                    }
+
+                    el => {
+                        let err_msg = format!("first element is not a function ({:?})", el);
+                        return Err(EvalError::General(err_msg));
+                    }
+                }
+            }
+        };
+
+        node = next;
+    }
+}
+
+fn make_eval_closure(env_rc: EnvType) -> Rc<CallableFun> {
+    let eval_closure: Rc<CallableFun> = Rc::from(move |fun_ctx: &FunContext, args: &[MalData]| { eval(env_rc.clone(), &args[0]) });
+
+    eval_closure
+}
@@ -0,0 +1,386 @@
+// zyklensammler fuer umgebungen, closures und atome
+//
+// eine closure haelt ihre umgebung, und def! legt die closure wieder in dieser ab; solche Rc-zyklen
+// gibt das zaehlen der referenzen nie frei. jeder zyklus fuehrt ueber eine umgebung, die eine
+// closure haelt, oder ueber ein atom (auch die zellen der vm sind atome), daher werden genau
+// diese als kandidaten vorgemerkt.
+//
+// gesammelt wird durch probeweises loeschen: von den kandidaten aus wird der graph der Rc-knoten
+// durchlaufen und fuer jeden knoten gezaehlt, wie viele der verweise auf ihn aus dem graphen selbst
+// stammen. hat ein knoten mehr starke referenzen, haelt ihn jemand ausserhalb (der rust-stack, der
+// interpreter, ein analysierter Node-baum) und er lebt, ebenso alles von ihm erreichbare. die
+// uebrigen kandidaten sind nur noch untereinander erreichbar; ihre bindungen werden geloescht,
+// womit die zyklen brechen.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use common::{MalData, FnCode, Opaque};
+use env::{Env, EnvType};
+
+// neue kandidaten bis zur ersten automatischen sammlung; danach das doppelte der ueberlebenden
+const MIN_THRESHOLD: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stats {
+    pub collections: u64,
+
+    // insgesamt freigegebene umgebungen und atome
+    pub freed: u64,
+
+    // knoten, die die letzte sammlung durchlaufen hat
+    pub scanned: usize,
+
+    // vorgemerkte kandidaten, darunter moeglicherweise bereits freigegebene
+    pub tracked: usize
+}
+
+struct Heap {
+    envs: Vec<Weak<RefCell<Env>>>,
+    atoms: Vec<Weak<RefCell<MalData>>>,
+    threshold: usize,
+    stats: Stats
+}
+
+impl Heap {
+    fn due(&self) -> bool {
+        self.envs.len() + self.atoms.len() >= self.threshold
+    }
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap { envs: Vec::new(), atoms: Vec::new(), threshold: MIN_THRESHOLD, stats: Stats::default() });
+}
+
+// merkt die umgebung einer neuen closure vor
+pub fn track_env(env: &EnvType) {
+    let due = HEAP.with( |heap| {
+        let mut heap = heap.borrow_mut();
+
+        // closures entstehen oft nacheinander in derselben umgebung
+        if heap.envs.last().is_none_or( |last| last.as_ptr() != Rc::as_ptr(env) ) {
+            heap.envs.push(Rc::downgrade(env));
+        }
+
+        heap.due()
+    });
+
+    if due {
+        collect();
+    }
+}
+
+pub fn track_atom(atom: &Rc<RefCell<MalData>>) {
+    let due = HEAP.with( |heap| {
+        let mut heap = heap.borrow_mut();
+        heap.atoms.push(Rc::downgrade(atom));
+
+        heap.due()
+    });
+
+    if due {
+        collect();
+    }
+}
+
+pub fn stats() -> Stats {
+    HEAP.with( |heap| {
+        let heap = heap.borrow();
+        Stats { tracked: heap.envs.len() + heap.atoms.len(), ..heap.stats }
+    })
+}
+
+// eine sammlung; liefert die anzahl der freigegebenen umgebungen und atome
+pub fn collect() -> usize {
+    let ( envs, atoms ) = HEAP.with( |heap| {
+        let mut heap = heap.borrow_mut();
+        ( upgrade(mem::take(&mut heap.envs)), upgrade(mem::take(&mut heap.atoms)) )
+    });
+
+    let mut graph = Graph::default();
+
+    // jeder kandidat zaehlt als ein verweis aus dem graphen: er steht fuer die eben erzeugte
+    // starke referenz
+    let env_nodes: Vec<usize> = envs.iter().map( |env| graph.env(env) ).collect();
+    let atom_nodes: Vec<usize> = atoms.iter().map( |atom| graph.atom(atom) ).collect();
+
+    let live = graph.live();
+
+    let mut released = Vec::new();
+    let mut released_values = Vec::new();
+    let mut survivors = ( Vec::new(), Vec::new() );
+
+    for ( env, node ) in envs.iter().zip(env_nodes) {
+        if live[node] {
+            survivors.0.push(Rc::downgrade(env));
+        } else if let Ok(mut env) = env.try_borrow_mut() {
+            released.push(env.clear());
+        }
+    }
+
+    for ( atom, node ) in atoms.iter().zip(atom_nodes) {
+        if live[node] {
+            survivors.1.push(Rc::downgrade(atom));
+        } else if let Ok(mut atom) = atom.try_borrow_mut() {
+            released_values.push(mem::replace(&mut *atom, MalData::Nil));
+        }
+    }
+
+    let freed = released.len() + released_values.len();
+    let scanned = graph.nodes.len();
+
+    debug!("gc, scanned: {}, freed: {}", scanned, freed);
+
+    // erst jetzt, ohne offene borrows, freigeben
+    drop(released);
+    drop(released_values);
+    drop(envs);
+    drop(atoms);
+
+    HEAP.with( |heap| {
+        let mut heap = heap.borrow_mut();
+        let survived = survivors.0.len() + survivors.1.len();
+
+        // waehrend der freigabe vorgemerkte kandidaten bleiben erhalten
+        heap.envs.extend(survivors.0);
+        heap.atoms.extend(survivors.1);
+        heap.threshold = MIN_THRESHOLD.max(2 * survived);
+
+        heap.stats.collections += 1;
+        heap.stats.freed += freed as u64;
+        heap.stats.scanned = scanned;
+    });
+
+    freed
+}
+
+// die noch lebenden kandidaten, jeder nur einmal
+fn upgrade<T>(tracked: Vec<Weak<T>>) -> Vec<Rc<T>> {
+    let mut seen = HashMap::new();
+
+    tracked.iter()
+        .filter_map(Weak::upgrade)
+        .filter( |rc| seen.insert(Rc::as_ptr(rc) as *const () as usize, ()).is_none() )
+        .collect()
+}
+
+#[derive(Default)]
+struct Node {
+    strong: usize,
+
+    // verweise aus dem graphen
+    internal: usize,
+
+    // nicht durchlaufen, weil gerade geborgt; gilt als von aussen gehalten
+    pinned: bool,
+
+    children: Vec<usize>
+}
+
+#[derive(Default)]
+struct Graph {
+    index: HashMap<usize, usize>,
+    nodes: Vec<Node>
+}
+
+impl Graph {
+    // zaehlt einen verweis auf `rc`; liefert den knoten und ob er neu ist
+    fn enter<T: ?Sized>(&mut self, rc: &Rc<T>) -> ( usize, bool ) {
+        let ptr = Rc::as_ptr(rc) as *const () as usize;
+
+        let ( node, new ) = match self.index.get(&ptr) {
+            Some(&node) => ( node, false ),
+
+            None => {
+                let node = self.nodes.len();
+
+                self.index.insert(ptr, node);
+                self.nodes.push(Node { strong: Rc::strong_count(rc), ..Node::default() });
+
+                ( node, true )
+            }
+        };
+
+        self.nodes[node].internal += 1;
+        ( node, new )
+    }
+
+    fn env(&mut self, env: &EnvType) -> usize {
+        let ( node, new ) = self.enter(env);
+
+        if new {
+            match env.try_borrow() {
+                Ok(env) => {
+                    let mut children: Vec<usize> = env.envs().into_iter().map( |outer| self.env(outer) ).collect();
+
+                    for value in env.values() {
+                        self.value(value, &mut children);
+                    }
+
+                    self.nodes[node].children = children;
+                }
+
+                Err(_) =>
+                    self.nodes[node].pinned = true
+            }
+        }
+
+        node
+    }
+
+    fn atom(&mut self, atom: &Rc<RefCell<MalData>>) -> usize {
+        let ( node, new ) = self.enter(atom);
+
+        if new {
+            match atom.try_borrow() {
+                Ok(value) => {
+                    let mut children = Vec::new();
+                    self.value(&value, &mut children);
+
+                    self.nodes[node].children = children;
+                }
+
+                Err(_) =>
+                    self.nodes[node].pinned = true
+            }
+        }
+
+        node
+    }
+
+    fn seq(&mut self, seq: &Rc<Vec<MalData>>) -> usize {
+        let ( node, new ) = self.enter(seq);
+
+        if new {
+            let mut children = Vec::new();
+
+            for value in seq.iter() {
+                self.value(value, &mut children);
+            }
+
+            self.nodes[node].children = children;
+        }
+
+        node
+    }
+
+    fn meta(&mut self, meta: &Rc<MalData>) -> usize {
+        let ( node, new ) = self.enter(meta);
+
+        if new {
+            let mut children = Vec::new();
+            self.value(meta, &mut children);
+
+            self.nodes[node].children = children;
+        }
+
+        node
+    }
+
+    // ein opaker wert sieht nur die werte, die er mit Opaque::with_children nennt
+    fn opaque(&mut self, opaque: &Opaque) -> usize {
+        let ( node, new ) = self.enter(opaque.shared());
+
+        if new {
+            let mut children = Vec::new();
+            opaque.visit_children(&mut |value| self.value(value, &mut children));
+
+            self.nodes[node].children = children;
+        }
+
+        node
+    }
+
+    // die knoten, auf die `value` verweist; metadaten von funktionen, die von nativen funktionen
+    // eingefangenen werte und der nicht genannte inhalt opaker werte bleiben unsichtbar, was nur
+    // dazu fuehren kann, dass ein zyklus erhalten bleibt
+    fn value(&mut self, value: &MalData, children: &mut Vec<usize>) {
+        match *value {
+            MalData::List(ref seq, ref meta) | MalData::Vector(ref seq, ref meta) => {
+                children.push(self.seq(seq));
+
+                if let Some(ref meta) = *meta {
+                    children.push(self.meta(meta));
+                }
+            }
+
+            MalData::Map(ref map, ref meta) => {
+                let ( node, new ) = self.enter(map);
+
+                if new {
+                    let mut map_children = Vec::new();
+
+                    for value in map.values() {
+                        self.value(value, &mut map_children);
+                    }
+
+                    self.nodes[node].children = map_children;
+                }
+
+                children.push(node);
+
+                if let Some(ref meta) = *meta {
+                    children.push(self.meta(meta));
+                }
+            }
+
+            MalData::Atom(ref atom) =>
+                children.push(self.atom(atom)),
+
+            MalData::Opaque(ref opaque) =>
+                children.push(self.opaque(opaque)),
+
+            MalData::Function(ref function) =>
+                if let Some(state) = function.state_opaque() {
+                    children.push(self.opaque(state));
+                },
+
+            MalData::FnClosure(ref closure) => {
+                children.push(self.env(&closure.outer_env));
+
+                if let FnCode::Bytecode(_, ref upvalues) = closure.code {
+                    children.push(self.seq(upvalues));
+                }
+            }
+
+            MalData::ExInfo(ref ex) => {
+                let ( node, new ) = self.enter(ex);
+
+                if new {
+                    let mut ex_children = Vec::new();
+                    self.value(&ex.data, &mut ex_children);
+
+                    if let Some(ref cause) = ex.cause {
+                        self.value(cause, &mut ex_children);
+                    }
+
+                    self.nodes[node].children = ex_children;
+                }
+
+                children.push(node);
+            }
+
+            _ => ()
+        }
+    }
+
+    // von aussen gehaltene knoten und alles von ihnen erreichbare
+    fn live(&self) -> Vec<bool> {
+        let mut live = vec![false; self.nodes.len()];
+
+        let mut pending: Vec<usize> = (0..self.nodes.len())
+            .filter( |&node| self.nodes[node].pinned || self.nodes[node].strong > self.nodes[node].internal )
+            .collect();
+
+        while let Some(node) = pending.pop() {
+            if !live[node] {
+                live[node] = true;
+                pending.extend(self.nodes[node].children.iter().filter( |&&child| !live[child] ));
+            }
+        }
+
+        live
+    }
+}
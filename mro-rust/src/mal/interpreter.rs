@@ -0,0 +1,218 @@
+// einbettbarer interpreter: namensraum mal.core mit den core-funktionen, eval und dem prelude,
+// ausgewertet wird im aktuellen namensraum (anfangs user, siehe namespace); dateien laedt der
+// loader
+//
+//     let interpreter = Interpreter::new();
+//     interpreter.define_fn("repeat", repeat);
+//     let res = interpreter.eval_str("(repeat 3 \"a\")")?;
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use reader;
+use printer;
+use env::{EnvType, Env, SymbolId};
+use common::{MalData, NativeFunction, CallableFun, FunContext, make_mal_list_from_vec, make_mal_string};
+use convert::{IntoMal, IntoNativeFn, native_fn};
+use core::init_ns_map;
+use capabilities::Capabilities;
+use limits::Limits;
+use eval::{self, EvalError, CoreError, MalEvalResult};
+use vm;
+use namespace::{self, Namespaces};
+use loader::{self, Loader};
+
+// in mal selbst definierte funktionen und makros
+const PRELUDE: &[&str] = &[
+    "(def! not (fn* [a] (if a false true)))",
+    "(defmacro! cond (fn* (& xs) (if (> (count xs) 0) (list 'if (first xs) (if (> (count xs) 1) (nth xs 1) (throw \"odd number of forms to cond\")) (cons 'cond (rest (rest xs)))))))",
+    "(def! ^:dynamic *gensym-counter* (atom 0))",
+    "(def! gensym (fn* [] (symbol (str \"G__\" (swap! *gensym-counter* (fn* [x] (+ 1 x)))))))",
+    "(defmacro! defmulti (fn* (name dispatch & options) `(def! ~name (multi-fn '~name ~dispatch ~@(if options options (list))))))",
+    "(defmacro! defmethod (fn* (name dispatch-value & fn-tail) `(add-method ~name ~dispatch-value (fn* ~@fn-tail))))",
+    "(defmacro! or (fn* (& xs) (if (empty? xs) nil (if (= 1 (count xs)) (first xs) (let* (condvar (gensym)) `(let* (~condvar ~(first xs)) (if ~condvar ~condvar (or ~@(rest xs)))))))))"
+];
+
+// wer die formen auswertet: der evaluator den analysierten Node-baum oder die vm dessen
+// uebersetzung in bytecode (siehe vm)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Backend {
+    #[default]
+    Evaluator,
+    Vm
+}
+
+impl Backend {
+    pub fn eval(&self, env: EnvType, ast: &MalData) -> MalEvalResult {
+        match *self {
+            Backend::Evaluator => eval::eval(env, ast),
+            Backend::Vm => vm::eval(env, ast)
+        }
+    }
+}
+
+pub struct Interpreter {
+    namespaces: Rc<Namespaces>,
+    loader: Rc<Loader>,
+    limits: Limits,
+
+    // geteilt mit der core-funktion eval
+    backend: Rc<Cell<Backend>>
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::with_capabilities(Capabilities::all())
+    }
+
+    // interpreter, dessen core-funktionen nur im rahmen von `capabilities` auf den host zugreifen
+    pub fn with_capabilities(capabilities: Capabilities) -> Interpreter {
+        let namespaces = Namespaces::new();
+        let backend = Rc::new(Cell::new(Backend::default()));
+        let loader = Rc::new(Loader::new(&namespaces, backend.clone(), capabilities.clone()));
+
+        let interpreter = Interpreter { namespaces, loader, limits: Limits::default(), backend };
+
+        for ( name, fun ) in init_ns_map() {
+            interpreter.define_callable(name, capabilities.guard(name, fun));
+        }
+
+        // 'eval' wertet immer im aktuellen namensraum aus
+        let namespaces = Rc::downgrade(&interpreter.namespaces);
+        let backend = interpreter.backend.clone();
+        let eval_fun: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, args: &[MalData]| {
+            let form = args.first().ok_or_else( || CoreError::arity("eval", 1, Some(1), args.len()) )?;
+            let env = namespaces.upgrade().ok_or_else( || EvalError::General("eval: interpreter dropped".to_owned()) )?.current();
+
+            backend.get().eval(env, form)
+        });
+        interpreter.define_callable("eval", capabilities.guard("eval", eval_fun));
+
+        for ( name, fun ) in namespace::natives(&interpreter.namespaces).into_iter().chain(loader::natives(&interpreter.loader)) {
+            interpreter.define_callable(name, fun);
+        }
+
+        interpreter.load_prelude();
+
+        interpreter.define("*host-language*", make_mal_string("mro-rust"));
+        interpreter.define("*ARGV*", make_mal_list_from_vec(vec![]));
+        interpreter.define("*file*", MalData::Nil);
+
+        // mit binding umzulenken wie die mit ^:dynamic definierten vars
+        for name in &["*host-language*", "*ARGV*", "*file*"] {
+            Env::set_dynamic(&interpreter.namespaces.core(), SymbolId::intern(name));
+        }
+
+        interpreter
+    }
+
+    // das prelude gehoert zu mal.core
+    fn load_prelude(&self) {
+        let current = self.namespaces.current_name();
+        self.namespaces.in_ns(namespace::CORE);
+
+        for form in PRELUDE {
+            self.eval_str(form).expect("prelude must evaluate");
+        }
+
+        self.namespaces.in_ns(&current);
+    }
+
+    // backend fuer jede folgende auswertung; das prelude wird damit neu ausgewertet, damit auch
+    // dessen funktionen und makros vom gewaehlten backend ausgefuehrt werden
+    pub fn with_backend(self, backend: Backend) -> Interpreter {
+        self.backend.set(backend);
+        self.load_prelude();
+        self
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend.get()
+    }
+
+    // grenzen fuer jede folgende auswertung, ersetzt die voreingestellten (Limits::default)
+    pub fn with_limits(mut self, limits: Limits) -> Interpreter {
+        self.limits = limits;
+        self
+    }
+
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    // verzeichnisse, in denen require die dateien der namensraeume sucht, ersetzt MAL_PATH
+    pub fn with_search_path(self, search_path: Vec<PathBuf>) -> Interpreter {
+        self.loader.set_search_path(search_path);
+        self
+    }
+
+    pub fn search_path(&self) -> Vec<PathBuf> {
+        self.loader.search_path()
+    }
+
+    // umgebung des aktuellen namensraums
+    pub fn env(&self) -> EnvType {
+        self.namespaces.current()
+    }
+
+    pub fn namespaces(&self) -> &Rc<Namespaces> {
+        &self.namespaces
+    }
+
+    pub fn eval(&self, ast: &MalData) -> MalEvalResult {
+        self.limits.run( || self.backend.get().eval(self.env(), ast) )
+    }
+
+    // wertet alle formen der eingabe aus und liefert das ergebnis der letzten
+    pub fn eval_str(&self, input: &str) -> MalEvalResult {
+        let ast = reader::read_str(&format!("(do {}\n)", input))?;
+        self.eval(&ast)
+    }
+
+    // wie load-file, aber ohne pruefung der capabilities
+    pub fn eval_file<P: AsRef<Path>>(&self, path: P) -> MalEvalResult {
+        self.limits.run( || self.loader.load("eval-file", path.as_ref()) )
+    }
+
+    // read-eval-print einer einzelnen form, leere eingaben ergeben einen leeren string
+    pub fn rep(&self, input: &str) -> Result<String, EvalError> {
+        let ast = reader::read_str(input)?;
+
+        if let MalData::Nothing = ast {
+            return Ok("".to_owned());
+        }
+
+        self.eval(&ast).map( |res| printer::pr_str(&res, true) )
+    }
+
+    // definiert `name` in mal.core und damit in jedem namensraum
+    pub fn define<V: IntoMal>(&self, name: &str, value: V) {
+        self.namespaces.core().borrow_mut().set(name, &value.into_mal());
+    }
+
+    pub fn define_callable(&self, name: &str, fun: Rc<CallableFun>) {
+        self.define(name, MalData::Function(NativeFunction::new(name, fun)));
+    }
+
+    pub fn define_fn<Args, F: IntoNativeFn<Args>>(&self, name: &str, fun: F) {
+        self.define(name, MalData::Function(native_fn(name, fun)));
+    }
+
+    pub fn get(&self, name: &str) -> Option<MalData> {
+        Env::get(&self.namespaces.current(), name)
+    }
+
+    // ruft die unter `name` definierte funktion mit bereits ausgewerteten argumenten auf
+    pub fn call(&self, name: &str, args: &[MalData]) -> MalEvalResult {
+        let fun = self.get(name).ok_or_else( || EvalError::General(format!("'{}' not found", name)) )?;
+
+        self.limits.run( || eval::apply(self.env(), &fun, args) )
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
@@ -0,0 +1,28 @@
+// unterbrechen einer laufenden auswertung von aussen, z.b. aus einem signal-handler fuer SIGINT;
+// der evaluator prueft das flag in jedem schritt und bricht mit EvalError::Interrupted ab, das
+// nicht von try* gefangen wird
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use eval::EvalError;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// fordert den abbruch der laufenden auswertung an; darf aus einem signal-handler aufgerufen werden
+pub fn interrupt() {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+// verwirft eine angeforderte, aber nicht mehr zum zug gekommene unterbrechung
+pub fn clear() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+// das flag wird beim abbruch zurueckgesetzt, damit finally*-formen auf dem weg nach aussen noch laufen
+pub fn check() -> Result<(), EvalError> {
+    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+        Err(EvalError::Interrupted)
+    } else {
+        Ok(())
+    }
+}
@@ -6,10 +6,30 @@ extern crate regex;
 
 #[macro_use] extern crate log;
 
+#[cfg(feature = "serde")]
+extern crate serde;
+
 pub mod common;
 pub mod reader;
 pub mod printer;
 pub mod env;
+pub mod gc;
 pub mod core;
 pub mod eval;
+pub mod analyze;
+pub mod destructure;
+pub mod dynamic;
+pub mod namespace;
+pub mod loader;
+pub mod protocol;
+pub mod vm;
+pub mod convert;
+pub mod capabilities;
+pub mod limits;
+pub mod interrupt;
+pub mod interpreter;
+
+pub use interpreter::{Interpreter, Backend};
 
+#[cfg(feature = "serde")]
+mod serialization;
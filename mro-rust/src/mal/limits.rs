@@ -0,0 +1,234 @@
+// grenzen fuer die auswertung: anzahl der evaluator-schritte, verschachtelungstiefe der nativen
+// eval-aufrufe, der dabei verbrauchte stack und verstrichene zeit; eine ueberschreitung liefert
+// CoreError::LimitExceeded
+//
+//     let limits = Limits::none().with_max_steps(100_000).with_timeout(Duration::from_millis(500));
+//     let interpreter = Interpreter::new().with_limits(limits);
+//
+// die grenzen gelten je aufruf von Interpreter::eval (bzw. eval_str, rep, call). das
+// ueberschreiten der schritte oder der zeit faengt try* nicht ab, das der tiefe oder des stacks
+// schon, denn beim verlassen von try* ist der stack wieder abgebaut.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::hint;
+use std::time::{Duration, Instant};
+
+use eval::{CoreError, MalEvalResult};
+
+// voreingestellte tiefe; passt mit reserve auf den 8 MB grossen stack des hauptthreads (release)
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+// voreingestellter stackverbrauch in bytes; ein level der tiefe kostet in debug-builds ueber 10 KB,
+// die tiefe allein schuetzt dort also nicht. 1 MB laesst auf einem 2 MB grossen thread-stack
+// genug reserve fuer den aufrufer
+pub const DEFAULT_MAX_STACK: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Steps(u64),
+    Depth(usize),
+    Stack(usize),
+    Time(Duration)
+}
+
+impl Limit {
+    // wert fuer :limit in ex-data
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Limit::Steps(_) => "steps",
+            Limit::Depth(_) => "depth",
+            Limit::Stack(_) => "stack",
+            Limit::Time(_) => "time"
+        }
+    }
+
+    // die grenze als zahl, den stack in bytes, die zeit in millisekunden
+    pub fn max(&self) -> u64 {
+        match *self {
+            Limit::Steps(max) => max,
+            Limit::Depth(max) => max as u64,
+            Limit::Stack(max) => max as u64,
+            Limit::Time(timeout) => timeout.as_millis() as u64
+        }
+    }
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Limit::Steps(max) => write!(f, "more than {} evaluation steps", max),
+            Limit::Depth(max) => write!(f, "nesting deeper than {}", max),
+            Limit::Stack(max) => write!(f, "using more than {} bytes of stack", max),
+            Limit::Time(timeout) => write!(f, "running longer than {} ms", timeout.as_millis())
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    max_steps: Option<u64>,
+    max_depth: Option<usize>,
+    max_stack: Option<usize>,
+    timeout: Option<Duration>
+}
+
+impl Limits {
+    // ohne jede grenze; tiefe rekursion kann dann den stack ueberlaufen lassen
+    pub fn none() -> Limits {
+        Limits { max_steps: None, max_depth: None, max_stack: None, timeout: None }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: u64) -> Limits {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Limits {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    // stack in bytes, den eine auswertung ab ihrem beginn verbrauchen darf
+    pub fn with_max_stack(mut self, max_stack: usize) -> Limits {
+        self.max_stack = Some(max_stack);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Limits {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn max_steps(&self) -> Option<u64> {
+        self.max_steps
+    }
+
+    pub fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    pub fn max_stack(&self) -> Option<usize> {
+        self.max_stack
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    // wertet `f` mit diesen grenzen aus; laeuft bereits eine begrenzte auswertung (z.b. bei einem
+    // aufruf des interpreters aus einer nativen funktion), gelten weiter deren grenzen
+    pub fn run<F: FnOnce() -> MalEvalResult>(&self, f: F) -> MalEvalResult {
+        let installed = BUDGET.with( |budget| {
+            let mut budget = budget.borrow_mut();
+
+            if budget.is_some() {
+                false
+            } else {
+                *budget = Some(Budget::new(self.clone()));
+                true
+            }
+        });
+
+        let res = f();
+
+        if installed {
+            BUDGET.with( |budget| *budget.borrow_mut() = None );
+        }
+
+        res
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::none().with_max_depth(DEFAULT_MAX_DEPTH).with_max_stack(DEFAULT_MAX_STACK)
+    }
+}
+
+// verbrauch der laufenden auswertung
+struct Budget {
+    limits: Limits,
+    steps: u64,
+    depth: usize,
+
+    // stackadresse beim beginn der auswertung
+    stack_base: usize,
+    deadline: Option<Instant>
+}
+
+impl Budget {
+    fn new(limits: Limits) -> Budget {
+        let deadline = limits.timeout.map( |timeout| Instant::now() + timeout );
+
+        Budget { limits, steps: 0, depth: 0, stack_base: stack_address(), deadline }
+    }
+}
+
+thread_local! {
+    static BUDGET: RefCell<Option<Budget>> = const { RefCell::new(None) };
+}
+
+// eine adresse auf dem stack des aktuellen threads; der abstand zweier solcher adressen ist der
+// dazwischen verbrauchte stack
+#[inline(never)]
+fn stack_address() -> usize {
+    let marker = 0u8;
+    hint::black_box(&marker) as *const u8 as usize
+}
+
+fn exceeded(limit: Limit) -> CoreError {
+    CoreError::LimitExceeded { limit }
+}
+
+// ein schritt des evaluators, also ein durchlauf der eval-schleife
+pub fn step() -> Result<(), CoreError> {
+    BUDGET.with( |budget| {
+        if let Some(ref mut budget) = *budget.borrow_mut() {
+            budget.steps += 1;
+
+            match budget.limits.max_steps {
+                Some(max) if budget.steps > max => return Err(exceeded(Limit::Steps(max))),
+                _ => ()
+            }
+
+            match ( budget.deadline, budget.limits.timeout ) {
+                ( Some(deadline), Some(timeout) ) if Instant::now() >= deadline => return Err(exceeded(Limit::Time(timeout))),
+                _ => ()
+            }
+        }
+
+        Ok(())
+    })
+}
+
+// betreten eines verschachtelten eval; bei erfolg ist anschliessend leave aufzurufen
+pub fn enter() -> Result<(), CoreError> {
+    BUDGET.with( |budget| {
+        if let Some(ref mut budget) = *budget.borrow_mut() {
+            match budget.limits.max_depth {
+                Some(max) if budget.depth >= max => return Err(exceeded(Limit::Depth(max))),
+                _ => budget.depth += 1
+            }
+
+            match budget.limits.max_stack {
+                Some(max) if stack_address().abs_diff(budget.stack_base) > max => {
+                    budget.depth -= 1;
+                    return Err(exceeded(Limit::Stack(max)));
+                }
+
+                _ => ()
+            }
+        }
+
+        Ok(())
+    })
+}
+
+pub fn leave() {
+    BUDGET.with( |budget| {
+        if let Some(ref mut budget) = *budget.borrow_mut() {
+            budget.depth = budget.depth.saturating_sub(1);
+        }
+    })
+}
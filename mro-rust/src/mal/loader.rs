@@ -0,0 +1,213 @@
+// laden von dateien: require laedt einen namensraum, den es noch nicht gibt, aus einer datei. der
+// name app.util steht fuer die datei app/util.mal (ein - im namen fuer _), gesucht in den
+// verzeichnissen von MAL_PATH (getrennt wie bei PATH, ohne MAL_PATH im arbeitsverzeichnis). ein
+// string nennt die datei selbst; ein relativer pfad gilt zuerst ab dem verzeichnis der ladenden
+// datei, ausserhalb einer datei ab dem arbeitsverzeichnis, danach ab den verzeichnissen von MAL_PATH.
+//
+//     (ns app.main (:require [app.util :as u] "helpers.mal"))
+//
+// require laedt jede datei nur einmal, load-file wertet sie bei jedem aufruf aus. waehrend eine
+// datei geladen wird, nennt *file* ihren pfad; eine datei, die sich ueber andere selbst laedt,
+// ist ein fehler. nach dem laden ist wieder der namensraum aktuell, der vorher aktuell war.
+
+use std::collections::HashSet;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+use reader;
+use env::{Env, SymbolId};
+use common::{MalData, CallableFun, FunContext, make_mal_string};
+use capabilities::{Capabilities, Capability};
+use interpreter::Backend;
+use namespace::{self, Namespaces};
+use eval::{EvalError, CoreError, MalEvalResult};
+
+pub struct Loader {
+    registry: Weak<Namespaces>,
+    backend: Rc<Cell<Backend>>,
+    capabilities: Capabilities,
+    search_path: RefCell<Vec<PathBuf>>,
+
+    // kanonische pfade der mit require geladenen dateien
+    loaded: RefCell<HashSet<PathBuf>>,
+
+    // die gerade geladenen dateien, die innerste zuletzt
+    loading: RefCell<Vec<PathBuf>>
+}
+
+impl Loader {
+    // lader fuer die namensraeume von `registry`, der suchpfad kommt aus MAL_PATH, wenn die
+    // capabilities das lesen der umgebung des prozesses erlauben
+    pub fn new(registry: &Rc<Namespaces>, backend: Rc<Cell<Backend>>, capabilities: Capabilities) -> Loader {
+        let search_path = match env::var_os("MAL_PATH") {
+            Some(ref paths) if capabilities.has(Capability::Process) => env::split_paths(paths).collect(),
+            _ => Vec::new()
+        };
+
+        Loader {
+            registry: Rc::downgrade(registry),
+            backend,
+            capabilities,
+            search_path: RefCell::new(search_path),
+            loaded: RefCell::new(HashSet::new()),
+            loading: RefCell::new(Vec::new())
+        }
+    }
+
+    pub fn search_path(&self) -> Vec<PathBuf> {
+        self.search_path.borrow().clone()
+    }
+
+    pub fn set_search_path(&self, search_path: Vec<PathBuf>) {
+        *self.search_path.borrow_mut() = search_path;
+    }
+
+    // (require spec): laedt bei bedarf die datei des namensraums bzw. die genannte datei, dann
+    // wie Namespaces::require
+    pub fn require(&self, spec: &MalData) -> Result<(), EvalError> {
+        let registry = self.registry()?;
+
+        if let MalData::String(ref path) = *spec {
+            let found = self.find(Path::new(&**path), true)
+                .ok_or_else( || CoreError::io("require", &format!("{}: file not found", path)) )?;
+
+            return self.load_once(&found);
+        }
+
+        if let Some(name) = namespace::spec_name(spec) {
+            if registry.find(name).is_none() {
+                let file = module_file(name);
+                let found = self.find(&file, false).ok_or_else( || CoreError::illegal_argument("require",
+                    &format!("no namespace named {} and no file {} on the search path", name, file.display())) )?;
+
+                self.load_once(&found)?;
+
+                if registry.find(name).is_none() {
+                    return Err(CoreError::illegal_argument("require", &format!("{} does not define namespace {}", found.display(), name)).into());
+                }
+            }
+        }
+
+        registry.require(spec).map_err(EvalError::from)
+    }
+
+    // wertet die datei `path` im aktuellen namensraum aus und liefert den wert der letzten form;
+    // `fun` nennt die ladende funktion in fehlermeldungen
+    pub fn load(&self, fun: &str, path: &Path) -> MalEvalResult {
+        let registry = self.registry()?;
+        let path = path.canonicalize().map_err( |err| CoreError::io(fun, &format!("{}: {}", path.display(), err)) )?;
+
+        if let Some(at) = self.loading.borrow().iter().position( |loading| *loading == path ) {
+            let cycle: Vec<String> = self.loading.borrow()[at..].iter().chain(Some(&path))
+                .map( |path| path.display().to_string() )
+                .collect();
+
+            return Err(CoreError::illegal_argument(fun, &format!("circular load: {}", cycle.join(" -> "))).into());
+        }
+
+        let source = fs::read_to_string(&path).map_err( |err| CoreError::io(fun, &format!("{}: {}", path.display(), err)) )?;
+        let ast = reader::read_str(&format!("(do {}\n)", source))
+            .map_err( |message| CoreError::Reader { fun: fun.to_owned(), message: format!("{}: {}", path.display(), message) } )?;
+
+        // *file* wird wie mit binding gebunden, der namensraum danach zurueckgesetzt
+        let file = SymbolId::intern("*file*");
+        Env::push_binding(&registry.core(), file, make_mal_string(&path.display().to_string()));
+        let outer_ns = registry.current_name();
+
+        self.loading.borrow_mut().push(path);
+        let res = self.backend.get().eval(registry.current(), &ast);
+        self.loading.borrow_mut().pop();
+
+        Env::pop_binding(&registry.core(), file);
+        registry.in_ns(&outer_ns);
+
+        res
+    }
+
+    fn load_once(&self, path: &Path) -> Result<(), EvalError> {
+        if self.loaded.borrow().contains(path) {
+            return Ok(());
+        }
+
+        self.check("require", path)?;
+        self.load("require", path)?;
+        self.loaded.borrow_mut().insert(path.to_path_buf());
+
+        Ok(())
+    }
+
+    // kanonischer pfad der ersten vorhandenen datei `path`: ab der ladenden datei (`relative`),
+    // dann ab den verzeichnissen des suchpfads
+    fn find(&self, path: &Path, relative: bool) -> Option<PathBuf> {
+        if path.is_absolute() {
+            return path.canonicalize().ok();
+        }
+
+        let mut dirs = self.search_path();
+
+        if dirs.is_empty() {
+            dirs.push(PathBuf::from("."));
+        }
+
+        if relative {
+            let base = self.loading.borrow().last().and_then( |file| file.parent() ).map_or_else( || PathBuf::from("."), Path::to_path_buf );
+            dirs.insert(0, base);
+        }
+
+        dirs.into_iter()
+            .map( |dir| dir.join(path) )
+            .find( |candidate| candidate.is_file() )
+            .and_then( |candidate| candidate.canonicalize().ok() )
+    }
+
+    // laden heisst lesen und auswerten
+    fn check(&self, fun: &str, path: &Path) -> Result<(), CoreError> {
+        self.capabilities.check(fun, Capability::FsRead)?;
+        self.capabilities.check(fun, Capability::Eval)?;
+        self.capabilities.check_path(fun, &path.display().to_string())
+    }
+
+    fn registry(&self) -> Result<Rc<Namespaces>, EvalError> {
+        self.registry.upgrade().ok_or_else( || EvalError::General("the interpreter of this loader no longer exists".to_owned()) )
+    }
+}
+
+// app.util-helpers -> app/util_helpers.mal
+fn module_file(name: &str) -> PathBuf {
+    let mut file: PathBuf = name.split('.').map( |part| part.replace('-', "_") ).collect();
+    file.set_extension("mal");
+
+    file
+}
+
+// require und load-file, gebunden an `loader`
+pub fn natives(loader: &Rc<Loader>) -> Vec<( &'static str, Rc<CallableFun> )> {
+    let require_loader = loader.clone();
+    let require: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, args: &[MalData]| {
+        for spec in args {
+            require_loader.require(spec)?;
+        }
+
+        Ok(MalData::Nil)
+    });
+
+    let load_file_loader = loader.clone();
+    let load_file: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, args: &[MalData]| {
+        let path = match args {
+            [MalData::String(ref path)] => path,
+            [other] => return Err(CoreError::type_error("load-file", 1, "string", other).into()),
+            _ => return Err(CoreError::arity("load-file", 1, Some(1), args.len()).into())
+        };
+
+        let found = load_file_loader.find(Path::new(&**path), true)
+            .ok_or_else( || CoreError::io("load-file", &format!("{}: file not found", path)) )?;
+
+        load_file_loader.check("load-file", &found)?;
+        load_file_loader.load("load-file", &found)
+    });
+
+    vec![( "require", require ), ( "load-file", load_file )]
+}
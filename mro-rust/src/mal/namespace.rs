@@ -0,0 +1,324 @@
+// namensraeume: jeder namensraum ist eine aeusserste umgebung mit eigener globaler tabelle, die das
+// verzeichnis des interpreters unter seinem namen fuehrt. einen name, den ein namensraum nicht
+// selbst definiert, sucht er in den referierten namensraeumen; mal.core mit den core-funktionen
+// und dem prelude ist in jedem referiert. ein qualifizierter name alias/name bezeichnet den namen
+// im namensraum hinter dem alias; unter seinem vollen namen ist jeder namensraum erreichbar.
+//
+//     (ns app.main (:require [app.util :as u :refer [parse]]))
+//     (u/format (parse "..."))
+//
+// die formen einer eingabe werden im jeweils aktuellen namensraum analysiert und ausgewertet,
+// ein ns wirkt daher ab der folgenden form. *ns* nennt den aktuellen namensraum. einen namensraum,
+// den es noch nicht gibt, laedt require aus einer datei (siehe loader).
+
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::{Rc, Weak};
+
+use env::{Env, EnvType, SymbolId, wrapped_env_type};
+use common::{MalData, CallableFun, FunContext, make_mal_list_from_vec, make_mal_symbol, make_mal_keyword};
+use printer::pr_str;
+use eval::{EvalError, CoreError};
+
+pub const CORE: &str = "mal.core";
+pub const USER: &str = "user";
+
+// verzeichnis der namensraeume eines interpreters
+pub struct Namespaces {
+    envs: RefCell<HashMap<Rc<str>, EnvType>>,
+    current: RefCell<EnvType>
+}
+
+// angaben eines namensraums, gehalten von dessen umgebung
+pub struct Namespace {
+    name: Rc<str>,
+    registry: Weak<Namespaces>,
+
+    // mal.core, zuerst durchsucht; mal.core selbst referiert nichts, haelt also keinen zyklus
+    core: Option<EnvType>,
+
+    // die namensraeume hinter den aliasen und die uebrigen referierten; schwach, da das
+    // verzeichnis sie haelt
+    aliases: RefCell<HashMap<SymbolId, Weak<RefCell<Env>>>>,
+    refers: RefCell<Vec<Refer>>
+}
+
+struct Refer {
+    env: Weak<RefCell<Env>>,
+
+    // nur diese namen, sonst alle
+    only: Option<Vec<SymbolId>>
+}
+
+impl Refer {
+    fn admits(&self, id: SymbolId) -> bool {
+        self.only.as_ref().is_none_or( |only| only.contains(&id) )
+    }
+}
+
+impl fmt::Debug for Namespace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Namespace {{ name: {} }}", self.name)
+    }
+}
+
+impl Namespaces {
+    // verzeichnis mit mal.core und dem aktuellen namensraum user
+    pub fn new() -> Rc<Namespaces> {
+        Rc::new_cyclic( |registry| {
+            let core = new_namespace(registry, CORE, None);
+            let user = new_namespace(registry, USER, Some(&core));
+
+            core.borrow_mut().set("*ns*", &make_mal_symbol(USER));
+
+            let envs = vec![( Rc::from(CORE), core ), ( Rc::from(USER), user.clone() )];
+            Namespaces { envs: RefCell::new(envs.into_iter().collect()), current: RefCell::new(user) }
+        })
+    }
+
+    pub fn core(&self) -> EnvType {
+        self.find(CORE).expect("mal.core is always registered")
+    }
+
+    pub fn current(&self) -> EnvType {
+        self.current.borrow().clone()
+    }
+
+    pub fn current_name(&self) -> String {
+        Env::namespace_of(&self.current()).map_or_else( || USER.to_owned(), |ns| ns.name().to_owned() )
+    }
+
+    pub fn find(&self, name: &str) -> Option<EnvType> {
+        self.envs.borrow().get(name).cloned()
+    }
+
+    // macht den namensraum `name` zum aktuellen, ein unbekannter wird angelegt
+    pub fn in_ns(self: &Rc<Namespaces>, name: &str) -> EnvType {
+        let existing = self.find(name);
+
+        let env = existing.unwrap_or_else( || {
+            let env = new_namespace(&Rc::downgrade(self), name, Some(&self.core()));
+            self.envs.borrow_mut().insert(Rc::from(name), env.clone());
+
+            env
+        });
+
+        *self.current.borrow_mut() = env.clone();
+        self.core().borrow_mut().set("*ns*", &make_mal_symbol(name));
+
+        env
+    }
+
+    // (require spec): spec ist ein name oder [name :as alias :refer [namen...]] bzw. :refer :all;
+    // wirkt auf den aktuellen namensraum
+    pub fn require(&self, spec: &MalData) -> Result<(), CoreError> {
+        let ( name, options ) = match *spec {
+            MalData::Symbol(ref name) => ( name.clone(), &[][..] ),
+
+            MalData::Vector(ref items, _) | MalData::List(ref items, _) => match items.split_first() {
+                Some(( MalData::Symbol(name), options )) => ( name.clone(), options ),
+                _ => return Err(require_error(&format!("expected a namespace name in {}", pr_str(spec, true))))
+            },
+
+            ref other =>
+                return Err(require_error(&format!("expected a symbol or vector, got {}", other.type_name())))
+        };
+
+        let target = self.find(&name).ok_or_else( || require_error(&format!("no namespace named {}", name)) )?;
+        let current = self.current();
+        let ns = Env::namespace_of(&current).expect("registered environments have a namespace");
+
+        if options.len() % 2 != 0 {
+            return Err(require_error(&format!("options must come in pairs in {}", pr_str(spec, true))));
+        }
+
+        for option in options.chunks(2) {
+            match ( &option[0], &option[1] ) {
+                ( key, MalData::Symbol(alias) ) if *key == make_mal_keyword("as") =>
+                    ns.alias(SymbolId::intern(alias), &target),
+
+                ( key, value ) if *key == make_mal_keyword("refer") =>
+                    ns.refer(&target, refer_names(value)?),
+
+                ( key, value ) =>
+                    return Err(require_error(&format!("unsupported option {} {}", pr_str(key, true), pr_str(value, true))))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Namespace {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // wert eines nicht selbst definierten namens
+    pub fn lookup(&self, id: SymbolId) -> Option<MalData> {
+        if let Some(value) = self.core.as_ref().and_then( |core| core.borrow().interned(id) ) {
+            return Some(value);
+        }
+
+        let referred = self.refers.borrow().iter()
+            .filter( |refer| refer.admits(id) )
+            .find_map( |refer| refer.env.upgrade().and_then( |env| env.borrow().interned(id) ) );
+
+        referred.or_else( || self.qualified(id).and_then( |( env, id )| Env::get_interned(&env, id) ) )
+    }
+
+    // umgebung und name eines nicht selbst definierten namens: in einem referierten namensraum
+    // oder qualifiziert
+    pub fn resolve(&self, id: SymbolId) -> Option<( EnvType, SymbolId )> {
+        let referred = self.core.clone().into_iter()
+            .chain(self.refers.borrow().iter().filter( |refer| refer.admits(id) ).filter_map( |refer| refer.env.upgrade() ))
+            .find( |env| Env::get_interned(env, id).is_some() );
+
+        match referred {
+            Some(env) => Some(( env, id )),
+            None => self.qualified(id)
+        }
+    }
+
+    // alias/name im namensraum hinter dem alias bzw. mit diesem namen
+    fn qualified(&self, id: SymbolId) -> Option<( EnvType, SymbolId )> {
+        let name = id.name();
+
+        match name.find('/') {
+            Some(at) if at > 0 && at + 1 < name.len() => {
+                let alias = self.aliases.borrow().get(&SymbolId::intern(&name[..at])).and_then(Weak::upgrade);
+                let target = alias.or_else( || self.registry.upgrade().and_then( |registry| registry.find(&name[..at]) ) )?;
+
+                Some(( target, SymbolId::intern(&name[at + 1..]) ))
+            }
+
+            _ =>
+                None
+        }
+    }
+
+    fn alias(&self, alias: SymbolId, env: &EnvType) {
+        self.aliases.borrow_mut().insert(alias, Rc::downgrade(env));
+    }
+
+    fn refer(&self, env: &EnvType, only: Option<Vec<SymbolId>>) {
+        self.refers.borrow_mut().push(Refer { env: Rc::downgrade(env), only });
+    }
+}
+
+// neue aeusserste umgebung fuer den namensraum `name`, die `core` referiert
+fn new_namespace(registry: &Weak<Namespaces>, name: &str, core: Option<&EnvType>) -> EnvType {
+    let ns = Namespace {
+        name: Rc::from(name),
+        registry: registry.clone(),
+        core: core.cloned(),
+        aliases: RefCell::new(HashMap::new()),
+        refers: RefCell::new(Vec::new())
+    };
+
+    wrapped_env_type(Env::for_namespace(Rc::new(ns)))
+}
+
+// :refer [namen...] oder :refer :all
+fn refer_names(value: &MalData) -> Result<Option<Vec<SymbolId>>, CoreError> {
+    match *value {
+        MalData::Vector(ref names, _) | MalData::List(ref names, _) =>
+            names.iter().map( |name| match *name {
+                MalData::Symbol(ref name) => Ok(SymbolId::intern(name)),
+                ref other => Err(require_error(&format!(":refer expects symbols, got {}", other.type_name())))
+            }).collect::<Result<Vec<SymbolId>, CoreError>>().map(Some),
+
+        ref all if *all == make_mal_keyword("all") =>
+            Ok(None),
+
+        ref other =>
+            Err(require_error(&format!(":refer expects a vector of names or :all, got {}", pr_str(other, true))))
+    }
+}
+
+fn require_error(message: &str) -> CoreError {
+    CoreError::illegal_argument("require", message)
+}
+
+// umgebung, in der die naechste form einer eingabe ausgewertet wird: bei der umgebung eines
+// namensraums der aktuelle namensraum, sonst die umgebung selbst
+pub fn current(env: &EnvType) -> EnvType {
+    let registry = if env.borrow().is_root() { Env::namespace_of(env).and_then( |ns| ns.registry.upgrade() ) } else { None };
+    registry.map_or_else( || env.clone(), |registry| registry.current() )
+}
+
+// (ns name (:require spec...) ...) wird zu
+// (do (mal.core/in-ns 'name) (mal.core/require 'spec...) ... nil)
+pub fn ns_form(args: &[MalData]) -> Result<MalData, EvalError> {
+    let name = match args.first() {
+        Some(MalData::Symbol(name)) => name,
+        _ => return Err(EvalError::General("ns requires a namespace name".to_owned()))
+    };
+
+    let mut forms = vec![make_mal_symbol("do"), call("in-ns", vec![quote(make_mal_symbol(name))])];
+
+    for reference in &args[1..] {
+        match *reference {
+            MalData::List(ref items, _) if items.first() == Some(&make_mal_keyword("require")) =>
+                forms.push(call("require", items[1..].iter().cloned().map(quote).collect())),
+
+            ref other =>
+                return Err(EvalError::General(format!("ns: unsupported reference {}, expected (:require ...)", pr_str(other, true))))
+        }
+    }
+
+    forms.push(MalData::Nil);
+    Ok(make_mal_list_from_vec(forms))
+}
+
+fn call(name: &str, args: Vec<MalData>) -> MalData {
+    let fun = make_mal_symbol(&format!("{}/{}", CORE, name));
+    make_mal_list_from_vec(Some(fun).into_iter().chain(args).collect())
+}
+
+fn quote(form: MalData) -> MalData {
+    make_mal_list_from_vec(vec![make_mal_symbol("quote"), form])
+}
+
+// name des namensraums einer angabe fuer require: name oder [name ...]
+pub fn spec_name(spec: &MalData) -> Option<&str> {
+    match *spec {
+        MalData::Symbol(ref name) => Some(name),
+
+        MalData::Vector(ref items, _) | MalData::List(ref items, _) => match items.first() {
+            Some(MalData::Symbol(name)) => Some(name),
+            _ => None
+        },
+
+        _ =>
+            None
+    }
+}
+
+// in-ns, gebunden an das verzeichnis `registry`; require stellt der loader bereit
+pub fn natives(registry: &Rc<Namespaces>) -> Vec<( &'static str, Rc<CallableFun> )> {
+    let in_ns_registry = Rc::downgrade(registry);
+    let in_ns: Rc<CallableFun> = Rc::new(move |_ctx: &FunContext, args: &[MalData]| {
+        let registry = in_ns_registry.upgrade().ok_or_else(dropped)?;
+
+        match args {
+            [MalData::Symbol(ref name)] => {
+                registry.in_ns(name);
+                Ok(make_mal_symbol(name))
+            }
+
+            [other] =>
+                Err(CoreError::type_error("in-ns", 1, "symbol", other).into()),
+
+            _ =>
+                Err(CoreError::arity("in-ns", 1, Some(1), args.len()).into())
+        }
+    });
+
+    vec![( "in-ns", in_ns )]
+}
+
+fn dropped() -> EvalError {
+    EvalError::General("the interpreter of this namespace no longer exists".to_owned())
+}
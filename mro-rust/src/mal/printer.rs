@@ -18,7 +18,7 @@ impl PrStr for MapKey {
             MapKey::True => "true".to_owned(),
             MapKey::False => "false".to_owned(),
             MapKey::String(ref string) => make_readable_string(string),
-            MapKey::Symbol(ref sym) => sym.clone(),
+            MapKey::Symbol(ref sym) => sym.to_string(),
             MapKey::Keyword(ref kw) => ":".chars().chain(kw.chars().skip(1)).collect(),
             MapKey::Number(ref num) => num.to_string(),
         }
@@ -33,8 +33,8 @@ impl<'d> PrStr for MalData {
             MalData::Nil => "nil".to_owned(),
             MalData::True => "true".to_owned(),
             MalData::False => "false".to_owned(),
-            MalData::String(ref string) => if print_readably { make_readable_string(string) } else { string.clone() },
-            MalData::Symbol(ref sym) => sym.clone(),  // TODO symbolname
+            MalData::String(ref string) => if print_readably { make_readable_string(string) } else { string.to_string() },
+            MalData::Symbol(ref sym) => sym.to_string(),  // TODO symbolname
             MalData::Keyword(ref kw) => ":".chars().chain(kw.chars().skip(1)).collect(),
             MalData::Number(ref num) => num.to_string(),  // TODO zahl
 
@@ -75,8 +75,22 @@ impl<'d> PrStr for MalData {
 
             MalData::Function(_) | MalData::FnClosure(_) => "#<function>".to_string(),
 
-            MalData::Exception(_) =>
-                "#<exception>".to_string(),
+            MalData::Opaque(ref opaque) => opaque.print(),
+
+            MalData::ExInfo(ref ex_info) => {
+                let mut out = format!("#error {{:message {} :data {}",
+                                      make_readable_string(&ex_info.message),
+                                      pr_str(&ex_info.data, print_readably));
+
+                if let Some(ref cause) = ex_info.cause {
+                    out.push_str(" :cause ");
+                    out.push_str(&pr_str(cause, print_readably));
+                }
+
+                out.push_str("}");
+
+                out
+            }
         }
     }
 }
@@ -85,7 +99,7 @@ pub fn pr_str(data: &PrStr, print_readably: bool) -> String {
     data.pr_str(print_readably)
 }
 
-fn make_readable_string(string: &String) -> String {
+fn make_readable_string(string: &str) -> String {
     let newline_re = Regex::new(r"\n").unwrap();
     let dquote_re = Regex::new(r#"""#).unwrap();
     let backslash_re = Regex::new(r#"\\"#).unwrap();
@@ -0,0 +1,293 @@
+// protokolle: (defprotocol Name (methode [this ...]) ...) definiert Name und je methode eine
+// funktion, die nach dem typ ihres ersten arguments verzweigt. implementiert werden die methoden
+// mit extend-type bzw. extend-protocol, fuer typen wie :list, :vector, :map, :string und :fn oder
+// den typ eines opaken werts (siehe type_of); eine implementierung fuer :default gilt fuer alle
+// typen ohne eigene.
+//
+//     (defprotocol Shape (area [this]) (scale [this k]))
+//     (extend-type :vector Shape
+//       (area [v] (* (nth v 0) (nth v 1)))
+//       (scale [v k] [(* k (nth v 0)) (* k (nth v 1))]))
+//     (extend-protocol Shape
+//       :map (area [m] (get m :area))
+//       :default (area [_] 0))
+//
+// jede methode merkt sich die implementierung fuer den zuletzt gesehenen typ, ein aufruf mit
+// demselben typ kommt ohne nachschlagen aus; extend-type verwirft diesen cache.
+
+use std::collections::HashMap;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use common::{MalData, NativeFunction, CallableFun, FunContext, Opaque};
+use common::{make_mal_list_from_vec, make_mal_symbol, make_mal_keyword};
+use printer::pr_str;
+use core::apply_fun;
+use eval::{EvalError, CoreError};
+
+const DEFAULT: &str = "default";
+
+pub struct Protocol {
+    name: Rc<str>,
+    methods: Vec<Method>
+}
+
+struct Method {
+    name: Rc<str>,
+
+    // implementierungen nach typname
+    impls: RefCell<HashMap<Rc<str>, MalData>>,
+
+    // zuletzt gesehener typ und dessen implementierung
+    cache: RefCell<Option<( Rc<str>, MalData )>>
+}
+
+impl Protocol {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // ob `type_name` (oder :default) mindestens eine methode implementiert
+    pub fn extends(&self, type_name: &str) -> bool {
+        self.methods.iter().any( |method| {
+            let impls = method.impls.borrow();
+            impls.contains_key(type_name) || impls.contains_key(DEFAULT)
+        })
+    }
+
+    fn method(&self, name: &str) -> Option<usize> {
+        self.methods.iter().position( |method| &*method.name == name )
+    }
+
+    fn implementation(&self, index: usize, type_name: &str) -> Option<MalData> {
+        let method = &self.methods[index];
+
+        if let Some(( ref cached, ref fun )) = *method.cache.borrow() {
+            if &**cached == type_name {
+                return Some(fun.clone());
+            }
+        }
+
+        let impls = method.impls.borrow();
+        let ( key, fun ) = impls.get_key_value(type_name).or_else( || impls.get_key_value(DEFAULT) )?;
+        *method.cache.borrow_mut() = Some(( if &**key == DEFAULT { Rc::from(type_name) } else { key.clone() }, fun.clone() ));
+
+        Some(fun.clone())
+    }
+
+    fn extend(&self, type_name: &str, index: usize, fun: MalData) {
+        let method = &self.methods[index];
+
+        method.impls.borrow_mut().insert(Rc::from(type_name), fun);
+        *method.cache.borrow_mut() = None;
+    }
+}
+
+// typname, nach dem protokolle verzweigen: der von type_name, bei opaken werten deren eigener
+pub fn type_of(value: &MalData) -> &str {
+    match *value {
+        MalData::Opaque(ref opaque) => opaque.type_name(),
+        ref other => other.type_name()
+    }
+}
+
+pub fn protocol_of(value: &MalData) -> Option<Rc<Protocol>> {
+    match *value {
+        MalData::Opaque(ref opaque) => opaque.downcast::<Protocol>(),
+        _ => None
+    }
+}
+
+// (defprotocol Name "doku"? (methode [this ...]+ "doku"?) ...) wird zu
+// (do (def! Name (protocol 'Name 'methode ...)) (def! methode (method Name 'methode)) ... Name)
+pub fn defprotocol_form(args: &[MalData]) -> Result<MalData, EvalError> {
+    let name = match args.first() {
+        Some(MalData::Symbol(name)) => name,
+        _ => return Err(EvalError::General("defprotocol requires a protocol name".to_owned()))
+    };
+
+    let mut methods = Vec::new();
+
+    for signature in &args[1..] {
+        match *signature {
+            MalData::String(_) => (),
+
+            MalData::List(ref items, _) => match items.split_first() {
+                Some(( MalData::Symbol(method), params )) if params.iter().any(is_param_vector) =>
+                    methods.push(method.clone()),
+
+                _ =>
+                    return Err(EvalError::General(format!("defprotocol: expected (method [this ...]), got {}", pr_str(signature, true))))
+            },
+
+            ref other =>
+                return Err(EvalError::General(format!("defprotocol: expected (method [this ...]), got {}", pr_str(other, true))))
+        }
+    }
+
+    let protocol = Some(native("protocol", make_protocol)).into_iter()
+        .chain(Some(name).into_iter().chain(&methods).map( |name| quote(make_mal_symbol(name)) ))
+        .collect();
+
+    let mut forms = vec![make_mal_symbol("do"), def(name, make_mal_list_from_vec(protocol))];
+
+    for method in &methods {
+        let dispatch = vec![native("method", make_method), make_mal_symbol(name), quote(make_mal_symbol(method))];
+        forms.push(def(method, make_mal_list_from_vec(dispatch)));
+    }
+
+    forms.push(make_mal_symbol(name));
+    Ok(make_mal_list_from_vec(forms))
+}
+
+// (extend-type typ Protokoll (methode [this ...] rumpf...) ...) wird zu
+// (extend Protokoll typ 'methode (fn* [this ...] rumpf...) ...)
+pub fn extend_type_form(args: &[MalData]) -> Result<MalData, EvalError> {
+    match args {
+        [type_name, protocol, impls @ ..] => extend_call("extend-type", type_name, protocol, impls),
+        _ => Err(EvalError::General("extend-type requires a type and a protocol".to_owned()))
+    }
+}
+
+// (extend-protocol Protokoll typ (methode ...) ... typ (methode ...) ...) wird zu
+// (do (extend Protokoll typ ...) ...)
+pub fn extend_protocol_form(args: &[MalData]) -> Result<MalData, EvalError> {
+    let ( protocol, rest ) = args.split_first().ok_or_else( || EvalError::General("extend-protocol requires a protocol".to_owned()) )?;
+    let mut forms = vec![make_mal_symbol("do")];
+    let mut rest = rest;
+
+    while let Some(( type_name, tail )) = rest.split_first() {
+        if let MalData::List(_, _) = *type_name {
+            return Err(EvalError::General(format!("extend-protocol: expected a type before {}", pr_str(type_name, true))));
+        }
+
+        let count = tail.iter().take_while( |form| matches!(form, MalData::List(_, _)) ).count();
+        forms.push(extend_call("extend-protocol", type_name, protocol, &tail[..count])?);
+        rest = &tail[count..];
+    }
+
+    forms.push(MalData::Nil);
+    Ok(make_mal_list_from_vec(forms))
+}
+
+fn extend_call(form: &str, type_name: &MalData, protocol: &MalData, impls: &[MalData]) -> Result<MalData, EvalError> {
+    let mut call = vec![native("extend", extend), protocol.clone(), type_name.clone()];
+
+    for method in impls {
+        match *method {
+            MalData::List(ref items, _) => match items.split_first() {
+                Some(( MalData::Symbol(name), fn_tail )) if !fn_tail.is_empty() => {
+                    call.push(quote(make_mal_symbol(name)));
+                    call.push(make_mal_list_from_vec(Some(make_mal_symbol("fn*")).into_iter().chain(fn_tail.iter().cloned()).collect()));
+                }
+
+                _ =>
+                    return Err(EvalError::General(format!("{}: expected (method [this ...] body...), got {}", form, pr_str(method, true))))
+            },
+
+            ref other =>
+                return Err(EvalError::General(format!("{}: expected (method [this ...] body...), got {}", form, pr_str(other, true))))
+        }
+    }
+
+    Ok(make_mal_list_from_vec(call))
+}
+
+fn is_param_vector(form: &MalData) -> bool {
+    matches!(*form, MalData::Vector(ref params, _) if !params.is_empty())
+}
+
+fn native(name: &str, fun: fn(&FunContext, &[MalData]) -> Result<MalData, EvalError>) -> MalData {
+    let callable: Rc<CallableFun> = Rc::new(fun);
+    MalData::Function(NativeFunction::new(name, callable))
+}
+
+fn def(name: &str, value: MalData) -> MalData {
+    make_mal_list_from_vec(vec![make_mal_symbol("def!"), make_mal_symbol(name), value])
+}
+
+fn quote(form: MalData) -> MalData {
+    make_mal_list_from_vec(vec![make_mal_symbol("quote"), form])
+}
+
+fn symbol_arg<'a>(fun: &str, args: &'a [MalData], index: usize) -> Result<&'a Rc<str>, EvalError> {
+    match args.get(index) {
+        Some(MalData::Symbol(name)) => Ok(name),
+        Some(other) => Err(CoreError::type_error(fun, index + 1, "symbol", other).into()),
+        None => Err(CoreError::arity(fun, index + 1, None, args.len()).into())
+    }
+}
+
+fn protocol_arg(fun: &str, args: &[MalData], index: usize) -> Result<Rc<Protocol>, EvalError> {
+    match args.get(index) {
+        Some(value) => protocol_of(value).ok_or_else( || CoreError::type_error(fun, index + 1, "protocol", value).into() ),
+        None => Err(CoreError::arity(fun, index + 1, None, args.len()).into())
+    }
+}
+
+// (protocol 'Name 'methode ...)
+fn make_protocol(_ctx: &FunContext, args: &[MalData]) -> Result<MalData, EvalError> {
+    let name = symbol_arg("defprotocol", args, 0)?.clone();
+    let methods = (1..args.len())
+        .map( |index| symbol_arg("defprotocol", args, index).map( |name| Method {
+            name: name.clone(),
+            impls: RefCell::new(HashMap::new()),
+            cache: RefCell::new(None)
+        }))
+        .collect::<Result<Vec<Method>, EvalError>>()?;
+
+    let opaque = Opaque::new("protocol", Protocol { name, methods })
+        .with_printer( |protocol: &Protocol| format!("#<protocol {}>", protocol.name) );
+
+    Ok(MalData::Opaque(opaque))
+}
+
+// (method Protokoll 'methode): die funktion, die nach dem typ des ersten arguments verzweigt
+fn make_method(_ctx: &FunContext, args: &[MalData]) -> Result<MalData, EvalError> {
+    let protocol = protocol_arg("defprotocol", args, 0)?;
+    let name = symbol_arg("defprotocol", args, 1)?.clone();
+    let index = protocol.method(&name).ok_or_else( || not_a_method("defprotocol", &protocol, &name) )?;
+
+    let method_name = name.clone();
+    let dispatch: Rc<CallableFun> = Rc::new(move |ctx: &FunContext, args: &[MalData]| {
+        let target = args.first().ok_or_else( || CoreError::arity(&method_name, 1, None, 0) )?;
+        let type_name = type_of(target);
+
+        let fun = protocol.implementation(index, type_name).ok_or_else( || CoreError::illegal_argument(&method_name,
+            &format!("no implementation of method {} of protocol {} for type :{}", method_name, protocol.name, type_name)) )?;
+
+        apply_fun(ctx, &fun, args)
+    });
+
+    Ok(MalData::Function(NativeFunction::new(&name, dispatch)))
+}
+
+// (extend Protokoll typ 'methode fn ...)
+fn extend(_ctx: &FunContext, args: &[MalData]) -> Result<MalData, EvalError> {
+    let protocol = protocol_arg("extend-type", args, 0)?;
+
+    let type_name = match args.get(1) {
+        Some(MalData::Keyword(ref keyword)) => keyword.trim_start_matches('\u{29e}'),
+        Some(other) => return Err(CoreError::type_error("extend-type", 2, "keyword naming a type like :vector", other).into()),
+        None => return Err(CoreError::arity("extend-type", 2, None, args.len()).into())
+    };
+
+    for index in (2..args.len()).step_by(2) {
+        let name = symbol_arg("extend-type", args, index)?;
+        let method = protocol.method(name).ok_or_else( || not_a_method("extend-type", &protocol, name) )?;
+        let fun = args.get(index + 1).cloned().unwrap_or(MalData::Nil);
+
+        protocol.extend(type_name, method, fun);
+    }
+
+    Ok(MalData::Nil)
+}
+
+fn not_a_method(fun: &str, protocol: &Protocol, name: &str) -> EvalError {
+    CoreError::illegal_argument(fun, &format!("{} is not a method of protocol {}", name, protocol.name)).into()
+}
+
+// typ eines werts als keyword, z.b. :vector
+pub fn type_keyword(value: &MalData) -> MalData {
+    make_mal_keyword(type_of(value))
+}
@@ -214,7 +214,7 @@ fn read_atom(reader: &mut Reader) -> Option<MalData> {
     let res = match atom {
         Some(str) if str.starts_with("\"") && str.ends_with("\"") => {
             let str_content = &str[1..str.len() - 1];    // ohne die anfuehrungszeichen
-            Some(MalData::String(transform_string(str_content)))
+            Some(MalData::String(Rc::from(transform_string(str_content))))
         }
 
         Some("nil") => {
@@ -0,0 +1,156 @@
+// serde-unterstuetzung fuer MalData und MapKey (feature "serde")
+//
+// werte werden als extern getaggte enums abgebildet, damit strings, symbole und keywords auch in
+// formaten wie JSON unterscheidbar bleiben, z.b. {"keyword":"a"}, {"string":"a"}, {"list":[...]};
+// maps werden als folge von [key, value]-paaren geschrieben, da ihre schluessel keine strings sein
+// muessen. keywords werden ohne das interne praefix abgelegt, metadaten werden nicht uebertragen.
+// funktionen, atome, ex-info und opake objekte haben keine datendarstellung und fuehren zu einem fehler.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+use serde::ser::Error;
+
+use common::{MalData, MapKey};
+
+#[derive(Serialize)]
+#[serde(rename = "MalData", rename_all = "lowercase")]
+enum MalDataRef<'a> {
+    Nil,
+    True,
+    False,
+    String(&'a str),
+    Symbol(&'a str),
+    Keyword(&'a str),
+    Number(i64),
+    List(&'a [MalData]),
+    Vector(&'a [MalData]),
+    Map(MapEntries<'a>)
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "MalData", rename_all = "lowercase")]
+enum MalDataRepr {
+    Nil,
+    True,
+    False,
+    String(String),
+    Symbol(String),
+    Keyword(String),
+    Number(i64),
+    List(Vec<MalData>),
+    Vector(Vec<MalData>),
+    Map(Vec<(MapKey, MalData)>)
+}
+
+#[derive(Serialize)]
+#[serde(rename = "MapKey", rename_all = "lowercase")]
+enum MapKeyRef<'a> {
+    True,
+    False,
+    String(&'a str),
+    Symbol(&'a str),
+    Keyword(&'a str),
+    Number(i64)
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "MapKey", rename_all = "lowercase")]
+enum MapKeyRepr {
+    True,
+    False,
+    String(String),
+    Symbol(String),
+    Keyword(String),
+    Number(i64)
+}
+
+struct MapEntries<'a>(&'a HashMap<MapKey, MalData>);
+
+impl<'a> Serialize for MapEntries<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+// name eines keywords ohne das interne praefix
+fn keyword_name(kw: &str) -> &str {
+    kw.char_indices().nth(1).map_or("", |(index, _)| &kw[index..])
+}
+
+fn keyword_value(name: &str) -> String {
+    format!("\u{29e}{}", name)
+}
+
+impl Serialize for MalData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match *self {
+            MalData::Nil => MalDataRef::Nil,
+            MalData::True => MalDataRef::True,
+            MalData::False => MalDataRef::False,
+            MalData::String(ref string) => MalDataRef::String(string),
+            MalData::Symbol(ref sym) => MalDataRef::Symbol(sym),
+            MalData::Keyword(ref kw) => MalDataRef::Keyword(keyword_name(kw)),
+            MalData::Number(num) => MalDataRef::Number(num),
+            MalData::List(ref list, _) => MalDataRef::List(list),
+            MalData::Vector(ref vec, _) => MalDataRef::Vector(vec),
+            MalData::Map(ref map, _) => MalDataRef::Map(MapEntries(map)),
+
+            MalData::Nothing | MalData::Atom(_) | MalData::Function(_) | MalData::FnClosure(_) | MalData::ExInfo(_) |
+            MalData::Opaque(_) =>
+                return Err(S::Error::custom(format!("cannot serialize a value of type {}", self.type_name())))
+        };
+
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MalData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MalData, D::Error> {
+        let value = match MalDataRepr::deserialize(deserializer)? {
+            MalDataRepr::Nil => MalData::Nil,
+            MalDataRepr::True => MalData::True,
+            MalDataRepr::False => MalData::False,
+            MalDataRepr::String(string) => MalData::String(Rc::from(string)),
+            MalDataRepr::Symbol(sym) => MalData::Symbol(Rc::from(sym)),
+            MalDataRepr::Keyword(name) => MalData::Keyword(Rc::from(keyword_value(&name))),
+            MalDataRepr::Number(num) => MalData::Number(num),
+            MalDataRepr::List(list) => MalData::List(Rc::new(list), None),
+            MalDataRepr::Vector(vec) => MalData::Vector(Rc::new(vec), None),
+            MalDataRepr::Map(entries) => MalData::Map(Rc::new(entries.into_iter().collect()), None)
+        };
+
+        Ok(value)
+    }
+}
+
+impl Serialize for MapKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = match *self {
+            MapKey::True => MapKeyRef::True,
+            MapKey::False => MapKeyRef::False,
+            MapKey::String(ref string) => MapKeyRef::String(string),
+            MapKey::Symbol(ref sym) => MapKeyRef::Symbol(sym),
+            MapKey::Keyword(ref kw) => MapKeyRef::Keyword(keyword_name(kw)),
+            MapKey::Number(num) => MapKeyRef::Number(num)
+        };
+
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MapKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<MapKey, D::Error> {
+        let key = match MapKeyRepr::deserialize(deserializer)? {
+            MapKeyRepr::True => MapKey::True,
+            MapKeyRepr::False => MapKey::False,
+            MapKeyRepr::String(string) => MapKey::String(Rc::from(string)),
+            MapKeyRepr::Symbol(sym) => MapKey::Symbol(Rc::from(sym)),
+            MapKeyRepr::Keyword(name) => MapKey::Keyword(Rc::from(keyword_value(&name))),
+            MapKeyRepr::Number(num) => MapKey::Number(num)
+        };
+
+        Ok(key)
+    }
+}
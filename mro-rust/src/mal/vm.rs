@@ -0,0 +1,1678 @@
+// bytecode-backend: der Node-baum einer form (siehe analyze) wird in einen kompakten bytecode
+// uebersetzt und von einer stapelmaschine ausgefuehrt. parameter und let*-bindungen liegen in
+// slots des stapels statt in Env-hashmaps, endrekursive aufrufe ersetzen den laufenden rahmen.
+//
+// eine closure faengt die werte der von ihr benutzten lokalen namen bei ihrer erzeugung ein. ein
+// let*-name, den eine closure schon vor seiner bindung sieht (z.b. eine rekursive lokale
+// funktion), liegt dazu in einer zelle, die erst bei der bindung gefuellt wird.
+//
+// wie im evaluator bindet def! innerhalb einer funktion oder eines let* einen lokalen namen fuer
+// die folgenden formen des bereichs. benutzt eine closure des bereichs den namen, liegt er von
+// anfang an in einer zelle, die def! neu fuellt; bis dahin gilt fuer sie der aeussere name. ein erst nach der uebersetzung definiertes makro wird zur
+// laufzeit expandiert und vom evaluator ausgewertet, ebenso eine nach einer neudefinition ihres
+// makros ungueltige expansion.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use env::{EnvType, Env, Symbol, SymbolId, wrapped_env_type, select_arity};
+use analyze::{analyze, clause_named, Node, Var, Lambda, Loop, Recur, Call, Try, Expansion, LateExpansion, macros_changed};
+use common::{MalData, MapKey, FnClosure, FnCode, mal_value_for, make_mal_atom};
+use printer::pr_str;
+use limits;
+use interrupt;
+use namespace;
+use eval::{self, EvalError, CoreError, MalEvalResult, StackFrame};
+use eval::{call_stack_depth, enter_call_frame, push_call_frame, leave_call_frames, capture_call_stack, set_caught_call_stack};
+
+// operanden sind indizes in die tabellen des Proto bzw. slots des rahmens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Const(u32),
+    LoadLocal(u32),
+    LoadCell(u32),
+    LoadDefined(u16, u32),
+    LoadUpvalue(u32),
+    LoadUpvalueCell(u32),
+    LoadGlobal(u32),
+    StoreLocal(u32),
+    NewCell(u32),
+    StoreCell(u32),
+    Pop,
+    Jump(u32),
+    JumpIfFalse(u32),
+    Recur(u32),
+    Vector(u32),
+    Map(u32),
+    Def(u32),
+    DefLocal(u16, u32),
+    DefCell(u16, u32),
+    DefMacro(u32),
+    Closure(u32),
+    Macroexpand(u32),
+    LateMacro(u32),
+    Expansion(u32),
+    Call(u16, u32),
+    TailCall(u16, u32),
+    Return,
+    Catch(u32),
+    Finally(u32),
+    PopHandler,
+    Reraise,
+    Raise(u32)
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Op::Const(i) => write!(f, "const {}", i),
+            Op::LoadLocal(slot) => write!(f, "load-local {}", slot),
+            Op::LoadCell(slot) => write!(f, "load-cell {}", slot),
+            Op::LoadDefined(slot, i) => write!(f, "load-defined {} {}", slot, i),
+            Op::LoadUpvalue(i) => write!(f, "load-upvalue {}", i),
+            Op::LoadUpvalueCell(i) => write!(f, "load-upvalue-cell {}", i),
+            Op::LoadGlobal(i) => write!(f, "load-global {}", i),
+            Op::StoreLocal(slot) => write!(f, "store-local {}", slot),
+            Op::NewCell(slot) => write!(f, "new-cell {}", slot),
+            Op::StoreCell(slot) => write!(f, "store-cell {}", slot),
+            Op::Pop => write!(f, "pop"),
+            Op::Jump(target) => write!(f, "jump {}", target),
+            Op::JumpIfFalse(target) => write!(f, "jump-if-false {}", target),
+            Op::Recur(target) => write!(f, "recur {}", target),
+            Op::Vector(n) => write!(f, "vector {}", n),
+            Op::Map(i) => write!(f, "map {}", i),
+            Op::Def(i) => write!(f, "def {}", i),
+            Op::DefLocal(slot, i) => write!(f, "def-local {} {}", slot, i),
+            Op::DefCell(slot, i) => write!(f, "def-cell {} {}", slot, i),
+            Op::DefMacro(i) => write!(f, "defmacro {}", i),
+            Op::Closure(i) => write!(f, "closure {}", i),
+            Op::Macroexpand(i) => write!(f, "macroexpand {}", i),
+            Op::LateMacro(site) => write!(f, "late-macro @{}", site),
+            Op::Expansion(site) => write!(f, "expansion @{}", site),
+            Op::Call(argc, site) => write!(f, "call {} @{}", argc, site),
+            Op::TailCall(argc, site) => write!(f, "tail-call {} @{}", argc, site),
+            Op::Return => write!(f, "return"),
+            Op::Catch(target) => write!(f, "catch {}", target),
+            Op::Finally(target) => write!(f, "finally {}", target),
+            Op::PopHandler => write!(f, "pop-handler"),
+            Op::Reraise => write!(f, "reraise"),
+            Op::Raise(i) => write!(f, "raise {}", i)
+        }
+    }
+}
+
+// zugriff auf einen namen, aufgeloest zur uebersetzungszeit
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Access {
+    Local(u32, bool),
+    Upvalue(u32, bool),
+    Global
+}
+
+// herkunft eines eingefangenen werts im rahmen, der die closure erzeugt
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Source {
+    Local(u32),
+    Upvalue(u32)
+}
+
+struct Capture {
+    name: SymbolId,
+    source: Source
+}
+
+// aufrufstelle; bei einem aufruf eines noch unbekannten namens und einer expansion auch die zur
+// auswertung durch den evaluator noetigen angaben
+struct Site {
+    form: MalData,
+    late: Option<LateSite>,
+    expansion: Option<Rc<Expansion>>
+}
+
+struct LateSite {
+    // fortsetzung nach dem aufruf
+    end: u32,
+
+    // die an der aufrufstelle sichtbaren lokalen namen
+    scope: Vec<( SymbolId, Access )>,
+
+    cache: LateExpansion
+}
+
+// einsprungstelle einer arity
+struct Entry {
+    binds: Vec<Symbol>,
+    required: usize,
+    variadic: bool,
+    start: u32
+}
+
+// uebersetzte funktion (bzw. form auf oberster ebene); die arities teilen sich code, slots und
+// eingefangene werte
+pub struct Proto {
+    name: Option<Rc<str>>,
+    entries: Vec<Entry>,
+    slots: usize,
+    code: Vec<Op>,
+    consts: Vec<MalData>,
+    names: Vec<SymbolId>,
+    protos: Vec<Rc<Proto>>,
+    captures: Vec<Capture>,
+    sites: Vec<Site>,
+    map_keys: Vec<Vec<MapKey>>,
+    slot_names: Vec<String>,
+
+    // fehler fuer raise, siehe Node::Raise
+    errors: Vec<EvalError>,
+    form: MalData
+}
+
+impl Proto {
+    fn new(name: Option<Rc<str>>, form: &MalData) -> Proto {
+        Proto {
+            name, entries: Vec::new(), slots: 0,
+            code: Vec::new(), consts: Vec::new(), names: Vec::new(), protos: Vec::new(),
+            captures: Vec::new(), sites: Vec::new(), map_keys: Vec::new(), slot_names: Vec::new(),
+            errors: Vec::new(), form: form.clone()
+        }
+    }
+
+    pub fn name(&self) -> Option<Rc<str>> {
+        self.name.clone()
+    }
+
+    pub fn binds(&self) -> Vec<&[Symbol]> {
+        self.entries.iter().map( |entry| &entry.binds[..] ).collect()
+    }
+
+    pub fn form(&self) -> &MalData {
+        &self.form
+    }
+
+    // die arity fuer `argc` argumente
+    fn entry(&self, argc: usize) -> Result<&Entry, CoreError> {
+        let shapes = self.entries.iter().map( |entry| ( entry.required, entry.variadic ) );
+
+        match select_arity(shapes.clone(), argc) {
+            Some(i) => Ok(&self.entries[i]),
+            None => Err(CoreError::arities("fn*", shapes, argc))
+        }
+    }
+}
+
+
+// uebersetzung
+
+// uebersetzt eine analysierte form auf oberster ebene in eine funktion ohne parameter
+pub fn compile(env: &EnvType, node: &Node, form: &MalData) -> Result<Proto, EvalError> {
+    let mut compiler = Compiler { env: env.clone(), fns: Vec::new() };
+
+    compiler.begin_fn(None, form);
+    compiler.begin_arity(&[], &[], false);
+    compiler.compile(node, true)?;
+
+    Ok(compiler.end_fn())
+}
+
+pub fn compile_lambda(env: &EnvType, lambda: &Lambda) -> Result<Proto, EvalError> {
+    Compiler { env: env.clone(), fns: Vec::new() }.compile_fn(lambda)
+}
+
+struct Local {
+    name: SymbolId,
+    slot: u32,
+
+    // in einer zelle, weil eine closure den namen vor seiner bindung einfaengt
+    boxed: bool,
+
+    // let*-name, dessen wert gerade berechnet wird; direkt bezeichnet er noch den aeusseren namen
+    pending: bool,
+
+    // vorab angelegter name eines spaeteren def!; die zelle ist bis dahin leer (MalData::Nothing)
+    defined: bool
+}
+
+struct FnBuilder {
+    proto: Proto,
+    locals: Vec<Local>,
+    next_slot: u32,
+
+    // anfang der namen des innersten bereichs in `locals`, in dem ein def! bindet
+    scope: usize,
+
+    // die den gerade uebersetzten code umgebenden loop*, der innerste zuletzt
+    loops: Vec<LoopTarget>
+}
+
+// anfang des rumpfs eines loop* und die slots seiner bindungen (slot, in einer zelle)
+#[derive(Clone)]
+struct LoopTarget {
+    start: u32,
+    slots: Vec<( u32, bool )>
+}
+
+struct Compiler {
+    env: EnvType,
+
+    // die gerade uebersetzten, ineinander verschachtelten funktionen
+    fns: Vec<FnBuilder>
+}
+
+impl Compiler {
+    fn current(&mut self) -> &mut FnBuilder {
+        self.fns.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        let code = &mut self.current().proto.code;
+        code.push(op);
+        code.len() - 1
+    }
+
+    fn here(&mut self) -> u32 {
+        self.current().proto.code.len() as u32
+    }
+
+    // setzt das sprungziel der anweisung `at` auf die aktuelle position
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+
+        match self.current().proto.code[at] {
+            Op::Jump(ref mut t) | Op::JumpIfFalse(ref mut t) | Op::Catch(ref mut t) | Op::Finally(ref mut t) => *t = target,
+            op => panic!("patch: {} is not a jump", op)
+        }
+    }
+
+    fn constant(&mut self, value: MalData) -> u32 {
+        let consts = &mut self.current().proto.consts;
+        consts.push(value);
+        (consts.len() - 1) as u32
+    }
+
+    fn name(&mut self, name: SymbolId) -> u32 {
+        let names = &mut self.current().proto.names;
+
+        match names.iter().position( |&n| n == name ) {
+            Some(i) => i as u32,
+            None => {
+                names.push(name);
+                (names.len() - 1) as u32
+            }
+        }
+    }
+
+    fn declare(&mut self, name: SymbolId, boxed: bool, pending: bool) -> u32 {
+        let slot = self.reserve_slot();
+        let builder = self.current();
+
+        let text = name.name();
+        let slot_name = &mut builder.proto.slot_names[slot as usize];
+
+        if slot_name.is_empty() {
+            slot_name.push_str(&text);
+        } else if !slot_name.split('/').any( |n| n == &*text ) {
+            slot_name.push('/');
+            slot_name.push_str(&text);
+        }
+
+        builder.locals.push(Local { name, slot, boxed, pending, defined: false });
+        slot
+    }
+
+    // slot ohne namen, z.b. fuer zwischenergebnisse
+    fn reserve_slot(&mut self) -> u32 {
+        let builder = self.current();
+        let slot = builder.next_slot;
+
+        builder.next_slot += 1;
+
+        if builder.proto.slots < builder.next_slot as usize {
+            builder.proto.slots = builder.next_slot as usize;
+            builder.proto.slot_names.push(String::new());
+        }
+
+        slot
+    }
+
+    // gibt die seit `locals`/`next_slot` angelegten namen und slots wieder frei
+    fn release(&mut self, locals: usize, next_slot: u32) {
+        let builder = self.current();
+
+        builder.locals.truncate(locals);
+        builder.next_slot = next_slot;
+    }
+
+    fn begin_fn(&mut self, name: Option<Rc<str>>, form: &MalData) {
+        self.fns.push(FnBuilder { proto: Proto::new(name, form), locals: Vec::new(), next_slot: 0, scope: 0, loops: Vec::new() });
+    }
+
+    // die arity beginnt an der aktuellen position mit den parametern in den ersten slots
+    fn begin_arity(&mut self, binds: &[Symbol], params: &[SymbolId], variadic: bool) {
+        self.release(0, 0);
+        self.current().scope = 0;
+
+        let start = self.here();
+        let required = if variadic { params.len() - 1 } else { params.len() };
+        self.current().proto.entries.push(Entry { binds: binds.to_vec(), required, variadic, start });
+
+        for param in params {
+            self.declare(*param, false, false);
+        }
+    }
+
+    fn end_fn(&mut self) -> Proto {
+        self.fns.pop().unwrap().proto
+    }
+
+    fn compile_fn(&mut self, lambda: &Lambda) -> Result<Proto, EvalError> {
+        self.begin_fn(lambda.name.clone(), &lambda.arities[0].form);
+
+        let res = lambda.arities.iter().try_for_each( |arity| {
+            self.begin_arity(&arity.binds, &arity.params, arity.variadic);
+            self.begin_scope(&rebound(&[&arity.body]))?;
+            self.compile(&arity.body, true)
+        });
+
+        let proto = self.end_fn();
+        res.map( |()| proto )
+    }
+
+    // die im bereich ab `scope` von einem def! neu gebundenen namen `rebound` liegen in zellen: ein
+    // schon gebundener name wird in eine zelle umgelagert, ein noch unbekannter vorab angelegt
+    fn begin_scope(&mut self, rebound: &[SymbolId]) -> Result<(), EvalError> {
+        for &name in rebound {
+            let scope = self.current().scope;
+            let found = self.current().locals[scope..].iter_mut().rev().find( |local| local.name == name );
+
+            match found {
+                Some(local) if local.boxed =>
+                    (),
+
+                Some(local) => {
+                    local.boxed = true;
+                    let slot = local.slot;
+
+                    self.emit(Op::LoadLocal(slot));
+                    self.emit(Op::NewCell(slot));
+                    self.emit(Op::StoreCell(slot));
+                }
+
+                // ein aeusserer lokaler name wird mit seinem wert uebernommen, sonst bleibt die zelle
+                // leer und verweist auf den globalen namen
+                None => {
+                    let outer = self.resolve_here(name);
+                    let slot = self.declare(name, true, true);
+
+                    if slot > u16::MAX as u32 {
+                        return Err(EvalError::General(format!("too many local bindings: {}", name)));
+                    }
+
+                    self.current().locals.last_mut().unwrap().defined = true;
+                    self.emit(Op::NewCell(slot));
+
+                    match outer {
+                        Access::Global => {
+                            let empty = self.constant(MalData::Nothing);
+                            self.emit(Op::Const(empty));
+                        }
+
+                        access =>
+                            self.load(name, access)
+                    }
+
+                    self.emit(Op::StoreCell(slot));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // sucht `name` in der funktion auf ebene `level` und den umgebenden; ein von einer inneren
+    // funktion benutzter name wird in jede dazwischen liegende funktion eingefangen
+    fn resolve(&mut self, level: usize, name: SymbolId, see_pending: bool, nested_see_pending: bool) -> Access {
+        let found = self.fns[level].locals.iter().rev()
+            .find( |local| local.name == name && (see_pending || !local.pending) )
+            .map( |local| Access::Local(local.slot, local.boxed) );
+
+        if let Some(access) = found {
+            return access;
+        }
+
+        if level == 0 {
+            return Access::Global;
+        }
+
+        let ( source, boxed ) = match self.resolve(level - 1, name, nested_see_pending, nested_see_pending) {
+            Access::Global => return Access::Global,
+            Access::Local(slot, boxed) => ( Source::Local(slot), boxed ),
+            Access::Upvalue(i, boxed) => ( Source::Upvalue(i), boxed )
+        };
+
+        let captures = &mut self.fns[level].proto.captures;
+
+        let index = match captures.iter().position( |capture| capture.source == source ) {
+            Some(i) => i,
+            None => {
+                captures.push(Capture { name, source });
+                captures.len() - 1
+            }
+        };
+
+        Access::Upvalue(index as u32, boxed)
+    }
+
+    fn resolve_here(&mut self, name: SymbolId) -> Access {
+        let level = self.fns.len() - 1;
+        self.resolve(level, name, false, true)
+    }
+
+    fn load(&mut self, name: SymbolId, access: Access) {
+        let op = match access {
+            Access::Local(slot, false) => Op::LoadLocal(slot),
+            Access::Local(slot, true) if self.is_defined(slot) => Op::LoadDefined(slot as u16, self.name(name)),
+            Access::Local(slot, true) => Op::LoadCell(slot),
+            Access::Upvalue(i, false) => Op::LoadUpvalue(i),
+            Access::Upvalue(i, true) => Op::LoadUpvalueCell(i),
+            Access::Global => Op::LoadGlobal(self.name(name))
+        };
+
+        self.emit(op);
+    }
+
+    // der eintrag eines namens des innersten bereichs, der in einer zelle liegt
+    fn cell_in_scope(&mut self, name: SymbolId) -> Option<usize> {
+        let builder = self.current();
+        builder.locals[builder.scope..].iter().rposition( |local| local.name == name && local.boxed ).map( |i| builder.scope + i )
+    }
+
+    fn is_defined(&mut self, slot: u32) -> bool {
+        self.current().locals.iter().any( |local| local.slot == slot && local.defined )
+    }
+
+    fn is_local(&self, name: SymbolId) -> bool {
+        self.fns.iter().any( |builder| builder.locals.iter().any( |local| local.name == name ) )
+    }
+
+    fn compile(&mut self, node: &Node, tail: bool) -> Result<(), EvalError> {
+        match *node {
+            Node::Const(ref value) => {
+                let i = self.constant(value.clone());
+                self.emit(Op::Const(i));
+            }
+
+            Node::Symbol(ref var) => {
+                let access = self.resolve_here(var.id());
+                self.load(var.id(), access);
+            }
+
+            Node::Vector(ref nodes) => {
+                for node in nodes {
+                    self.compile(node, false)?;
+                }
+
+                self.emit(Op::Vector(nodes.len() as u32));
+            }
+
+            Node::Map(ref entries) => {
+                for ( _, value ) in entries {
+                    self.compile(value, false)?;
+                }
+
+                let map_keys = &mut self.current().proto.map_keys;
+                map_keys.push(entries.iter().map( |( key, _ )| key.clone() ).collect());
+
+                let i = (map_keys.len() - 1) as u32;
+                self.emit(Op::Map(i));
+            }
+
+            // ein name des bereichs in einer zelle erhaelt den neuen wert in ihr
+            Node::Def(Var::Local(_, _, name), ref value) if self.cell_in_scope(name).is_some() => {
+                let local = self.cell_in_scope(name).unwrap();
+                self.compile(value, false)?;
+
+                let i = self.name(name);
+                let slot = self.current().locals[local].slot;
+
+                self.emit(Op::DefCell(slot as u16, i));
+                self.current().locals[local].pending = false;
+            }
+
+            Node::Def(Var::Local(_, _, name), ref value) => {
+                let local = self.current().locals.len();
+                let slot = self.declare(name, false, true);
+
+                if slot > u16::MAX as u32 {
+                    return Err(EvalError::General(format!("too many local bindings: {}", name)));
+                }
+
+                self.compile(value, false)?;
+
+                let i = self.name(name);
+                self.emit(Op::DefLocal(slot as u16, i));
+                self.current().locals[local].pending = false;
+            }
+
+            Node::Def(ref var, ref value) => {
+                self.compile(value, false)?;
+
+                let i = self.name(var.id());
+                self.emit(Op::Def(i));
+            }
+
+            Node::DefMacro(ref var, ref value) => {
+                self.compile(value, false)?;
+
+                let i = self.name(var.id());
+                self.emit(Op::DefMacro(i));
+            }
+
+            Node::Let(ref bindings, ref body) =>
+                return self.compile_let(bindings, body, tail),
+
+            Node::Loop(ref lp) =>
+                return self.compile_loop(lp, tail),
+
+            Node::Recur(ref recur) =>
+                return self.compile_recur(recur),
+
+            Node::Do(ref forms, ref last) => {
+                for form in forms {
+                    self.compile(form, false)?;
+                    self.emit(Op::Pop);
+                }
+
+                return self.compile(last, tail);
+            }
+
+            Node::If(ref cond, ref then_node, ref else_node) =>
+                return self.compile_if(cond, then_node, else_node.as_ref(), tail),
+
+            Node::Fn(ref lambda) => {
+                let proto = self.compile_fn(lambda)?;
+
+                let protos = &mut self.current().proto.protos;
+                protos.push(Rc::new(proto));
+
+                let i = (protos.len() - 1) as u32;
+                self.emit(Op::Closure(i));
+            }
+
+            Node::Macroexpand(ref form) => {
+                let i = self.constant(form.clone());
+                self.emit(Op::Macroexpand(i));
+            }
+
+            Node::Try(ref try_node) =>
+                return self.compile_try(try_node, tail),
+
+            Node::Call(ref call) =>
+                return self.compile_call(call, tail),
+
+            Node::Expansion(ref expansion) =>
+                return self.compile_expansion(expansion, tail),
+
+            Node::Raise(ref err) => {
+                let errors = &mut self.current().proto.errors;
+                errors.push(err.clone());
+
+                let i = (errors.len() - 1) as u32;
+                self.emit(Op::Raise(i));
+                return Ok(());
+            }
+        }
+
+        if tail {
+            self.emit(Op::Return);
+        }
+
+        Ok(())
+    }
+
+    fn compile_let(&mut self, bindings: &[( Var, Rc<Node> )], body: &Node, tail: bool) -> Result<(), EvalError> {
+        let ( outer_locals, outer_slot, outer_scope ) = ( self.current().locals.len(), self.current().next_slot, self.current().scope );
+
+        self.compile_bindings(bindings, body)?;
+        self.compile(body, tail)?;
+        self.release(outer_locals, outer_slot);
+        self.current().scope = outer_scope;
+
+        Ok(())
+    }
+
+    // der rumpf folgt auf die bindungen; recur bindet die slots neu und springt zu ihm zurueck
+    fn compile_loop(&mut self, lp: &Loop, tail: bool) -> Result<(), EvalError> {
+        let ( outer_locals, outer_slot, outer_scope ) = ( self.current().locals.len(), self.current().next_slot, self.current().scope );
+
+        self.compile_bindings(&lp.bindings, &lp.body)?;
+
+        let start = self.here();
+        let slots = self.current().locals[outer_locals..outer_locals + lp.bindings.len()].iter().map( |local| ( local.slot, local.boxed ) ).collect();
+
+        self.current().loops.push(LoopTarget { start, slots });
+        let res = self.compile(&lp.body, tail);
+        self.current().loops.pop();
+
+        self.release(outer_locals, outer_slot);
+        self.current().scope = outer_scope;
+
+        res
+    }
+
+    // ein name in einer zelle erhaelt fuer jeden durchlauf eine neue, damit die closures eines
+    // frueheren durchlaufs ihren wert behalten
+    fn compile_recur(&mut self, recur: &Recur) -> Result<(), EvalError> {
+        let target = self.current().loops.last().cloned().ok_or("recur outside of loop*")?;
+
+        for arg in &recur.args {
+            self.compile(arg, false)?;
+        }
+
+        for &( slot, boxed ) in target.slots.iter().rev() {
+            if boxed {
+                self.emit(Op::NewCell(slot));
+                self.emit(Op::StoreCell(slot));
+            } else {
+                self.emit(Op::StoreLocal(slot));
+            }
+        }
+
+        self.emit(Op::Recur(target.start));
+
+        Ok(())
+    }
+
+    // legt die namen von let* und loop* an und bindet sie der reihe nach
+    fn compile_bindings(&mut self, bindings: &[( Var, Rc<Node> )], body: &Node) -> Result<(), EvalError> {
+        let outer_locals = self.current().locals.len();
+        self.current().scope = outer_locals;
+
+        let nodes: Vec<&Node> = bindings.iter().map( |( _, value )| &**value ).chain(Some(body)).collect();
+        let rebound = rebound(&nodes);
+
+        // alle namen gelten ab beginn des let* fuer die darin erzeugten closures
+        for ( i, ( var, _ ) ) in bindings.iter().enumerate() {
+            let boxed = rebound.contains(&var.id()) || bindings[..=i].iter().any( |( _, value )| captures(value, var.id()) );
+            let slot = self.declare(var.id(), boxed, true);
+
+            if boxed {
+                self.emit(Op::NewCell(slot));
+            }
+        }
+
+        self.begin_scope(&rebound)?;
+
+        for ( i, ( _, value ) ) in bindings.iter().enumerate() {
+            self.compile(value, false)?;
+
+            let local = &mut self.current().locals[outer_locals + i];
+            local.pending = false;
+
+            let op = if local.boxed { Op::StoreCell(local.slot) } else { Op::StoreLocal(local.slot) };
+            self.emit(op);
+        }
+
+        Ok(())
+    }
+
+    fn compile_if(&mut self, cond: &Node, then_node: &Node, else_node: Option<&Rc<Node>>, tail: bool) -> Result<(), EvalError> {
+        self.compile(cond, false)?;
+        let to_else = self.emit(Op::JumpIfFalse(0));
+
+        self.compile(then_node, tail)?;
+        let to_end = if tail { None } else { Some(self.emit(Op::Jump(0))) };
+
+        self.patch(to_else);
+
+        match else_node {
+            Some(else_node) => self.compile(else_node, tail)?,
+            None => self.compile(&Node::Const(MalData::Nil), tail)?
+        }
+
+        if let Some(to_end) = to_end {
+            self.patch(to_end);
+        }
+
+        Ok(())
+    }
+
+    // der rumpf steht nie in endposition, da waehrend seiner auswertung ein handler aktiv ist;
+    // ohne finally* steht der catch*-rumpf in endposition
+    fn compile_try(&mut self, try_node: &Try, tail: bool) -> Result<(), EvalError> {
+        let ( outer_locals, outer_slot ) = ( self.current().locals.len(), self.current().next_slot );
+
+        let cleanup = match try_node.finally {
+            None if try_node.catch.is_none() =>
+                return self.compile(&try_node.body, tail),
+
+            None =>
+                return self.compile_try_catch(try_node, tail),
+
+            Some(ref cleanup) =>
+                cleanup
+        };
+
+        let on_error = self.emit(Op::Finally(0));
+
+        if try_node.catch.is_some() {
+            self.compile_try_catch(try_node, false)?;
+        } else {
+            self.compile(&try_node.body, false)?;
+        }
+
+        // ohne fehler: cleanup ausfuehren, das ergebnis bleibt erhalten
+        self.emit(Op::PopHandler);
+
+        let result = self.reserve_slot();
+        self.emit(Op::StoreLocal(result));
+        self.compile(cleanup, false)?;
+        self.emit(Op::Pop);
+        self.emit(Op::LoadLocal(result));
+
+        let to_end = if tail { self.emit(Op::Return); None } else { Some(self.emit(Op::Jump(0))) };
+
+        // mit fehler: cleanup ausfuehren, dann den fehler weiterreichen
+        self.patch(on_error);
+        self.compile(cleanup, false)?;
+        self.emit(Op::Pop);
+        self.emit(Op::Reraise);
+
+        if let Some(to_end) = to_end {
+            self.patch(to_end);
+        }
+
+        self.release(outer_locals, outer_slot);
+
+        Ok(())
+    }
+
+    fn compile_try_catch(&mut self, try_node: &Try, tail: bool) -> Result<(), EvalError> {
+        let ( catch_bind, handler ) = try_node.catch.as_ref().unwrap();
+
+        let on_error = self.emit(Op::Catch(0));
+        self.compile(&try_node.body, false)?;
+        self.emit(Op::PopHandler);
+
+        let to_end = if tail { self.emit(Op::Return); None } else { Some(self.emit(Op::Jump(0))) };
+
+        // der handler findet die exception oben auf dem stapel
+        self.patch(on_error);
+
+        let ( outer_locals, outer_slot, outer_scope ) = ( self.current().locals.len(), self.current().next_slot, self.current().scope );
+        let slot = self.declare(catch_bind.id(), false, false);
+
+        self.emit(Op::StoreLocal(slot));
+        self.current().scope = outer_locals;
+        self.begin_scope(&rebound(&[handler]))?;
+
+        self.compile(handler, tail)?;
+        self.release(outer_locals, outer_slot);
+        self.current().scope = outer_scope;
+
+        if let Some(to_end) = to_end {
+            self.patch(to_end);
+        }
+
+        Ok(())
+    }
+
+    fn compile_call(&mut self, call: &Call, tail: bool) -> Result<(), EvalError> {
+        if call.args.len() > u16::MAX as usize {
+            return Err(EvalError::General(format!("too many arguments: {}", call.args.len())));
+        }
+
+        self.compile(&call.fun, false)?;
+
+        // ein noch nicht definierter name koennte spaeter als makro definiert werden
+        let late = match *call.fun {
+            Node::Symbol(ref var) => !self.is_local(var.id()) && Env::get_id(&self.env, var.id()).is_none(),
+            _ => false
+        };
+
+        let scope = if late { Some(self.visible_scope()) } else { None };
+
+        let sites = &mut self.current().proto.sites;
+        sites.push(Site { form: call.form.clone(), late: None, expansion: None });
+        let site = (sites.len() - 1) as u32;
+
+        if late {
+            self.emit(Op::LateMacro(site));
+        }
+
+        for arg in &call.args {
+            self.compile(arg, false)?;
+        }
+
+        let argc = call.args.len() as u16;
+
+        // auf TailCall folgt immer ein Return, fuer die faelle, in denen der aufruf den rahmen
+        // nicht ersetzt (native funktionen, expandierte makros)
+        let end = if tail {
+            self.emit(Op::TailCall(argc, site));
+            let end = self.here();
+            self.emit(Op::Return);
+            end
+        } else {
+            self.emit(Op::Call(argc, site));
+            self.here()
+        };
+
+        if let Some(scope) = scope {
+            self.current().proto.sites[site as usize].late = Some(LateSite { end, scope, cache: LateExpansion::default() });
+        }
+
+        Ok(())
+    }
+
+    // die expansion wird an ort und stelle uebersetzt; Op::Expansion springt ueber sie hinweg, wenn
+    // sie nicht mehr gilt
+    fn compile_expansion(&mut self, expansion: &Rc<Expansion>, tail: bool) -> Result<(), EvalError> {
+        let scope = self.visible_scope();
+
+        let sites = &mut self.current().proto.sites;
+        sites.push(Site { form: expansion.form.clone(), late: None, expansion: Some(expansion.clone()) });
+        let site = (sites.len() - 1) as u32;
+
+        self.emit(Op::Expansion(site));
+        self.compile(&expansion.node, tail)?;
+
+        let end = self.here();
+
+        if tail {
+            self.emit(Op::Return);
+        }
+
+        self.current().proto.sites[site as usize].late = Some(LateSite { end, scope, cache: LateExpansion::default() });
+
+        Ok(())
+    }
+
+    // alle an dieser stelle sichtbaren, bereits gebundenen lokalen namen
+    fn visible_scope(&mut self) -> Vec<( SymbolId, Access )> {
+        let mut names: Vec<SymbolId> = Vec::new();
+
+        for builder in self.fns.iter().rev() {
+            for local in builder.locals.iter().rev() {
+                if !names.contains(&local.name) {
+                    names.push(local.name);
+                }
+            }
+        }
+
+        let level = self.fns.len() - 1;
+
+        names.into_iter()
+            .filter_map( |name| match self.resolve(level, name, false, false) {
+                Access::Global => None,
+                access => Some(( name, access ))
+            })
+            .collect()
+    }
+}
+
+// die von einem def! in `nodes` gebundenen namen, die eine darin erzeugte closure benutzt
+fn rebound(nodes: &[&Node]) -> Vec<SymbolId> {
+    let mut names = Vec::new();
+
+    for node in nodes {
+        defined(node, &mut names);
+    }
+
+    names.retain( |&name| nodes.iter().any( |node| captures(node, name) ) );
+    names
+}
+
+// sammelt die namen, die ein def! in `node` im umgebenden bereich bindet; let*, loop*, fn* und
+// catch* bilden eigene bereiche
+fn defined(node: &Node, names: &mut Vec<SymbolId>) {
+    match *node {
+        Node::Def(ref var, ref value) | Node::DefMacro(ref var, ref value) => {
+            if let Var::Local(_, _, name) = *var {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+
+            defined(value, names);
+        }
+
+        Node::Const(_) | Node::Symbol(_) | Node::Let(..) | Node::Loop(_) | Node::Fn(_) | Node::Macroexpand(_) | Node::Raise(_) =>
+            (),
+
+        Node::Vector(ref nodes) | Node::Recur(Recur { args: ref nodes, .. }) =>
+            nodes.iter().for_each( |n| defined(n, names) ),
+
+        Node::Map(ref entries) =>
+            entries.iter().for_each( |( _, n )| defined(n, names) ),
+
+        Node::Do(ref forms, ref last) => {
+            forms.iter().for_each( |n| defined(n, names) );
+            defined(last, names);
+        }
+
+        Node::If(ref cond, ref then_node, ref else_node) => {
+            defined(cond, names);
+            defined(then_node, names);
+            else_node.iter().for_each( |n| defined(n, names) );
+        }
+
+        Node::Expansion(ref expansion) =>
+            defined(&expansion.node, names),
+
+        Node::Try(ref try_node) => {
+            defined(&try_node.body, names);
+            try_node.finally.iter().for_each( |n| defined(n, names) );
+        }
+
+        Node::Call(ref call) => {
+            defined(&call.fun, names);
+            call.args.iter().for_each( |n| defined(n, names) );
+        }
+    }
+}
+
+// true, falls eine in `node` erzeugte closure den namen `name` benutzt
+fn captures(node: &Node, name: SymbolId) -> bool {
+    any_node(node, &mut |n| match *n {
+        Node::Fn(ref lambda) => lambda.arities.iter().any( |arity| any_node(&arity.body, &mut |n| match *n {
+            Node::Symbol(ref var) => var.id() == name,
+            _ => false
+        })),
+
+        _ => false
+    })
+}
+
+fn any_node<F: FnMut(&Node) -> bool>(node: &Node, pred: &mut F) -> bool {
+    if pred(node) {
+        return true;
+    }
+
+    match *node {
+        Node::Const(_) | Node::Symbol(_) | Node::Macroexpand(_) | Node::Raise(_) =>
+            false,
+
+        Node::Vector(ref nodes) =>
+            nodes.iter().any( |n| any_node(n, pred) ),
+
+        Node::Map(ref entries) =>
+            entries.iter().any( |( _, n )| any_node(n, pred) ),
+
+        Node::Def(_, ref value) | Node::DefMacro(_, ref value) =>
+            any_node(value, pred),
+
+        Node::Let(ref bindings, ref body) =>
+            bindings.iter().any( |( _, n )| any_node(n, pred) ) || any_node(body, pred),
+
+        Node::Loop(ref lp) =>
+            lp.bindings.iter().any( |( _, n )| any_node(n, pred) ) || any_node(&lp.body, pred),
+
+        Node::Recur(ref recur) =>
+            recur.args.iter().any( |n| any_node(n, pred) ),
+
+        Node::Do(ref forms, ref last) =>
+            forms.iter().any( |n| any_node(n, pred) ) || any_node(last, pred),
+
+        Node::If(ref cond, ref then_node, ref else_node) =>
+            any_node(cond, pred) || any_node(then_node, pred) || else_node.as_ref().is_some_and( |n| any_node(n, pred) ),
+
+        Node::Fn(ref lambda) =>
+            lambda.arities.iter().any( |arity| any_node(&arity.body, pred) ),
+
+        Node::Expansion(ref expansion) =>
+            any_node(&expansion.node, pred),
+
+        Node::Try(ref try_node) =>
+            any_node(&try_node.body, pred)
+                || try_node.catch.as_ref().is_some_and( |( _, n )| any_node(n, pred) )
+                || try_node.finally.as_ref().is_some_and( |n| any_node(n, pred) ),
+
+        Node::Call(ref call) =>
+            any_node(&call.fun, pred) || call.args.iter().any( |n| any_node(n, pred) )
+    }
+}
+
+
+// ausfuehrung
+
+// wertet `ast` in `env` mit der vm aus; ein do auf oberster ebene wird wie im evaluator form fuer
+// form im jeweils aktuellen namensraum uebersetzt
+pub fn eval(env: EnvType, ast: &MalData) -> MalEvalResult {
+    if let Some(forms) = clause_named(ast, "do") {
+        let mut res = MalData::Nil;
+
+        for form in forms {
+            res = eval(namespace::current(&env), form)?;
+        }
+
+        return Ok(res);
+    }
+
+    let proto = analyze(&env, ast)
+        .and_then( |node| compile(&env, &node, ast) )
+        .map_err( |err| err.with_stack(capture_call_stack()) )?;
+
+    let mut vm = Vm::new();
+
+    vm.stack.push(MalData::Nil);
+    let call_depth = vm.call_depth;
+    vm.enter(Rc::new(proto), Rc::new(Vec::new()), env, None, 0, call_depth)?;
+    vm.run()
+}
+
+// ruft eine von der vm uebersetzte closure auf
+pub fn call(closure: &FnClosure, args: &[MalData]) -> MalEvalResult {
+    let ( proto, upvalues ) = match closure.code {
+        FnCode::Bytecode(ref proto, ref upvalues) => ( proto.clone(), upvalues.clone() ),
+        FnCode::Tree(_) => return eval::apply(closure.outer_env.clone(), &MalData::FnClosure(closure.clone()), args)
+    };
+
+    let mut vm = Vm::new();
+
+    vm.stack.push(MalData::Nil);
+    vm.stack.extend_from_slice(args);
+    let call_depth = vm.call_depth;
+    vm.enter(proto, upvalues, closure.outer_env.clone(), closure.name.as_deref(), args.len(), call_depth)?;
+    vm.run()
+}
+
+struct Frame {
+    proto: Rc<Proto>,
+    upvalues: Rc<Vec<MalData>>,
+    env: EnvType,
+    pc: usize,
+
+    // erster slot; darunter liegt die aufgerufene funktion
+    base: usize,
+
+    // ebene des eintrags im aufrufstapel (eval::enter_call_frame)
+    call_depth: usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HandlerKind {
+    Catch,
+    Finally
+}
+
+// aktiver try*-handler mit dem zustand, auf den bei einem fehler zurueckgesetzt wird
+struct Handler {
+    kind: HandlerKind,
+    target: usize,
+    frames: usize,
+    stack_len: usize,
+    call_depth: usize,
+    pending: usize
+}
+
+struct Vm {
+    stack: Vec<MalData>,
+    frames: Vec<Frame>,
+    handlers: Vec<Handler>,
+
+    // fehler, die nach ihrem finally* weitergereicht werden
+    pending: Vec<EvalError>,
+
+    call_depth: usize
+}
+
+impl Vm {
+    fn new() -> Vm {
+        Vm { stack: Vec::new(), frames: Vec::new(), handlers: Vec::new(), pending: Vec::new(), call_depth: call_stack_depth() }
+    }
+
+    fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    // bindet die `argc` argumente oben auf dem stapel an die parameter der passenden arity und legt
+    // die uebrigen slots an; liefert den ersten slot und den anfang des codes der arity
+    fn bind_args(&mut self, proto: &Proto, name: Option<&str>, argc: usize) -> Result<( usize, usize ), EvalError> {
+        let base = self.stack.len() - argc;
+
+        let entry = match proto.entry(argc) {
+            Ok(entry) => entry,
+            Err(err) => return Err(name.map_or(err.clone(), |name| err.in_fun(name)).into())
+        };
+
+        if entry.variadic {
+            let rest = if argc > entry.required {
+                MalData::List(Rc::from(self.stack.split_off(base + entry.required)), None)
+            } else {
+                MalData::Nil
+            };
+
+            self.stack.push(rest);
+        }
+
+        self.stack.resize(base + proto.slots, MalData::Nil);
+
+        Ok(( base, entry.start as usize ))
+    }
+
+    fn enter(&mut self, proto: Rc<Proto>, upvalues: Rc<Vec<MalData>>, env: EnvType, name: Option<&str>, argc: usize, call_depth: usize) -> Result<(), EvalError> {
+        let ( base, pc ) = self.bind_args(&proto, name, argc)?;
+        limits::enter()?;
+
+        self.frames.push(Frame { proto, upvalues, env, pc, base, call_depth });
+
+        Ok(())
+    }
+
+    fn run(&mut self) -> MalEvalResult {
+        loop {
+            let err = match self.execute() {
+                Ok(value) => return Ok(value),
+                Err(err) => err.with_stack(capture_call_stack())
+            };
+
+            self.unwind(err)?;
+        }
+    }
+
+    // setzt auf den innersten passenden handler zurueck oder beendet die ausfuehrung mit dem fehler
+    fn unwind(&mut self, err: EvalError) -> Result<(), EvalError> {
+        while let Some(handler) = self.handlers.pop() {
+            if handler.kind == HandlerKind::Catch && !err.is_catchable() {
+                continue;
+            }
+
+            while self.frames.len() > handler.frames {
+                self.frames.pop();
+                limits::leave();
+            }
+
+            leave_call_frames(handler.call_depth);
+            self.stack.truncate(handler.stack_len);
+            self.pending.truncate(handler.pending);
+            self.frames.last_mut().unwrap().pc = handler.target;
+
+            match handler.kind {
+                HandlerKind::Catch => {
+                    set_caught_call_stack(err.stack().cloned());
+                    self.stack.push(err.to_mal_value());
+                }
+
+                HandlerKind::Finally =>
+                    self.pending.push(err)
+            }
+
+            return Ok(());
+        }
+
+        while self.frames.pop().is_some() {
+            limits::leave();
+        }
+
+        leave_call_frames(self.call_depth);
+
+        Err(err)
+    }
+
+    fn push_handler(&mut self, kind: HandlerKind, target: u32) {
+        let handler = Handler {
+            kind, target: target as usize, frames: self.frames.len(), stack_len: self.stack.len(),
+            call_depth: call_stack_depth(), pending: self.pending.len()
+        };
+
+        self.handlers.push(handler);
+    }
+
+    fn global(&self, name: SymbolId) -> MalEvalResult {
+        Env::get_id(&self.frame().env, name).ok_or_else( || EvalError::General(format!("'{}' not found", name)) )
+    }
+
+    // der inhalt einer zelle ist MalData::Nothing, solange ihr def! noch aussteht
+    fn load(&self, access: Access) -> MalData {
+        let frame = self.frame();
+
+        let value = match access {
+            Access::Local(slot, _) => &self.stack[frame.base + slot as usize],
+            Access::Upvalue(i, _) => &frame.upvalues[i as usize],
+            Access::Global => unreachable!()
+        };
+
+        match ( access, value ) {
+            ( Access::Local(_, true), &MalData::Atom(ref cell) ) | ( Access::Upvalue(_, true), &MalData::Atom(ref cell) ) =>
+                cell.borrow().clone(),
+
+            _ =>
+                value.clone()
+        }
+    }
+
+    // fuehrt befehle aus, bis der unterste rahmen zurueckkehrt
+    fn execute(&mut self) -> MalEvalResult {
+        loop {
+            let op = {
+                let frame = self.frames.last_mut().unwrap();
+                let op = frame.proto.code[frame.pc];
+
+                frame.pc += 1;
+                op
+            };
+
+            match op {
+                Op::Const(i) => {
+                    let value = self.frame().proto.consts[i as usize].clone();
+                    self.stack.push(value);
+                }
+
+                Op::LoadLocal(slot) => {
+                    let value = self.load(Access::Local(slot, false));
+                    self.stack.push(value);
+                }
+
+                Op::LoadCell(slot) => {
+                    let value = self.load(Access::Local(slot, true));
+                    self.stack.push(value);
+                }
+
+                Op::LoadUpvalue(i) => {
+                    let value = self.load(Access::Upvalue(i, false));
+                    self.stack.push(value);
+                }
+
+                Op::LoadDefined(slot, i) => {
+                    let value = match self.load(Access::Local(slot as u32, true)) {
+                        MalData::Nothing => self.global(self.frame().proto.names[i as usize])?,
+                        value => value
+                    };
+
+                    self.stack.push(value);
+                }
+
+                Op::LoadUpvalueCell(i) => {
+                    let value = match self.load(Access::Upvalue(i, true)) {
+                        MalData::Nothing => self.global(self.frame().proto.captures[i as usize].name)?,
+                        value => value
+                    };
+
+                    self.stack.push(value);
+                }
+
+                Op::LoadGlobal(i) => {
+                    let value = self.global(self.frame().proto.names[i as usize])?;
+                    self.stack.push(value);
+                }
+
+                Op::StoreLocal(slot) => {
+                    let value = self.stack.pop().unwrap();
+                    let base = self.frame().base;
+
+                    self.stack[base + slot as usize] = value;
+                }
+
+                Op::NewCell(slot) => {
+                    let base = self.frame().base;
+                    self.stack[base + slot as usize] = make_mal_atom(MalData::Nil);
+                }
+
+                Op::StoreCell(slot) => {
+                    let value = self.stack.pop().unwrap();
+                    let base = self.frame().base;
+
+                    if let MalData::Atom(ref cell) = self.stack[base + slot as usize] {
+                        *cell.borrow_mut() = value;
+                    }
+                }
+
+                Op::Pop => {
+                    self.stack.pop();
+                }
+
+                Op::Jump(target) =>
+                    self.frames.last_mut().unwrap().pc = target as usize,
+
+                // ruecksprung: wie ein aufruf unterbrechbar und auf die schrittzahl begrenzt
+                Op::Recur(target) => {
+                    interrupt::check()?;
+                    limits::step()?;
+
+                    self.frames.last_mut().unwrap().pc = target as usize;
+                }
+
+                Op::JumpIfFalse(target) => {
+                    match self.stack.pop().unwrap() {
+                        MalData::Nil | MalData::False => self.frames.last_mut().unwrap().pc = target as usize,
+                        _ => ()
+                    }
+                }
+
+                Op::Vector(n) => {
+                    let items = self.stack.split_off(self.stack.len() - n as usize);
+                    self.stack.push(MalData::Vector(Rc::from(items), None));
+                }
+
+                Op::Map(i) => {
+                    let values = self.stack.split_off(self.stack.len() - self.frame().proto.map_keys[i as usize].len());
+                    let map: HashMap<MapKey, MalData> = self.frame().proto.map_keys[i as usize].iter().cloned().zip(values).collect();
+
+                    self.stack.push(MalData::Map(Rc::new(map), None));
+                }
+
+                Op::Def(i) => {
+                    let value = self.stack.pop().unwrap();
+                    let value = eval::define(&self.frame().env, &Var::Dynamic(self.frame().proto.names[i as usize]), value);
+
+                    self.stack.push(value);
+                }
+
+                Op::DefLocal(slot, i) => {
+                    let value = self.stack.pop().unwrap();
+                    let value = eval::named(&self.frame().proto.names[i as usize].name(), value);
+                    let base = self.frame().base;
+
+                    self.stack[base + slot as usize] = value.clone();
+                    self.stack.push(value);
+                }
+
+                Op::DefCell(slot, i) => {
+                    let value = self.stack.pop().unwrap();
+                    let value = eval::named(&self.frame().proto.names[i as usize].name(), value);
+                    let base = self.frame().base;
+
+                    if let MalData::Atom(ref cell) = self.stack[base + slot as usize] {
+                        *cell.borrow_mut() = value.clone();
+                    }
+
+                    self.stack.push(value);
+                }
+
+                Op::DefMacro(i) => {
+                    let value = match self.stack.pop().unwrap() {
+                        MalData::FnClosure(ref fnc) => {
+                            let frame = self.frame();
+                            let name = frame.proto.names[i as usize];
+                            let res = MalData::FnClosure(fnc.with_name(&name.name()).to_macro());
+
+                            frame.env.borrow_mut().set_id(name, &res);
+                            macros_changed();
+                            res
+                        }
+
+                        _ =>
+                            return Err(EvalError::General("closure expected".to_owned()))
+                    };
+
+                    self.stack.push(value);
+                }
+
+                Op::Closure(i) => {
+                    let closure = {
+                        let frame = self.frame();
+                        let proto = frame.proto.protos[i as usize].clone();
+
+                        let upvalues = proto.captures.iter().map( |capture| match capture.source {
+                            Source::Local(slot) => self.stack[frame.base + slot as usize].clone(),
+                            Source::Upvalue(i) => frame.upvalues[i as usize].clone()
+                        }).collect();
+
+                        FnClosure::compiled(frame.env.clone(), proto, Rc::new(upvalues))
+                    };
+
+                    self.stack.push(MalData::FnClosure(closure));
+                }
+
+                Op::Macroexpand(i) => {
+                    let value = eval::macroexpand(self.frame().env.clone(), &self.frame().proto.consts[i as usize])?;
+                    self.stack.push(value);
+                }
+
+                Op::LateMacro(site) => {
+                    let is_macro = match self.stack.last() {
+                        Some(MalData::FnClosure(fnc)) => fnc.is_macro(),
+                        _ => false
+                    };
+
+                    if is_macro {
+                        self.late_macro(site)?;
+                    }
+                }
+
+                Op::Expansion(site) => {
+                    let is_current = {
+                        let frame = self.frame();
+                        frame.proto.sites[site as usize].expansion.as_ref().unwrap().is_current(&frame.env)
+                    };
+
+                    if !is_current {
+                        self.redo_expansion(site)?;
+                    }
+                }
+
+                Op::Call(argc, site) =>
+                    self.call(argc as usize, site, false)?,
+
+                Op::TailCall(argc, site) =>
+                    self.call(argc as usize, site, true)?,
+
+                Op::Return => {
+                    let value = self.stack.pop().unwrap();
+                    let frame = self.frames.pop().unwrap();
+
+                    self.stack.truncate(frame.base - 1);
+                    leave_call_frames(frame.call_depth);
+                    limits::leave();
+
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+
+                    self.stack.push(value);
+                }
+
+                Op::Catch(target) =>
+                    self.push_handler(HandlerKind::Catch, target),
+
+                Op::Finally(target) =>
+                    self.push_handler(HandlerKind::Finally, target),
+
+                Op::PopHandler => {
+                    self.handlers.pop();
+                }
+
+                Op::Reraise =>
+                    return Err(self.pending.pop().unwrap()),
+
+                Op::Raise(i) =>
+                    return Err(self.frame().proto.errors[i as usize].clone())
+            }
+        }
+    }
+
+    // aufruf der funktion unter den `argc` argumenten oben auf dem stapel; eine uebersetzte
+    // closure in endposition ersetzt den laufenden rahmen
+    fn call(&mut self, argc: usize, site: u32, tail: bool) -> Result<(), EvalError> {
+        interrupt::check()?;
+        limits::step()?;
+
+        let callee_at = self.stack.len() - argc - 1;
+        let form = self.frame().proto.sites[site as usize].form.clone();
+
+        let callee = match self.stack[callee_at] {
+            MalData::FnClosure(ref fnc) => match fnc.code {
+                FnCode::Bytecode(ref proto, ref upvalues) =>
+                    Some(( proto.clone(), upvalues.clone(), fnc.outer_env.clone(), fnc.name.clone() )),
+
+                FnCode::Tree(_) =>
+                    None
+            },
+
+            _ =>
+                None
+        };
+
+        if let Some(( proto, upvalues, env, name )) = callee {
+            let stack_frame = StackFrame::new(name.as_deref(), &form);
+
+            if !tail {
+                let call_depth = call_stack_depth();
+
+                enter_call_frame(call_depth, stack_frame);
+                return self.enter(proto, upvalues, env, name.as_deref(), argc, call_depth);
+            }
+
+            let ( base, call_depth ) = ( self.frame().base, self.frame().call_depth );
+
+            enter_call_frame(call_depth, stack_frame);
+
+            // funktion und argumente ersetzen die slots des laufenden rahmens
+            self.stack.drain(base - 1..callee_at);
+            let ( _, pc ) = self.bind_args(&proto, name.as_deref(), argc)?;
+
+            let frame = self.frames.last_mut().unwrap();
+            frame.proto = proto;
+            frame.upvalues = upvalues;
+            frame.env = env;
+            frame.pc = pc;
+
+            return Ok(());
+        }
+
+        let args = self.stack.split_off(callee_at + 1);
+        let fun = self.stack.pop().unwrap();
+        let depth = call_stack_depth();
+
+        let res = match fun {
+            MalData::Function(ref f) => {
+                if !f.is_internal() {
+                    push_call_frame(StackFrame::new(Some(f.name()), &form));
+                }
+
+                eval::call_function(self.frame().env.clone(), f, &args)?
+            }
+
+            MalData::FnClosure(ref fnc) => {
+                enter_call_frame(depth, StackFrame::new(fnc.name.as_deref(), &form));
+                eval::apply(self.frame().env.clone(), &fun, &args)?
+            }
+
+            el =>
+                return Err(EvalError::General(format!("first element is not a function ({:?})", el)))
+        };
+
+        leave_call_frames(depth);
+        self.stack.push(res);
+
+        Ok(())
+    }
+
+    // aufruf eines erst nach der uebersetzung definierten makros: die expansion wertet der
+    // evaluator in einer umgebung mit den an der aufrufstelle sichtbaren lokalen namen aus
+    fn late_macro(&mut self, site: u32) -> Result<(), EvalError> {
+        let mac = self.stack.pop().unwrap();
+        let frame_proto = self.frame().proto.clone();
+        let site = &frame_proto.sites[site as usize];
+        let late = site.late.as_ref().unwrap();
+
+        let env = self.scope_env(late);
+        let node = late.cache.node(&env, &mac, &site.form)?;
+
+        self.finish_late(late, eval::eval_node(env, &node)?);
+        Ok(())
+    }
+
+    // expansion, deren makro seit der uebersetzung neu definiert wurde
+    fn redo_expansion(&mut self, site: u32) -> Result<(), EvalError> {
+        let frame_proto = self.frame().proto.clone();
+        let site = &frame_proto.sites[site as usize];
+        let late = site.late.as_ref().unwrap();
+
+        let env = self.scope_env(late);
+        let node = site.expansion.as_ref().unwrap().reanalyzed(&env)?;
+
+        self.finish_late(late, eval::eval_node(env, &node)?);
+        Ok(())
+    }
+
+    fn scope_env(&self, late: &LateSite) -> EnvType {
+        let env = wrapped_env_type(Env::local(self.frame().env.clone()));
+
+        for ( name, access ) in &late.scope {
+            match self.load(*access) {
+                MalData::Nothing => (),
+                value => env.borrow_mut().set_id(*name, &value)
+            }
+        }
+
+        env
+    }
+
+    fn finish_late(&mut self, late: &LateSite, res: MalData) {
+        self.stack.push(res);
+        self.frames.last_mut().unwrap().pc = late.end as usize;
+    }
+}
+
+// disassembler
+
+// lesbare fassung des bytecodes einer closure; eine vom evaluator erzeugte closure wird dazu
+// zuerst uebersetzt
+pub fn disassemble(closure: &FnClosure) -> Result<String, EvalError> {
+    let proto = match closure.code {
+        FnCode::Bytecode(ref proto, _) => proto.clone(),
+        FnCode::Tree(ref lambda) => Rc::new(compile_lambda(&closure.outer_env, lambda)?)
+    };
+
+    let mut out = String::new();
+    write_proto(&mut out, &proto, closure.name.as_deref().unwrap_or("fn*"), "");
+
+    Ok(out)
+}
+
+fn write_proto(out: &mut String, proto: &Proto, title: &str, indent: &str) {
+    let params = |entry: &Entry| format!("[{}]", entry.binds.iter().map( |b| b.as_str() ).collect::<Vec<&str>>().join(" "));
+    let arities = proto.entries.iter().map(params).collect::<Vec<String>>().join(" ");
+    writeln!(out, "{}{} {} ; {} slots, {} upvalues", indent, title, arities, proto.slots, proto.captures.len()).unwrap();
+
+    for ( pc, op ) in proto.code.iter().enumerate() {
+        // bei mehreren arities beginnt jede mit ihren parametern
+        if proto.entries.len() > 1 {
+            if let Some(entry) = proto.entries.iter().find( |entry| entry.start as usize == pc ) {
+                writeln!(out, "{}{}:", indent, params(entry)).unwrap();
+            }
+        }
+
+        let note = match *op {
+            Op::Const(i) | Op::Macroexpand(i) =>
+                Some(pr_str(&proto.consts[i as usize], true)),
+
+            Op::LoadLocal(slot) | Op::LoadCell(slot) | Op::StoreLocal(slot) | Op::NewCell(slot) | Op::StoreCell(slot) =>
+                Some(proto.slot_names[slot as usize].clone()).filter( |name| !name.is_empty() ),
+
+            Op::LoadUpvalue(i) | Op::LoadUpvalueCell(i) =>
+                Some(proto.captures[i as usize].name.to_string()),
+
+            Op::LoadGlobal(i) | Op::LoadDefined(_, i) | Op::Def(i) | Op::DefLocal(_, i) | Op::DefCell(_, i) | Op::DefMacro(i) =>
+                Some(proto.names[i as usize].to_string()),
+
+            Op::Raise(i) =>
+                Some(proto.errors[i as usize].to_string()),
+
+            Op::Map(i) =>
+                Some(proto.map_keys[i as usize].iter().map( |key| pr_str(&mal_value_for(key), true) ).collect::<Vec<String>>().join(" ")),
+
+            Op::Call(_, site) | Op::TailCall(_, site) | Op::LateMacro(site) | Op::Expansion(site) =>
+                Some(pr_str(&proto.sites[site as usize].form, true)),
+
+            _ =>
+                None
+        };
+
+        let line = format!("{}{:4}  {}", indent, pc, op);
+
+        match note {
+            Some(note) => writeln!(out, "{:<36} ; {}", line, note).unwrap(),
+            None => writeln!(out, "{}", line).unwrap()
+        }
+    }
+
+    let nested_indent = format!("{}    ", indent);
+
+    for ( i, nested ) in proto.protos.iter().enumerate() {
+        write_proto(out, nested, &format!("closure {}:", i), &nested_indent);
+    }
+}
@@ -0,0 +1,129 @@
+// capabilities: was ein interpreter auf dem host darf; dateizugriffe lassen sich zusaetzlich auf
+// verzeichnisse beschraenken, deren grenze weder .. noch symlinks umgehen
+
+extern crate mal;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mal::Interpreter;
+use mal::capabilities::{Capabilities, Capability};
+
+use common::eval;
+
+// frisches verzeichnis mit root/inside.txt und outside.txt daneben
+fn sandbox(name: &str) -> ( PathBuf, PathBuf ) {
+    let dir = env::temp_dir().join(format!("mal-capabilities-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    let root = dir.join("root");
+    fs::create_dir_all(&root).unwrap();
+    fs::write(root.join("inside.txt"), "inside").unwrap();
+    fs::write(dir.join("outside.txt"), "outside").unwrap();
+
+    ( dir.canonicalize().unwrap(), root.canonicalize().unwrap() )
+}
+
+fn slurp(interpreter: &Interpreter, path: &Path) -> String {
+    eval(interpreter, &format!("(slurp {:?})", path.display().to_string()))
+}
+
+#[test]
+fn allowed_paths_cannot_be_left_with_dot_dot() {
+    let ( dir, root ) = sandbox("dotdot");
+    let interpreter = Interpreter::with_capabilities(Capabilities::all().allow_path(&root));
+
+    assert_eq!(slurp(&interpreter, &root.join("inside.txt")), "\"inside\"");
+    assert_eq!(slurp(&interpreter, &root.join("..").join("root").join("inside.txt")), "\"inside\"");
+
+    let escaped = root.join("..").join("outside.txt");
+    assert_eq!(slurp(&interpreter, &escaped),
+               format!("error: slurp: capability denied: {} is outside the allowed paths", escaped.display()));
+
+    // ein erlaubter pfad mit .. gilt fuer das verzeichnis, auf das er zeigt
+    let interpreter = Interpreter::with_capabilities(Capabilities::all().allow_path(root.join("..")));
+    assert_eq!(slurp(&interpreter, &dir.join("outside.txt")), "\"outside\"");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn allowed_paths_cannot_be_left_with_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let ( dir, root ) = sandbox("symlink");
+    symlink(dir.join("outside.txt"), root.join("link.txt")).unwrap();
+    symlink(&dir, root.join("up")).unwrap();
+
+    let interpreter = Interpreter::with_capabilities(Capabilities::all().allow_path(&root));
+
+    assert!(slurp(&interpreter, &root.join("link.txt")).contains("capability denied"));
+    assert!(slurp(&interpreter, &root.join("up").join("outside.txt")).contains("capability denied"));
+    assert!(eval(&interpreter, &format!("(spit {:?} \"x\")", root.join("up").join("new.txt").display().to_string()))
+        .contains("capability denied"));
+    assert!(!dir.join("new.txt").exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn spit_creates_new_files_only_inside_allowed_paths() {
+    let ( dir, root ) = sandbox("spit");
+    let interpreter = Interpreter::with_capabilities(Capabilities::all().allow_path(&root));
+
+    let created = root.join("created.txt");
+    assert_eq!(eval(&interpreter, &format!("(spit {:?} \"hello\")", created.display().to_string())), "nil");
+    assert_eq!(fs::read_to_string(&created).unwrap(), "hello");
+
+    let outside = dir.join("created.txt");
+    assert_eq!(eval(&interpreter, &format!("(spit {:?} \"hello\")", outside.display().to_string())),
+               format!("error: spit: capability denied: {} is outside the allowed paths", outside.display()));
+    assert!(!outside.exists());
+
+    // ohne fs-write darf spit auch innerhalb nicht schreiben
+    let interpreter = Interpreter::with_capabilities(Capabilities::all().without(Capability::FsWrite).allow_path(&root));
+    assert_eq!(eval(&interpreter, &format!("(spit {:?} \"x\")", created.display().to_string())),
+               "error: spit: capability denied: fs-write");
+    assert_eq!(fs::read_to_string(&created).unwrap(), "hello");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn no_capabilities_deny_host_access() {
+    let ( dir, root ) = sandbox("none");
+    let interpreter = Interpreter::with_capabilities(Capabilities::none());
+
+    assert_eq!(slurp(&interpreter, &root.join("inside.txt")), "error: slurp: capability denied: fs-read");
+    assert_eq!(eval(&interpreter, "(prn 1)"), "error: prn: capability denied: stdout");
+    assert_eq!(eval(&interpreter, "(time-ms)"), "error: time-ms: capability denied: time");
+    assert_eq!(eval(&interpreter, "(eval '(+ 1 2))"), "error: eval: capability denied: eval");
+
+    // die sprache selbst bleibt nutzbar
+    assert_eq!(eval(&interpreter, "(let* [f (fn* [x] (* x 2))] (map f [1 2 3]))"), "(2 4 6)");
+    assert_eq!(eval(&interpreter, "(pr-str {:a 1})"), "\"{:a 1}\"");
+
+    let interpreter = Interpreter::with_capabilities(Capabilities::none().with(Capability::FsRead));
+    assert_eq!(slurp(&interpreter, &root.join("inside.txt")), "\"inside\"");
+
+    // MAL_PATH gehoert zur umgebung des prozesses
+    env::set_var("MAL_PATH", &root);
+    assert_eq!(Interpreter::with_capabilities(Capabilities::none()).search_path(), Vec::<PathBuf>::new());
+    assert_eq!(Interpreter::with_capabilities(Capabilities::none().with(Capability::Process)).search_path(), vec![root.clone()]);
+    env::remove_var("MAL_PATH");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn denied_calls_throw_capability_denied() {
+    let interpreter = Interpreter::with_capabilities(Capabilities::none());
+
+    assert_eq!(eval(&interpreter, "(try* (slurp \"x\") (catch* e (get (ex-data e) :type)))"), ":capability-denied");
+    assert_eq!(eval(&interpreter, "(try* (slurp \"x\") (catch* e (get (ex-data e) :fn)))"), "\"slurp\"");
+    assert_eq!(eval(&interpreter, "(try* (time-ms) (catch* e (get (ex-data e) :fn)))"), "\"time-ms\"");
+}
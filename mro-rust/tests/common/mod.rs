@@ -0,0 +1,12 @@
+// gemeinsame hilfsfunktionen der integrationstests
+
+use mal::Interpreter;
+use mal::printer::pr_str;
+
+// das ergebnis von `input` wie in der repl, ein fehler als "error: ..."
+pub fn eval(interpreter: &Interpreter, input: &str) -> String {
+    match interpreter.eval_str(input) {
+        Ok(res) => pr_str(&res, true),
+        Err(err) => format!("error: {}", err)
+    }
+}
@@ -0,0 +1,143 @@
+// umwandlung zwischen MalData und rust-typen (FromMal/IntoMal) sowie mit native_fn registrierte
+// funktionen: anzahl und typen der argumente, nil als Option, maps als HashMap
+
+extern crate mal;
+
+mod common;
+
+use std::collections::HashMap;
+
+use mal::Interpreter;
+use mal::common::{MalData, MapKey};
+use mal::convert::{FromMal, IntoMal, IntoMapKey, TypeMismatch};
+
+use common::eval;
+
+fn read(interpreter: &Interpreter, input: &str) -> MalData {
+    interpreter.eval_str(input).unwrap()
+}
+
+#[test]
+fn numbers_convert_within_range() {
+    let interpreter = Interpreter::new();
+
+    assert_eq!(i32::from_mal(&read(&interpreter, "-7")), Ok(-7));
+    assert_eq!(i64::from_mal(&read(&interpreter, "4294967296")), Ok(4294967296));
+    assert_eq!(i32::from_mal(&read(&interpreter, "4294967296")),
+               Err(TypeMismatch { expected: "a number between -2147483648 and 2147483647".to_owned(), got: "number" }));
+    assert_eq!(u8::from_mal(&read(&interpreter, "255")), Ok(255));
+    assert_eq!(u8::from_mal(&read(&interpreter, "256")),
+               Err(TypeMismatch { expected: "a number between 0 and 255".to_owned(), got: "number" }));
+    assert_eq!(usize::from_mal(&read(&interpreter, "-1")),
+               Err(TypeMismatch { expected: "a non-negative number".to_owned(), got: "number" }));
+    assert_eq!(i64::from_mal(&read(&interpreter, "\"1\"")),
+               Err(TypeMismatch { expected: "a number".to_owned(), got: "string" }));
+}
+
+#[test]
+fn wide_integers_saturate_into_mal() {
+    assert_eq!(42usize.into_mal(), MalData::Number(42));
+    assert_eq!(u32::MAX.into_mal(), MalData::Number(4294967295));
+    assert_eq!(u64::MAX.into_mal(), MalData::Number(i64::MAX));
+    assert_eq!(i64::MIN.into_mal(), MalData::Number(i64::MIN));
+    assert_eq!((-5i64).into_mal(), MalData::Number(-5));
+    assert_eq!(usize::MAX.into_map_key(), MapKey::Number(i64::MAX));
+}
+
+#[test]
+fn option_is_nil_or_value() {
+    let interpreter = Interpreter::new();
+
+    assert_eq!(Option::<String>::from_mal(&MalData::Nil), Ok(None));
+    assert_eq!(Option::<String>::from_mal(&read(&interpreter, "\"a\"")), Ok(Some("a".to_owned())));
+    assert_eq!(Option::<String>::from_mal(&read(&interpreter, "1")),
+               Err(TypeMismatch { expected: "a string or nil".to_owned(), got: "number" }));
+
+    assert_eq!(None::<i32>.into_mal(), MalData::Nil);
+    assert_eq!(Some(3).into_mal(), MalData::Number(3));
+}
+
+#[test]
+fn vectors_report_the_bad_element() {
+    let interpreter = Interpreter::new();
+
+    assert_eq!(Vec::<i32>::from_mal(&read(&interpreter, "[1 2 3]")), Ok(vec![1, 2, 3]));
+    assert_eq!(Vec::<i32>::from_mal(&read(&interpreter, "'(1 2)")), Ok(vec![1, 2]));
+    assert_eq!(Vec::<i64>::from_mal(&read(&interpreter, "[1 :a]")),
+               Err(TypeMismatch { expected: "a list or vector, element 2 must be a number".to_owned(), got: "keyword" }));
+}
+
+#[test]
+fn hash_maps_convert_keys_and_values() {
+    let interpreter = Interpreter::new();
+
+    let map = HashMap::<String, i32>::from_mal(&read(&interpreter, "{\"a\" 1 \"b\" 2}")).unwrap();
+    assert_eq!(map.len(), 2);
+    assert_eq!(map["a"], 1);
+    assert_eq!(map["b"], 2);
+
+    let map = HashMap::<i32, bool>::from_mal(&read(&interpreter, "{1 true 2 false}")).unwrap();
+    assert!(map[&1]);
+    assert!(!map[&2]);
+
+    assert_eq!(HashMap::<String, i32>::from_mal(&read(&interpreter, "{:a 1}")),
+               Err(TypeMismatch { expected: "a map, keys must be a string".to_owned(), got: "keyword" }));
+    assert_eq!(HashMap::<String, i64>::from_mal(&read(&interpreter, "{\"a\" nil}")),
+               Err(TypeMismatch { expected: "a map, values must be a number".to_owned(), got: "nil" }));
+
+    // MapKey laesst jeden schluessel durch
+    let map = HashMap::<MapKey, i32>::from_mal(&read(&interpreter, "{:a 1}")).unwrap();
+    assert_eq!(map.len(), 1);
+
+    let mut counts = HashMap::new();
+    counts.insert("x".to_owned(), 1usize);
+    counts.insert("y".to_owned(), 2usize);
+    interpreter.define("counts", counts.into_mal());
+    assert_eq!(eval(&interpreter, "(+ (get counts \"x\") (get counts \"y\"))"), "3");
+}
+
+#[test]
+fn native_functions_check_arity_and_types() {
+    let interpreter = Interpreter::new();
+
+    interpreter.define_fn("repeat-str", |n: usize, s: String| -> Result<String, String> { Ok(s.repeat(n)) });
+    interpreter.define_fn("len", |s: String| -> Result<usize, String> { Ok(s.len()) });
+
+    assert_eq!(eval(&interpreter, "(repeat-str 3 \"ab\")"), "\"ababab\"");
+    assert_eq!(eval(&interpreter, "(len \"abc\")"), "3");
+
+    assert_eq!(eval(&interpreter, "(repeat-str 3)"), "error: repeat-str: wrong number of arguments, expected 2, got 1");
+    assert_eq!(eval(&interpreter, "(repeat-str 1 2 3)"), "error: repeat-str: wrong number of arguments, expected 2, got 3");
+    assert_eq!(eval(&interpreter, "(repeat-str \"ab\" 3)"), "error: repeat-str: argument 1 must be a non-negative number, got string");
+    assert_eq!(eval(&interpreter, "(repeat-str 2 :a)"), "error: repeat-str: argument 2 must be a string, got keyword");
+
+    // fehler kommen in catch* mit :type an
+    assert_eq!(eval(&interpreter, "(try* (repeat-str -1 \"a\") (catch* e (get (ex-data e) :arg)))"), "1");
+    assert_eq!(eval(&interpreter, "(try* (len) (catch* e (get (ex-data e) :type)))"), ":arity");
+}
+
+#[test]
+fn native_functions_take_nil_as_none() {
+    let interpreter = Interpreter::new();
+
+    interpreter.define_fn("greet", |name: Option<String>| -> Result<String, String> {
+        Ok(format!("hello {}", name.unwrap_or_else( || "world".to_owned() )))
+    });
+
+    assert_eq!(eval(&interpreter, "(greet \"mal\")"), "\"hello mal\"");
+    assert_eq!(eval(&interpreter, "(greet nil)"), "\"hello world\"");
+    assert_eq!(eval(&interpreter, "(greet 1)"), "error: greet: argument 1 must be a string or nil, got number");
+}
+
+#[test]
+fn native_function_errors_are_native() {
+    let interpreter = Interpreter::new();
+
+    interpreter.define_fn("checked-div", |a: i32, b: i32| -> Result<i32, String> {
+        a.checked_div(b).ok_or_else( || "division by zero".to_owned() )
+    });
+
+    assert_eq!(eval(&interpreter, "(checked-div 7 2)"), "3");
+    assert_eq!(eval(&interpreter, "(checked-div 7 0)"), "error: checked-div: division by zero");
+    assert_eq!(eval(&interpreter, "(try* (checked-div 7 0) (catch* e (get (ex-data e) :type)))"), ":native");
+}
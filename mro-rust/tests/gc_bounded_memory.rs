@@ -0,0 +1,110 @@
+// closures, die sich ueber ihre umgebung selbst erreichen, bilden Rc-zyklen; ohne den
+// zyklensammler waechst der speicher mit jeder erzeugten closure. gezaehlt werden die belegten
+// bytes des laufenden threads
+
+extern crate mal;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use mal::{Interpreter, Backend};
+use mal::gc;
+
+struct Counting;
+
+thread_local! {
+    static LIVE_BYTES: Cell<isize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.with( |live| live.set(live.get() + layout.size() as isize) );
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.with( |live| live.set(live.get() - layout.size() as isize) );
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ROUNDS: usize = 60;
+
+// jeder aufruf von make-loop hinterlaesst einen zyklus aus der umgebung des let* und der closure
+// (in der vm aus der zelle von lp und der closure)
+const PROGRAM: &str = "
+(def! make-loop (fn* [] (let* [lp (fn* [n] (if (= n 0) :end (lp (- n 1))))] lp)))
+(def! churn (fn* [n] (if (= n 0) nil (do ((make-loop) 2) (churn (- n 1))))))";
+
+// belegte bytes nach jeder runde
+fn live_bytes_per_round(backend: Backend) -> Vec<isize> {
+    let interpreter = Interpreter::new().with_backend(backend);
+    interpreter.eval_str(PROGRAM).unwrap();
+
+    (0..ROUNDS).map( |_| {
+        interpreter.eval_str("(churn 500)").unwrap();
+        LIVE_BYTES.with(Cell::get)
+    }).collect()
+}
+
+fn assert_bounded(backend: Backend) {
+    let collections = gc::stats().collections;
+    let live = live_bytes_per_round(backend);
+
+    // bis zur haelfte wurde mehrfach gesammelt; danach darf der hoechststand nicht mehr steigen
+    let ( first, second ) = live.split_at(ROUNDS / 2);
+    let first_peak = *first.iter().max().unwrap();
+    let second_peak = *second.iter().max().unwrap();
+
+    assert!(gc::stats().collections > collections, "no automatic collection with {:?}", backend);
+    assert!(second_peak <= first_peak + first_peak / 10, "{:?}: memory grows from {} to {} bytes", backend, first_peak, second_peak);
+}
+
+#[test]
+fn collected_closures_keep_memory_bounded() {
+    assert_bounded(Backend::Evaluator);
+}
+
+#[test]
+fn collected_closures_keep_memory_bounded_in_vm() {
+    assert_bounded(Backend::Vm);
+}
+
+#[test]
+fn gc_frees_unreachable_cycles() {
+    let interpreter = Interpreter::new();
+    interpreter.eval_str(PROGRAM).unwrap();
+    gc::collect();
+
+    let before = LIVE_BYTES.with(Cell::get);
+    interpreter.eval_str("(churn 100)").unwrap();
+    let grown = LIVE_BYTES.with(Cell::get);
+
+    assert!(gc::collect() >= 100);
+    let after = LIVE_BYTES.with(Cell::get);
+
+    assert!(grown > before, "churn leaves no cycles behind");
+    assert!(after - before < (grown - before) / 10, "{} of {} bytes not freed", after - before, grown - before);
+}
+
+// die methode haelt die umgebung des let*, die die multimethode haelt; der zyklus fuehrt durch den
+// zustand der multimethode
+#[test]
+fn gc_frees_cycles_through_multimethods() {
+    let interpreter = Interpreter::new();
+    interpreter.eval_str("(def! churn-methods (fn* [n] (if (= n 0) nil (do (let* [m (multi-fn 'm first)] (add-method m [n] (fn* [x] m))) (churn-methods (- n 1))))))").unwrap();
+    gc::collect();
+
+    let before = LIVE_BYTES.with(Cell::get);
+    interpreter.eval_str("(churn-methods 100)").unwrap();
+    let grown = LIVE_BYTES.with(Cell::get);
+
+    assert!(gc::collect() >= 100);
+    let after = LIVE_BYTES.with(Cell::get);
+
+    assert!(grown > before, "churn-methods leaves no cycles behind");
+    assert!(after - before < (grown - before) / 10, "{} of {} bytes not freed", after - before, grown - before);
+}
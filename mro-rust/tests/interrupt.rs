@@ -0,0 +1,59 @@
+// interrupt::interrupt aus einem anderen thread bricht die laufende auswertung mit
+// EvalError::Interrupted ab; try* faengt den abbruch nicht, der interpreter bleibt benutzbar.
+// das flag gilt fuer den ganzen prozess, deshalb laeuft alles in einem test
+
+extern crate mal;
+
+use std::thread;
+use std::time::Duration;
+
+use mal::{Interpreter, Backend};
+use mal::common::MalData;
+use mal::eval::EvalError;
+use mal::interrupt;
+
+fn is_interrupted(err: &EvalError) -> bool {
+    match *err {
+        EvalError::Interrupted => true,
+        EvalError::Traced(ref err, _) => is_interrupted(err),
+        _ => false
+    }
+}
+
+// wertet `input` aus und unterbricht die auswertung nach 50 ms
+fn eval_interrupted(interpreter: &Interpreter, input: &str) -> EvalError {
+    interrupt::clear();
+
+    let interrupter = thread::spawn( || {
+        thread::sleep(Duration::from_millis(50));
+        interrupt::interrupt();
+    });
+
+    let res = interpreter.eval_str(input);
+    interrupter.join().unwrap();
+
+    res.expect_err(input)
+}
+
+#[test]
+fn interrupt_stops_the_running_evaluation() {
+    for &backend in &[Backend::Evaluator, Backend::Vm] {
+        let interpreter = Interpreter::new().with_backend(backend);
+        interpreter.eval_str("(def! counter (atom 0)) (def! spin (fn* [] (loop* [i 0] (do (reset! counter i) (recur (+ i 1))))))").unwrap();
+
+        let err = eval_interrupted(&interpreter, "(spin)");
+        assert!(is_interrupted(&err), "{:?}: {}", backend, err);
+
+        let err = eval_interrupted(&interpreter, "(try* (spin) (catch* e :caught))");
+        assert!(is_interrupted(&err), "{:?}: {}", backend, err);
+
+        // finally* laeuft noch, bevor der abbruch den interpreter verlaesst
+        let err = eval_interrupted(&interpreter, "(try* (spin) (finally* (reset! counter -1)))");
+        assert!(is_interrupted(&err), "{:?}: {}", backend, err);
+        assert_eq!(interpreter.eval_str("@counter").unwrap(), MalData::Number(-1));
+
+        // die umgebung ist danach unveraendert benutzbar
+        interpreter.eval_str("(def! x 41)").unwrap();
+        assert_eq!(interpreter.eval_str("(let* [f (fn* [y] (+ x y))] (f 1))").unwrap(), MalData::Number(42));
+    }
+}
@@ -0,0 +1,90 @@
+// grenzen der auswertung: die voreingestellten halten tiefe rekursion auch auf einem 2 MB grossen
+// thread-stack auf, in debug-builds wie in release-builds, ueber mal-funktionen wie ueber map und
+// apply; schritte und zeit begrenzen endlose schleifen. try* faengt keine der ueberschreitungen ab,
+// der interpreter bleibt danach benutzbar
+
+extern crate mal;
+
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use mal::{Interpreter, Backend};
+use mal::limits::Limits;
+
+use common::eval;
+
+const THREAD_STACK: usize = 2 * 1024 * 1024;
+
+fn eval_on_small_thread(backend: Backend, input: &'static str) -> String {
+    let thread = thread::Builder::new().stack_size(THREAD_STACK).spawn(move || {
+        let interpreter = Interpreter::new().with_backend(backend);
+        eval(&interpreter, input)
+    }).unwrap();
+
+    thread.join().unwrap()
+}
+
+fn assert_limit_exceeded(backend: Backend, input: &'static str) {
+    let res = eval_on_small_thread(backend, input);
+    assert_eq!(res, ":limit-exceeded", "{}", input);
+}
+
+fn assert_deep_recursion_stops(backend: Backend) {
+    assert_limit_exceeded(backend,
+        "(def! f (fn* [n] (if (= n 0) 0 (+ 1 (f (- n 1)))))) (try* (f 100000) (catch* e (get (ex-data e) :type)))");
+    assert_limit_exceeded(backend,
+        "(def! g (fn* [n] (if (= n 0) 0 (+ 1 (first (map g [(- n 1)])))))) (try* (g 100000) (catch* e (get (ex-data e) :type)))");
+    assert_limit_exceeded(backend,
+        "(def! h (fn* [n] (if (= n 0) 0 (+ 1 (apply h [(- n 1)]))))) (try* (h 100000) (catch* e (get (ex-data e) :type)))");
+
+    // danach ist der interpreter weiter benutzbar, flache rekursion bleibt erlaubt
+    assert_eq!(eval_on_small_thread(backend, "(def! f (fn* [n] (if (= n 0) 0 (+ 1 (f (- n 1)))))) (f 20)"), "20");
+}
+
+#[test]
+fn deep_recursion_stops_before_the_stack_overflows() {
+    assert_deep_recursion_stops(Backend::Evaluator);
+}
+
+#[test]
+fn deep_recursion_stops_before_the_stack_overflows_in_vm() {
+    assert_deep_recursion_stops(Backend::Vm);
+}
+
+#[test]
+fn uncaught_stack_limit_reports_the_limit() {
+    let res = eval_on_small_thread(Backend::Evaluator, "(def! f (fn* [n] (+ 1 (f n)))) (f 0)");
+    assert_eq!(res, format!("error: evaluation limit exceeded: using more than {} bytes of stack", mal::limits::DEFAULT_MAX_STACK));
+}
+
+fn assert_endless_loop_stops(limits: Limits, message: &str) {
+    for &backend in &[Backend::Evaluator, Backend::Vm] {
+        let interpreter = Interpreter::new().with_backend(backend).with_limits(limits.clone());
+        let expected = format!("error: evaluation limit exceeded: {}", message);
+
+        assert_eq!(eval(&interpreter, "(def! counter (atom 0))"), "(atom 0)");
+        assert_eq!(eval(&interpreter, "(loop* [i 0] (do (reset! counter i) (recur (+ i 1))))"), expected, "{:?}", backend);
+        assert_eq!(eval(&interpreter, "(try* (loop* [i 0] (recur (+ i 1))) (catch* e :caught))"), expected, "{:?}", backend);
+        assert_eq!(eval(&interpreter, "(try* (loop* [i 0] (recur (+ i 1))) (catch* e (get (ex-data e) :type)))"), expected, "{:?}", backend);
+
+        // die grenze gilt je auswertung, die definitionen davor bleiben erhalten
+        assert_eq!(eval(&interpreter, "(> @counter 0)"), "true", "{:?}", backend);
+        assert_eq!(eval(&interpreter, "(let* [f (fn* [x] (* x 2))] (f 21))"), "42", "{:?}", backend);
+    }
+}
+
+#[test]
+fn step_limit_stops_endless_loops() {
+    assert_endless_loop_stops(Limits::default().with_max_steps(10_000), "more than 10000 evaluation steps");
+}
+
+#[test]
+fn timeout_stops_endless_loops() {
+    let started = Instant::now();
+    assert_endless_loop_stops(Limits::default().with_timeout(Duration::from_millis(50)), "running longer than 50 ms");
+
+    // sechs abgebrochene schleifen, jede nach etwa 50 ms
+    assert!(started.elapsed() < Duration::from_secs(10));
+}
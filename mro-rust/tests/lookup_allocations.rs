@@ -0,0 +1,111 @@
+// das nachschlagen eines namens teilt den gebundenen wert, statt ihn zu kopieren: gezaehlt werden
+// die allokationen des laufenden threads
+
+extern crate mal;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::rc::Rc;
+
+use mal::Interpreter;
+use mal::common::MalData;
+use mal::env::{Env, wrapped_env_type};
+use mal::analyze::{analyze, Node};
+use mal::eval::eval_node;
+use mal::reader::read_str;
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with( |count| count.set(count.get() + 1) );
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+fn allocations_in<R, F: FnOnce() -> R>(f: F) -> usize {
+    let before = ALLOCATIONS.with(Cell::get);
+    let res = f();
+    let after = ALLOCATIONS.with(Cell::get);
+
+    drop(res);
+    after - before
+}
+
+fn values() -> Vec<MalData> {
+    ["{:a [1 2 3] :b \"text\"}", "(1 (2 3) [4])", "\"a string\"", ":keyword", "sym"].iter()
+        .map( |form| read_str(form).unwrap() )
+        .collect()
+}
+
+#[test]
+fn global_lookup_does_not_allocate() {
+    assert!(allocations_in(values) > 0, "allocations are not counted");
+
+    let root = wrapped_env_type(Env::new(None, &[], &[]).unwrap());
+
+    for ( i, value ) in values().iter().enumerate() {
+        let name = format!("value-{}", i);
+        root.borrow_mut().set(&name, value);
+
+        let node = Rc::new(analyze(&root, &read_str(&name).unwrap()).unwrap());
+
+        assert_eq!(allocations_in( || eval_node(root.clone(), &node).unwrap() ), 0, "lookup of {}", name);
+    }
+}
+
+#[test]
+fn local_lookup_does_not_allocate() {
+    let root = wrapped_env_type(Env::new(None, &[], &[]).unwrap());
+    let form = read_str("(fn* [a b c d e] (let* [x a] (fn* [] x)))").unwrap();
+
+    let lambda = match analyze(&root, &form).unwrap() {
+        Node::Fn(lambda) => lambda,
+        _ => panic!("fn* expected")
+    };
+
+    let arity = &lambda.arities[0];
+    let fn_env = wrapped_env_type(Env::with_args(Some(root.clone()), &arity.params, arity.variadic, &values()).unwrap());
+
+    let ( bindings, body ) = match *arity.body {
+        Node::Let(ref bindings, ref body) => ( bindings, body ),
+        _ => panic!("let* expected")
+    };
+
+    // der wert von x ist der parameter a, eine ebene ueber der umgebung des let*
+    let let_env = wrapped_env_type(Env::local(fn_env));
+    let ( x, init ) = &bindings[0];
+
+    assert_eq!(allocations_in( || eval_node(let_env.clone(), init).unwrap() ), 0, "lookup of a parameter");
+
+    let value = eval_node(let_env.clone(), init).unwrap();
+    let_env.borrow_mut().set_slot(0, x.id(), &value);
+
+    let inner = match **body {
+        Node::Fn(ref inner) => inner.clone(),
+        _ => panic!("fn* expected")
+    };
+
+    let inner_env = wrapped_env_type(Env::with_args(Some(let_env), &[], false, &[]).unwrap());
+    assert_eq!(allocations_in( || eval_node(inner_env.clone(), &inner.arities[0].body).unwrap() ), 0, "lookup of a captured name");
+}
+
+#[test]
+fn interpreter_get_does_not_allocate() {
+    let interpreter = Interpreter::new();
+    interpreter.eval_str("(def! m {:a (list 1 2) :f (fn* [x] x)})").unwrap();
+
+    assert_eq!(allocations_in( || interpreter.get("m").unwrap() ), 0);
+    assert_eq!(allocations_in( || interpreter.get("map").unwrap() ), 0);
+}
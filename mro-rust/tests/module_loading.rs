@@ -0,0 +1,59 @@
+// require findet die datei eines namensraums ueber den suchpfad (sonst MAL_PATH) und laedt sie
+// nur mit den noetigen capabilities; die dateien liegen unter tests/modules
+
+extern crate mal;
+
+mod common;
+
+use std::path::PathBuf;
+
+use mal::{Interpreter, Backend};
+use mal::capabilities::{Capabilities, Capability};
+
+use common::eval;
+
+fn modules() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("modules")
+}
+
+fn assert_loads_from_search_path(backend: Backend) {
+    let interpreter = Interpreter::new().with_backend(backend).with_search_path(vec![modules()]);
+
+    eval(&interpreter, "(def! loads (atom 0))");
+    assert_eq!(eval(&interpreter, "(ns app.test (:require [app.util :as u :refer [format]]))"), "nil");
+    assert_eq!(eval(&interpreter, "(str (u/format 1) (format 2))"), "\"<1><2>\"");
+
+    // app.main laedt util.mal ein zweites mal nicht
+    assert_eq!(eval(&interpreter, "(require 'app.main) (app.main/greet 3)"), "\"<3>\"");
+    assert_eq!(eval(&interpreter, "@user/loads"), "1");
+    assert_eq!(eval(&interpreter, "*ns*"), "app.test");
+}
+
+#[test]
+fn require_loads_namespaces_from_search_path() {
+    assert_loads_from_search_path(Backend::Evaluator);
+}
+
+#[test]
+fn require_loads_namespaces_from_search_path_in_vm() {
+    assert_loads_from_search_path(Backend::Vm);
+}
+
+#[test]
+fn require_reports_circular_loads() {
+    let interpreter = Interpreter::new().with_search_path(vec![modules()]);
+    let cycle = modules().join("cycle").canonicalize().unwrap();
+
+    let expected = format!("error: require: circular load: {0}/a.mal -> {0}/b.mal -> {0}/a.mal", cycle.display());
+    assert_eq!(eval(&interpreter, "(require \"cycle/a.mal\")"), expected);
+    assert_eq!(eval(&interpreter, "*file*"), "nil");
+}
+
+#[test]
+fn require_needs_capabilities_only_to_load_files() {
+    let capabilities = Capabilities::all().without(Capability::FsRead);
+    let interpreter = Interpreter::with_capabilities(capabilities).with_search_path(vec![modules()]);
+
+    assert_eq!(eval(&interpreter, "(require 'app.util)"), "error: require: capability denied: fs-read");
+    assert_eq!(eval(&interpreter, "(ns other) (def! x 1) (ns user) (require '[other :as o]) o/x"), "1");
+}
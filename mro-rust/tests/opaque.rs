@@ -0,0 +1,87 @@
+// opake werte: rust-werte, die mal nur weiterreicht; gleich sind sie nur als derselbe wert
+
+extern crate mal;
+
+mod common;
+
+use std::any;
+use std::rc::Rc;
+
+use mal::Interpreter;
+use mal::common::{MalData, Opaque};
+use mal::convert::{FromMal, TypeMismatch};
+
+use common::eval;
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: i32,
+    y: i32
+}
+
+#[test]
+fn opaque_values_are_equal_by_identity() {
+    let interpreter = Interpreter::new();
+    let point = Opaque::new("point", Point { x: 1, y: 2 });
+
+    interpreter.define("p", point.clone());
+    interpreter.define("same", point);
+    interpreter.define("other", Opaque::new("point", Point { x: 1, y: 2 }));
+
+    assert_eq!(eval(&interpreter, "(= p p)"), "true");
+    assert_eq!(eval(&interpreter, "(= p same)"), "true");
+    assert_eq!(eval(&interpreter, "(= p other)"), "false");
+    assert_eq!(eval(&interpreter, "(= [p] [same])"), "true");
+}
+
+#[test]
+fn opaque_values_print_with_their_printer() {
+    let interpreter = Interpreter::new();
+    let point = Opaque::new("point", Point { x: 1, y: 2 });
+
+    interpreter.define("plain", point.clone());
+    interpreter.define("p", point.with_printer( |p: &Point| format!("#<point {} {}>", p.x, p.y) ));
+
+    assert_eq!(eval(&interpreter, "(pr-str plain)"), "\"#<point>\"");
+    assert_eq!(eval(&interpreter, "(pr-str p)"), "\"#<point 1 2>\"");
+    assert_eq!(eval(&interpreter, "(str [p])"), "\"[#<point 1 2>]\"");
+
+    // ein printer fuer einen anderen typ passt nicht zum wert
+    interpreter.define("q", Opaque::new("point", Point { x: 1, y: 2 }).with_printer( |s: &String| s.clone() ));
+    assert_eq!(eval(&interpreter, "(pr-str q)"), "\"#<opaque>\"");
+
+    // der printer aendert nichts an der identitaet
+    interpreter.define("plain2", point);
+    assert_eq!(eval(&interpreter, "(= p plain2)"), "true");
+}
+
+#[test]
+fn opaque_values_carry_metadata() {
+    let interpreter = Interpreter::new();
+    interpreter.define("p", Opaque::new("point", Point { x: 1, y: 2 }));
+
+    assert_eq!(eval(&interpreter, "(meta p)"), "nil");
+    assert_eq!(eval(&interpreter, "(meta (with-meta p {:unit \"cm\"}))"), "{:unit \"cm\"}");
+    assert_eq!(eval(&interpreter, "(= p (with-meta p {:unit \"cm\"}))"), "true");
+    assert_eq!(eval(&interpreter, "(type (with-meta p {:unit \"cm\"}))"), ":point");
+    assert_eq!(eval(&interpreter, "(meta p)"), "nil");
+}
+
+#[test]
+fn downcast_fails_for_other_types() {
+    let opaque = Opaque::new("point", Point { x: 1, y: 2 });
+
+    assert!(opaque.is::<Point>());
+    assert!(!opaque.is::<String>());
+    assert_eq!(opaque.downcast_ref::<Point>(), Some(&Point { x: 1, y: 2 }));
+    assert_eq!(opaque.downcast_ref::<String>(), None);
+    assert_eq!(opaque.downcast::<Point>(), Some(Rc::new(Point { x: 1, y: 2 })));
+    assert!(opaque.downcast::<i32>().is_none());
+
+    let value = MalData::Opaque(opaque);
+    assert!(Rc::<Point>::from_mal(&value).is_ok());
+    assert_eq!(Rc::<String>::from_mal(&value),
+               Err(TypeMismatch { expected: format!("an opaque {}", any::type_name::<String>()), got: "opaque" }));
+    assert_eq!(Rc::<Point>::from_mal(&MalData::Nil).map( |_| () ),
+               Err(TypeMismatch { expected: format!("an opaque {}", any::type_name::<Point>()), got: "nil" }));
+}
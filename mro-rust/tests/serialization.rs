@@ -0,0 +1,64 @@
+// serde-darstellung von MalData: getaggte werte, maps als paare, fehler fuer werte ohne
+// datendarstellung; nur mit dem feature "serde"
+
+#![cfg(feature = "serde")]
+
+extern crate mal;
+extern crate serde_json;
+
+use mal::Interpreter;
+use mal::common::MalData;
+use mal::reader::read_str;
+
+fn to_json(form: &str) -> String {
+    serde_json::to_string(&read_str(form).unwrap()).unwrap()
+}
+
+#[test]
+fn strings_symbols_and_keywords_stay_distinct() {
+    assert_eq!(to_json("\"a\""), r#"{"string":"a"}"#);
+    assert_eq!(to_json("a"), r#"{"symbol":"a"}"#);
+    assert_eq!(to_json(":a"), r#"{"keyword":"a"}"#);
+    assert_eq!(to_json("nil"), r#""nil""#);
+    assert_eq!(to_json("true"), r#""true""#);
+    assert_eq!(to_json("7"), r#"{"number":7}"#);
+    assert_eq!(to_json("(1 [2])"), r#"{"list":[{"number":1},{"vector":[{"number":2}]}]}"#);
+}
+
+#[test]
+fn maps_are_written_as_key_value_pairs() {
+    assert_eq!(to_json("{:a 1}"), r#"{"map":[[{"keyword":"a"},{"number":1}]]}"#);
+    assert_eq!(to_json("{1 \"one\"}"), r#"{"map":[[{"number":1},{"string":"one"}]]}"#);
+}
+
+#[test]
+fn values_survive_a_round_trip() {
+    let forms = ["nil", "false", "-3", "\"text\"", "sym", ":kw", "(1 (2 3) [4])",
+                 "{:a [1 \"s\" sym] \"k\" (1 2) 3 nil sym :v}"];
+
+    for form in forms.iter() {
+        let value = read_str(form).unwrap();
+        let json = serde_json::to_string(&value).unwrap();
+        let back: MalData = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(back, value, "round trip of {} via {}", form, json);
+    }
+}
+
+#[test]
+fn functions_and_atoms_cannot_be_serialized() {
+    let interpreter = Interpreter::new();
+
+    for ( form, type_name ) in [( "(fn* [x] x)", "fn" ), ( "+", "fn" ), ( "(atom 1)", "atom" )].iter() {
+        let value = interpreter.eval_str(form).unwrap();
+        let err = serde_json::to_string(&value).unwrap_err();
+
+        assert_eq!(err.to_string(), format!("cannot serialize a value of type {}", type_name));
+    }
+}
+
+#[test]
+fn unknown_tags_are_rejected() {
+    assert!(serde_json::from_str::<MalData>(r#"{"atom":1}"#).is_err());
+    assert!(serde_json::from_str::<MalData>(r#"{"map":[[{"list":[]},{"number":1}]]}"#).is_err());
+}